@@ -147,6 +147,7 @@ impl MysqlShim for Postgres {
                             column: t.name().to_owned(),
                             coltype: ct,
                             colflags: ColumnFlags::empty(),
+                            ..Default::default()
                         }
                     })
                     .collect();
@@ -161,6 +162,7 @@ impl MysqlShim for Postgres {
                             column: c.name().to_owned(),
                             coltype: ct,
                             colflags: ColumnFlags::empty(),
+                            ..Default::default()
                         }
                     })
                     .collect();
@@ -280,6 +282,7 @@ fn answer_rows(
                             column: c.name().to_owned(),
                             coltype: ct,
                             colflags: ColumnFlags::empty(),
+                            ..Default::default()
                         }
                     })
                     .collect();
@@ -0,0 +1,214 @@
+use crate::{Column, ErrorKind, QueryResultWriter};
+use std::borrow::Cow;
+use std::io;
+
+/// The collation MySQL reports for `binary` values.
+const BINARY_COLLATION: u16 = 63;
+
+/// The characters that Windows code page 1252 maps the bytes `0x80..=0x9F` to. Where cp1252 leaves
+/// a byte undefined, MySQL maps it to the C1 control character of the same value.
+const CP1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// A character set in which a client sends queries or expects text results.
+///
+/// The character sets of a connection are initially those of the collation the client sent in its
+/// handshake, and are updated when the client issues `SET NAMES`, `SET CHARACTER SET`, or assigns
+/// `character_set_client` or `character_set_results`. Character sets that are not known here are
+/// treated as UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CharacterSet {
+    /// `utf8mb4`, or its subset `utf8mb3` (also known as `utf8`).
+    Utf8,
+    /// `latin1`, which MySQL defines as Windows code page 1252 (cp1252) rather than ISO 8859-1.
+    Latin1,
+    /// `binary`. Queries must still be valid UTF-8 to be decoded, and text results are sent as
+    /// UTF-8.
+    Binary,
+}
+
+impl Default for CharacterSet {
+    fn default() -> Self {
+        CharacterSet::Utf8
+    }
+}
+
+impl CharacterSet {
+    /// The character set of the collation with the given id, if it is known.
+    pub fn from_collation(id: u16) -> Option<Self> {
+        match id {
+            33 | 76 | 83 | 192..=215 | 223 | 45 | 46 | 224..=247 | 255..=323 => {
+                Some(CharacterSet::Utf8)
+            }
+            5 | 8 | 15 | 31 | 47 | 48 | 49 | 94 => Some(CharacterSet::Latin1),
+            BINARY_COLLATION => Some(CharacterSet::Binary),
+            _ => None,
+        }
+    }
+
+    /// The character set with the given (case-insensitive) name, if it is known.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match &*name.to_ascii_lowercase() {
+            "utf8" | "utf8mb3" | "utf8mb4" => Some(CharacterSet::Utf8),
+            "latin1" | "cp1252" => Some(CharacterSet::Latin1),
+            "binary" => Some(CharacterSet::Binary),
+            _ => None,
+        }
+    }
+
+    /// The name MySQL uses for this character set.
+    pub fn name(self) -> &'static str {
+        match self {
+            CharacterSet::Utf8 => "utf8mb4",
+            CharacterSet::Latin1 => "latin1",
+            CharacterSet::Binary => "binary",
+        }
+    }
+
+    /// The id of the default collation of this character set.
+    pub fn collation(self) -> u16 {
+        match self {
+            CharacterSet::Utf8 => crate::myc::constants::UTF8_GENERAL_CI,
+            CharacterSet::Latin1 => 8,
+            CharacterSet::Binary => BINARY_COLLATION,
+        }
+    }
+
+    /// Decode `bytes` in this character set, or return `None` if they are not valid in it.
+    pub fn decode(self, bytes: &[u8]) -> Option<Cow<'_, str>> {
+        match self {
+            CharacterSet::Utf8 | CharacterSet::Binary => {
+                std::str::from_utf8(bytes).ok().map(Cow::Borrowed)
+            }
+            CharacterSet::Latin1 if bytes.is_ascii() => {
+                Some(Cow::Borrowed(std::str::from_utf8(bytes).unwrap()))
+            }
+            CharacterSet::Latin1 => Some(Cow::Owned(
+                bytes
+                    .iter()
+                    .map(|&b| match b {
+                        0x80..=0x9F => CP1252_HIGH[usize::from(b - 0x80)],
+                        _ => char::from(b),
+                    })
+                    .collect(),
+            )),
+        }
+    }
+
+    /// Encode `text` in this character set. Like MySQL, characters that cannot be represented are
+    /// replaced with `?`.
+    pub fn encode(self, text: &str) -> Cow<'_, [u8]> {
+        match self {
+            CharacterSet::Utf8 | CharacterSet::Binary => Cow::Borrowed(text.as_bytes()),
+            CharacterSet::Latin1 if text.is_ascii() => Cow::Borrowed(text.as_bytes()),
+            CharacterSet::Latin1 => Cow::Owned(
+                text.chars()
+                    .map(|c| match c as u32 {
+                        0x00..=0x7F | 0xA0..=0xFF => c as u8,
+                        _ => CP1252_HIGH
+                            .iter()
+                            .position(|&h| h == c)
+                            .map_or(b'?', |i| 0x80 + i as u8),
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Whether text must be re-encoded to be sent to a client that expects this character set.
+    pub(crate) fn transcodes(self) -> bool {
+        self == CharacterSet::Latin1
+    }
+
+    /// Describe `columns` the way they are sent to a client that expects text results in this
+    /// character set, or return `None` if they can be sent as they are.
+    pub(crate) fn describe(self, columns: &[Column]) -> Option<Vec<Column>> {
+        if !self.transcodes() {
+            return None;
+        }
+        Some(
+            columns
+                .iter()
+                .map(|c| match c.character_set {
+                    BINARY_COLLATION => c.clone(),
+                    _ => c.clone().with_character_set(self.collation()),
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The character sets of a connection.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Charsets {
+    /// `character_set_client`, in which queries are sent.
+    pub(crate) client: CharacterSet,
+    /// `character_set_results`, in which text results are expected.
+    pub(crate) results: CharacterSet,
+}
+
+impl Charsets {
+    pub(crate) fn new(charset: CharacterSet) -> Self {
+        Charsets {
+            client: charset,
+            results: charset,
+        }
+    }
+}
+
+/// The message MySQL sends along with `ER_INVALID_CHARACTER_STRING`.
+pub(crate) fn invalid_string(charset: CharacterSet) -> String {
+    format!("Invalid {} character string", charset.name())
+}
+
+/// Reply to a query that is not valid in the client's character set the way MySQL does.
+pub(crate) fn reject_query(results: QueryResultWriter<'_>) -> io::Result<()> {
+    let msg = invalid_string(results.client_character_set());
+    results.error(ErrorKind::ER_INVALID_CHARACTER_STRING, msg.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_knows_collations() {
+        assert_eq!(CharacterSet::from_collation(33), Some(CharacterSet::Utf8));
+        assert_eq!(CharacterSet::from_collation(255), Some(CharacterSet::Utf8));
+        assert_eq!(CharacterSet::from_collation(8), Some(CharacterSet::Latin1));
+        assert_eq!(CharacterSet::from_collation(63), Some(CharacterSet::Binary));
+        assert_eq!(CharacterSet::from_collation(28), None);
+        assert_eq!(CharacterSet::from_name("UTF8MB4"), Some(CharacterSet::Utf8));
+        assert_eq!(
+            CharacterSet::from_name("latin1"),
+            Some(CharacterSet::Latin1)
+        );
+        assert_eq!(CharacterSet::from_name("gbk"), None);
+    }
+
+    #[test]
+    fn it_decodes_latin1() {
+        let latin1 = CharacterSet::Latin1;
+        assert_eq!(latin1.decode(b"abc").unwrap(), "abc");
+        assert_eq!(latin1.decode(b"caf\xe9 \x80").unwrap(), "café €");
+        assert_eq!(latin1.decode(b"\x81\x9f").unwrap(), "\u{81}Ÿ");
+        assert!(CharacterSet::Utf8.decode(b"caf\xe9").is_none());
+        assert_eq!(
+            CharacterSet::Utf8.decode("café".as_bytes()).unwrap(),
+            "café"
+        );
+    }
+
+    #[test]
+    fn it_encodes_latin1() {
+        let latin1 = CharacterSet::Latin1;
+        assert_eq!(&*latin1.encode("abc"), b"abc");
+        assert_eq!(&*latin1.encode("café €"), b"caf\xe9 \x80");
+        assert_eq!(&*latin1.encode("日本"), b"??");
+        assert_eq!(&*CharacterSet::Utf8.encode("café"), "café".as_bytes());
+    }
+}
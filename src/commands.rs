@@ -1,10 +1,26 @@
 use crate::myc::constants::{CapabilityFlags, Command as CommandByte};
 
+/// Capability flag for `resultset_metadata` support, which `CapabilityFlags` does not know about.
+pub const CLIENT_OPTIONAL_RESULTSET_METADATA: u32 = 0x0200_0000;
+
+/// Capability flag for query attributes, which `CapabilityFlags` does not know about.
+pub const CLIENT_QUERY_ATTRIBUTES: u32 = 0x0800_0000;
+
+/// Flag of `COM_STMT_EXECUTE` indicating that a parameter count is sent even if the statement
+/// takes no parameters, for clients that negotiated `CLIENT_QUERY_ATTRIBUTES`.
+pub const PARAMETER_COUNT_AVAILABLE: u8 = 0x08;
+
+/// MariaDB extended capability flag for caching the column definitions of prepared statements.
+pub const MARIADB_CLIENT_CACHE_METADATA: u32 = 0x0000_0010;
+
 #[derive(Debug)]
 pub struct ClientHandshake<'a> {
     pub capabilities: CapabilityFlags,
+    pub optional_resultset_metadata: bool,
+    pub query_attributes: bool,
+    pub mariadb_capabilities: u32,
     maxps: u32,
-    collation: u16,
+    pub collation: u16,
     username: Option<&'a [u8]>,
 }
 
@@ -24,7 +40,15 @@ pub fn client_handshake(i: &[u8]) -> nom::IResult<&[u8], ClientHandshake<'_>> {
 
         let (i, maxps) = nom::number::complete::le_u32(i)?;
         let (i, collation) = nom::bytes::complete::take(1u8)(i)?;
-        let (i, _) = nom::bytes::complete::take(23u8)(i)?;
+        let (i, filler) = nom::bytes::complete::take(23u8)(i)?;
+
+        // MariaDB clients put their extended capabilities at the end of the filler, unless they
+        // claim to be MySQL clients
+        let mariadb_capabilities = if capabilities.contains(CapabilityFlags::CLIENT_LONG_PASSWORD) {
+            0
+        } else {
+            nom::number::complete::le_u32(&filler[19..])?.1
+        };
 
         let (i, username) = if !capabilities.contains(CapabilityFlags::CLIENT_SSL) {
             let (i, user) = nom::bytes::complete::take_until(&b"\0"[..])(i)?;
@@ -38,6 +62,9 @@ pub fn client_handshake(i: &[u8]) -> nom::IResult<&[u8], ClientHandshake<'_>> {
             i,
             ClientHandshake {
                 capabilities,
+                optional_resultset_metadata: cap & CLIENT_OPTIONAL_RESULTSET_METADATA != 0,
+                query_attributes: cap & CLIENT_QUERY_ATTRIBUTES != 0,
+                mariadb_capabilities,
                 maxps,
                 collation: u16::from(collation[0]),
                 username,
@@ -54,6 +81,9 @@ pub fn client_handshake(i: &[u8]) -> nom::IResult<&[u8], ClientHandshake<'_>> {
             i,
             ClientHandshake {
                 capabilities: CapabilityFlags::from_bits_truncate(cap as u32),
+                optional_resultset_metadata: false,
+                query_attributes: false,
+                mariadb_capabilities: 0,
                 maxps,
                 collation: 0,
                 username: Some(username),
@@ -71,6 +101,7 @@ pub enum Command<'a> {
     Init(&'a [u8]),
     Execute {
         stmt: u32,
+        flags: u8,
         params: &'a [u8],
     },
     SendLongData {
@@ -78,15 +109,24 @@ pub enum Command<'a> {
         param: u16,
         data: &'a [u8],
     },
+    ResetStatement(u32),
+    ResetConnection,
     Ping,
     Quit,
 }
 
 pub fn execute(i: &[u8]) -> nom::IResult<&[u8], Command<'_>> {
     let (i, stmt) = nom::number::complete::le_u32(i)?;
-    let (i, _flags) = nom::bytes::complete::take(1u8)(i)?;
+    let (i, flags) = nom::number::complete::le_u8(i)?;
     let (i, _iterations) = nom::number::complete::le_u32(i)?;
-    Ok((&[], Command::Execute { stmt, params: i }))
+    Ok((
+        &[],
+        Command::Execute {
+            stmt,
+            flags,
+            params: i,
+        },
+    ))
 }
 
 pub fn send_long_data(i: &[u8]) -> nom::IResult<&[u8], Command<'_>> {
@@ -135,6 +175,16 @@ pub fn parse(i: &[u8]) -> nom::IResult<&[u8], Command<'_>> {
             ),
             Command::Close,
         ),
+        map(
+            preceded(
+                tag(&[CommandByte::COM_STMT_RESET as u8]),
+                nom::number::complete::le_u32,
+            ),
+            Command::ResetStatement,
+        ),
+        map(tag(&[CommandByte::COM_RESET_CONNECTION as u8]), |_| {
+            Command::ResetConnection
+        }),
         map(tag(&[CommandByte::COM_QUIT as u8]), |_| Command::Quit),
         map(tag(&[CommandByte::COM_PING as u8]), |_| Command::Ping),
     ))(i)
@@ -176,6 +226,26 @@ mod tests {
         assert_eq!(handshake.maxps, 16777216);
     }
 
+    #[test]
+    fn it_parses_extended_capabilities() {
+        let data = &[
+            0x25, 0x00, 0x00, 0x01, 0x84, 0xa6, 0x3f, 0x0a, 0x00, 0x00, 0x00, 0x01, 0x21, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x6a, 0x6f, 0x6e, 0x00, 0x00,
+        ];
+        let r = Cursor::new(&data[..]);
+        let mut pr = PacketReader::new(r);
+        let (_, p) = pr.next().unwrap().unwrap();
+        let (_, handshake) = client_handshake(&p).unwrap();
+        assert!(handshake.optional_resultset_metadata);
+        assert!(handshake.query_attributes);
+        assert_eq!(
+            handshake.mariadb_capabilities,
+            MARIADB_CLIENT_CACHE_METADATA
+        );
+        assert_eq!(handshake.username.unwrap(), &b"jon"[..]);
+    }
+
     #[test]
     fn it_parses_request() {
         let data = &[
@@ -212,4 +282,12 @@ mod tests {
             Command::ListFields(&b"select @@version_comment limit 1"[..])
         );
     }
+
+    #[test]
+    fn it_parses_resets() {
+        let (_, cmd) = parse(&[0x1a, 0x05, 0x00, 0x00, 0x00]).unwrap();
+        assert_eq!(cmd, Command::ResetStatement(5));
+        let (_, cmd) = parse(&[0x1f]).unwrap();
+        assert_eq!(cmd, Command::ResetConnection);
+    }
 }
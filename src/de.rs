@@ -0,0 +1,361 @@
+//! Deserialization of prepared statement parameters with serde.
+
+use crate::{FromMysqlValue, ParamValue, Params, Value, ValueInner};
+use byteorder::{ByteOrder, LittleEndian};
+use serde::de::{self, DeserializeSeed, IntoDeserializer, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+use std::fmt;
+use std::io;
+
+/// An error produced while deserializing parameters or serializing rows.
+#[derive(Debug)]
+pub(crate) struct Error(pub(crate) String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+impl serde::ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error(e.to_string())
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e.0)
+    }
+}
+
+/// Deserializes all the parameters of an `EXECUTE` command as a sequence, so that the fields of
+/// structs and tuples map positionally to the statement's placeholders.
+pub(crate) struct ParamsDeserializer<'a> {
+    params: Params<'a>,
+    // the index of the next parameter
+    next: usize,
+}
+
+impl<'a> ParamsDeserializer<'a> {
+    pub(crate) fn new(params: Params<'a>) -> Self {
+        ParamsDeserializer { params, next: 0 }
+    }
+
+    fn end(mut self) -> Result<(), Error> {
+        let remaining = self.params.by_ref().count();
+        if remaining == 0 {
+            Ok(())
+        } else {
+            Err(Error(format!(
+                "expected {} parameters, got {}",
+                self.next,
+                self.next + remaining
+            )))
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ParamsDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        let value = visitor.visit_seq(&mut self)?;
+        self.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> SeqAccess<'de> for ParamsDeserializer<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        let ParamValue { value, .. } = match self.params.next() {
+            Some(p) => p,
+            None => return Ok(None),
+        };
+        self.next += 1;
+        seed.deserialize(ValueDeserializer(value))
+            .map(Some)
+            .map_err(|e| Error(format!("parameter {}: {}", self.next, e)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.params.size_hint().1
+    }
+}
+
+/// Deserializes a single parameter value.
+///
+/// Primitive types are converted the same way as by [`FromMysqlValue`], so that, e.g., numbers
+/// bound as text are parsed. Dates and times are presented as ISO 8601 strings, which is what
+/// `chrono` and `time` expect.
+struct ValueDeserializer<'a>(Value<'a>);
+
+impl<'a> ValueDeserializer<'a> {
+    fn get<T: FromMysqlValue<'a>>(self) -> Result<T, Error> {
+        Ok(T::from_mysql_value(self.0)?)
+    }
+}
+
+macro_rules! deserialize_with {
+    ($($method:ident => $visit:ident($t:ty)),* $(,)?) => {
+        $(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            visitor.$visit(self.get::<$t>()?)
+        }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0.into_inner() {
+            ValueInner::NULL => visitor.visit_none(),
+            ValueInner::Int(i) => visitor.visit_i64(i),
+            ValueInner::UInt(u) => visitor.visit_u64(u),
+            ValueInner::Double(f) => visitor.visit_f64(f),
+            ValueInner::Bytes(b) => match std::str::from_utf8(b) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => visitor.visit_borrowed_bytes(b),
+            },
+            // a zero date has no bytes at all
+            ValueInner::Date([]) => visitor.visit_borrowed_str("0000-00-00"),
+            ValueInner::Datetime([]) => visitor.visit_borrowed_str("0000-00-00T00:00:00"),
+            ValueInner::Date(b) => visitor.visit_string(datetime(b, false)?),
+            ValueInner::Datetime(b) => visitor.visit_string(datetime(b, true)?),
+            ValueInner::Time(b) => visitor.visit_string(time(b)?),
+        }
+    }
+
+    deserialize_with! {
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+        deserialize_f32 => visit_f32(f32),
+        deserialize_f64 => visit_f64(f64),
+        deserialize_bytes => visit_borrowed_bytes(&'de [u8]),
+        deserialize_byte_buf => visit_borrowed_bytes(&'de [u8]),
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0.into_inner() {
+            ValueInner::Date(_) | ValueInner::Datetime(_) | ValueInner::Time(_) => {
+                self.deserialize_any(visitor)
+            }
+            _ => visitor.visit_borrowed_str(self.get::<&str>()?),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // MySQL has no boolean type, and uses TINYINT(1) instead
+        visitor.visit_bool(self.get::<i64>()? != 0)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.0.is_null() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.0.is_null() {
+            visitor.visit_unit()
+        } else {
+            Err(de::Error::invalid_type(
+                de::Unexpected::Other("non-NULL value"),
+                &visitor,
+            ))
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        // unit variants, as stored in ENUM columns
+        visitor.visit_enum(self.get::<&str>()?.into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Format a binary-encoded `DATE`, `DATETIME` or `TIMESTAMP` the way ISO 8601 does.
+///
+/// Clients leave out the time of datetimes at midnight, so it is filled in if `with_time` is set.
+fn datetime(b: &[u8], with_time: bool) -> Result<String, Error> {
+    if !matches!(b.len(), 4 | 7 | 11) {
+        return Err(Error("invalid date".to_owned()));
+    }
+    let mut s = format!(
+        "{:04}-{:02}-{:02}",
+        LittleEndian::read_u16(&b[0..2]),
+        b[2],
+        b[3]
+    );
+    if b.len() > 4 {
+        s.push_str(&format!("T{:02}:{:02}:{:02}", b[4], b[5], b[6]));
+    } else if with_time {
+        s.push_str("T00:00:00");
+    }
+    if b.len() == 11 {
+        s.push_str(&format!(".{:06}", LittleEndian::read_u32(&b[7..11])));
+    }
+    Ok(s)
+}
+
+/// Format a binary-encoded `TIME` as `[-]HH:MM:SS[.ffffff]`.
+fn time(b: &[u8]) -> Result<String, Error> {
+    match b.len() {
+        0 => Ok("00:00:00".to_owned()),
+        8 | 12 => {
+            let sign = if b[0] == 1 { "-" } else { "" };
+            let hours = u64::from(LittleEndian::read_u32(&b[1..5])) * 24 + u64::from(b[5]);
+            let mut s = format!("{}{:02}:{:02}:{:02}", sign, hours, b[6], b[7]);
+            if b.len() == 12 {
+                s.push_str(&format!(".{:06}", LittleEndian::read_u32(&b[8..12])));
+            }
+            Ok(s)
+        }
+        _ => Err(Error("invalid time".to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::myc::constants::ColumnType;
+    use crate::params::statement;
+    use crate::ParamParser;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct InsertUser<'a> {
+        id: u32,
+        name: &'a str,
+        email: Option<String>,
+        admin: bool,
+        born: String,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Role {
+        Admin,
+        Guest,
+    }
+
+    #[test]
+    fn it_deserializes_structs() {
+        let mut stmt = statement(&[
+            ColumnType::MYSQL_TYPE_LONGLONG,
+            ColumnType::MYSQL_TYPE_VAR_STRING,
+            ColumnType::MYSQL_TYPE_VAR_STRING,
+            ColumnType::MYSQL_TYPE_VAR_STRING,
+            ColumnType::MYSQL_TYPE_DATETIME,
+        ]);
+        let mut input = vec![0x04, 0x00, 0x2a, 0, 0, 0, 0, 0, 0, 0, 0x03];
+        input.extend(b"jon");
+        input.extend(&[0x01, b'1']);
+        input.extend(&[0x07, 0xe4, 0x07, 0x02, 0x1d, 0x17, 0x3b, 0x3a]);
+
+        let (parser, _) = ParamParser::new(&input, 0, false, &mut stmt).unwrap();
+        let user: InsertUser<'_> = parser.deserialize().unwrap();
+        assert_eq!(
+            user,
+            InsertUser {
+                id: 42,
+                name: "jon",
+                email: None,
+                admin: true,
+                born: "2020-02-29T23:59:58".to_owned(),
+            }
+        );
+
+        let (parser, _) = ParamParser::new(&input, 0, false, &mut stmt).unwrap();
+        let e = parser.deserialize::<(u32, String)>().unwrap_err();
+        assert_eq!(e.to_string(), "expected 2 parameters, got 5");
+
+        let (parser, _) = ParamParser::new(&input, 0, false, &mut stmt).unwrap();
+        let e = parser
+            .deserialize::<(u32, u32, Option<u32>, u32, String)>()
+            .unwrap_err();
+        assert!(e.to_string().starts_with("parameter 2: "));
+    }
+
+    #[test]
+    fn it_deserializes_midnight_datetimes() {
+        let mut stmt = statement(&[ColumnType::MYSQL_TYPE_DATETIME, ColumnType::MYSQL_TYPE_DATE]);
+        let input = [
+            0x00, 0x00, 0x04, 0xe4, 0x07, 0x02, 0x1d, 0x04, 0xe4, 0x07, 0x02, 0x1d,
+        ];
+        let (parser, _) = ParamParser::new(&input, 0, false, &mut stmt).unwrap();
+        let (datetime, date): (String, String) = parser.deserialize().unwrap();
+        assert_eq!(datetime, "2020-02-29T00:00:00");
+        assert!(datetime.parse::<chrono::NaiveDateTime>().is_ok());
+        assert_eq!(date, "2020-02-29");
+    }
+
+    #[test]
+    fn it_deserializes_enums() {
+        let mut stmt = statement(&[ColumnType::MYSQL_TYPE_ENUM]);
+        let input = [0x00, 0x00, 0x05, b'g', b'u', b'e', b's', b't'];
+        let (parser, _) = ParamParser::new(&input, 0, false, &mut stmt).unwrap();
+        let (role,): (Role,) = parser.deserialize().unwrap();
+        assert_eq!(role, Role::Guest);
+    }
+}
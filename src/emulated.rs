@@ -0,0 +1,350 @@
+use crate::managed::{forward_shim, Statements};
+use crate::myc::constants::ColumnType;
+use crate::{
+    CharacterSet, Column, ErrorKind, MysqlShim, ParamParser, QueryResultWriter,
+    StatementMetaWriter, StatusFlags, ValueInner,
+};
+use byteorder::{ByteOrder, LittleEndian};
+use std::fmt::Write;
+
+/// Emulates prepared statements for a [`MysqlShim`](trait.MysqlShim.html) that only implements
+/// [`on_query`](trait.MysqlShim.html#tymethod.on_query).
+///
+/// Preparing a statement merely counts its `?` placeholders. When the statement is executed, the
+/// parameters are interpolated into the query as SQL literals, and the resulting query is handed
+/// to the wrapped shim's `on_query` (with a `QueryResultWriter` that speaks the binary protocol
+/// the client expects). The wrapped shim's prepared statement methods are never called.
+///
+/// ```no_run
+/// # use msql_srv::*;
+/// # use std::{io, net};
+/// # struct Backend;
+/// # impl MysqlShim for Backend {
+/// #     type Error = io::Error;
+/// #     fn on_query(&mut self, _: &str, results: QueryResultWriter) -> io::Result<()> {
+/// #         results.completed(0, 0)
+/// #     }
+/// # }
+/// # let stream = net::TcpListener::bind("127.0.0.1:0").unwrap().accept().unwrap().0;
+/// MysqlIntermediary::run_on_tcp(EmulatedStatements::new(Backend), stream).unwrap();
+/// ```
+pub struct EmulatedStatements<S> {
+    shim: S,
+    statements: Statements<EmulatedStatement>,
+}
+
+impl<S: MysqlShim> EmulatedStatements<S> {
+    /// Emulate prepared statements for `shim`.
+    pub fn new(shim: S) -> Self {
+        EmulatedStatements {
+            shim,
+            statements: Statements::new(),
+        }
+    }
+
+    /// The wrapped shim.
+    pub fn get_ref(&self) -> &S {
+        &self.shim
+    }
+
+    /// The wrapped shim.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.shim
+    }
+
+    /// Consume this adapter, and return the wrapped shim.
+    pub fn into_inner(self) -> S {
+        self.shim
+    }
+}
+
+struct EmulatedStatement {
+    query: String,
+    // byte offsets of the `?` placeholders in `query`
+    placeholders: Vec<usize>,
+}
+
+impl<S: MysqlShim> MysqlShim for EmulatedStatements<S> {
+    type Error = S::Error;
+
+    fn on_prepare(&mut self, query: &str, info: StatementMetaWriter<'_>) -> Result<(), S::Error> {
+        let stmt = EmulatedStatement {
+            query: query.to_owned(),
+            placeholders: placeholders(query),
+        };
+        let params: Vec<_> = (0..stmt.placeholders.len())
+            .map(|_| Column::new("", "?", ColumnType::MYSQL_TYPE_VAR_STRING))
+            .collect();
+        // the columns are only known once the query has run
+        self.statements
+            .prepare(info)
+            .reply(stmt, &params, &[])
+            .map_err(Into::into)
+    }
+
+    fn on_execute(
+        &mut self,
+        id: u32,
+        params: ParamParser<'_>,
+        results: QueryResultWriter<'_>,
+    ) -> Result<(), S::Error> {
+        let stmt = self.statements.get_mut(id)?;
+        let charset = results.client_character_set();
+        let no_backslash_escapes = results
+            .status_flags()
+            .contains(StatusFlags::SERVER_STATUS_NO_BACKSLASH_ESCAPES);
+
+        let mut query = String::with_capacity(stmt.query.len());
+        let mut last = 0;
+        for (&at, param) in stmt.placeholders.iter().zip(params) {
+            query.push_str(&stmt.query[last..at]);
+            let value = param.value.into_inner();
+            if !write_literal(&mut query, value, charset, no_backslash_escapes) {
+                return results
+                    .error(
+                        ErrorKind::ER_WRONG_ARGUMENTS,
+                        &b"Incorrect arguments to mysqld_stmt_execute"[..],
+                    )
+                    .map_err(Into::into);
+            }
+            last = at + 1;
+        }
+        query.push_str(&stmt.query[last..]);
+
+        self.shim.on_query(&query, results)
+    }
+
+    fn on_close(&mut self, id: u32) {
+        self.statements.remove(id);
+    }
+
+    fn on_reset(&mut self) {
+        // the intermediary closes every statement it knows of first; drop any that remain
+        self.statements.clear();
+        self.shim.on_reset()
+    }
+
+    forward_shim!();
+}
+
+/// Find the `?` placeholders in `query` that are not inside a string, quoted identifier or
+/// comment.
+fn placeholders(query: &str) -> Vec<usize> {
+    let q = query.as_bytes();
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < q.len() {
+        match q[i] {
+            b'?' => found.push(i),
+            quote @ b'\'' | quote @ b'"' | quote @ b'`' => {
+                i += 1;
+                while i < q.len() && q[i] != quote {
+                    if q[i] == b'\\' && quote != b'`' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            b'#' => {
+                while i < q.len() && q[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'-' if q.get(i + 1) == Some(&b'-')
+                && q.get(i + 2).map_or(true, u8::is_ascii_whitespace) =>
+            {
+                while i < q.len() && q[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if q.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < q.len() && !(q[i] == b'*' && q.get(i + 1) == Some(&b'/')) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    found
+}
+
+/// Write `value` to `query` as an SQL literal, or return `false` if it has no such
+/// representation.
+fn write_literal(
+    query: &mut String,
+    value: ValueInner<'_>,
+    charset: CharacterSet,
+    no_backslash_escapes: bool,
+) -> bool {
+    match value {
+        ValueInner::NULL => query.push_str("NULL"),
+        ValueInner::Int(i) => write!(query, "{}", i).unwrap(),
+        ValueInner::UInt(u) => write!(query, "{}", u).unwrap(),
+        ValueInner::Double(f) if f.is_finite() => write!(query, "{:?}", f).unwrap(),
+        ValueInner::Double(_) => return false,
+        // strings are sent in the client's character set
+        ValueInner::Bytes(b) => match charset.decode(b) {
+            Some(s) => write_string(query, &s, no_backslash_escapes),
+            None => {
+                query.push_str("X'");
+                for byte in b {
+                    write!(query, "{:02X}", byte).unwrap();
+                }
+                query.push('\'');
+            }
+        },
+        ValueInner::Date(b) => match b.len() {
+            0 => query.push_str("'0000-00-00'"),
+            4 | 7 | 11 => {
+                query.push('\'');
+                write_date(query, b);
+                query.push('\'');
+            }
+            _ => return false,
+        },
+        ValueInner::Datetime(b) => match b.len() {
+            0 => query.push_str("'0000-00-00 00:00:00'"),
+            4 | 7 | 11 => {
+                query.push('\'');
+                write_date(query, b);
+                let (h, m, s) = if b.len() > 4 {
+                    (b[4], b[5], b[6])
+                } else {
+                    (0, 0, 0)
+                };
+                write!(query, " {:02}:{:02}:{:02}", h, m, s).unwrap();
+                if b.len() == 11 {
+                    write!(query, ".{:06}", LittleEndian::read_u32(&b[7..11])).unwrap();
+                }
+                query.push('\'');
+            }
+            _ => return false,
+        },
+        ValueInner::Time(b) => match b.len() {
+            0 => query.push_str("'00:00:00'"),
+            8 | 12 => {
+                let sign = if b[0] == 1 { "-" } else { "" };
+                let hours = u64::from(LittleEndian::read_u32(&b[1..5])) * 24 + u64::from(b[5]);
+                write!(query, "'{}{:02}:{:02}:{:02}", sign, hours, b[6], b[7]).unwrap();
+                if b.len() == 12 {
+                    write!(query, ".{:06}", LittleEndian::read_u32(&b[8..12])).unwrap();
+                }
+                query.push('\'');
+            }
+            _ => return false,
+        },
+    }
+    true
+}
+
+fn write_date(query: &mut String, b: &[u8]) {
+    let year = LittleEndian::read_u16(&b[0..2]);
+    write!(query, "{:04}-{:02}-{:02}", year, b[2], b[3]).unwrap();
+}
+
+fn write_string(query: &mut String, s: &str, no_backslash_escapes: bool) {
+    query.push('\'');
+    for c in s.chars() {
+        match c {
+            '\'' if no_backslash_escapes => query.push_str("''"),
+            _ if no_backslash_escapes => query.push(c),
+            '\0' => query.push_str("\\0"),
+            '\n' => query.push_str("\\n"),
+            '\r' => query.push_str("\\r"),
+            '\x1a' => query.push_str("\\Z"),
+            '\'' | '"' | '\\' => {
+                query.push('\\');
+                query.push(c);
+            }
+            _ => query.push(c),
+        }
+    }
+    query.push('\'');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(value: ValueInner<'_>) -> String {
+        let mut s = String::new();
+        assert!(write_literal(&mut s, value, CharacterSet::Utf8, false));
+        s
+    }
+
+    #[test]
+    fn it_finds_placeholders() {
+        assert_eq!(placeholders("SELECT ?"), vec![7]);
+        assert_eq!(
+            placeholders("SELECT a FROM t WHERE a = ? AND b = ?"),
+            vec![26, 36]
+        );
+        assert_eq!(placeholders("SELECT '?', \"?\", `?`, ?"), vec![22]);
+        assert_eq!(placeholders(r"SELECT 'it\'s ?', ?"), vec![18]);
+        assert_eq!(placeholders("SELECT 'it''s ?', ?"), vec![18]);
+        assert_eq!(placeholders("SELECT ? -- ?\n, ?"), vec![7, 16]);
+        assert_eq!(placeholders("SELECT ? # ?\n, ?"), vec![7, 15]);
+        assert_eq!(placeholders("SELECT ? /* ? */, ?"), vec![7, 18]);
+        assert_eq!(placeholders("SELECT 1--?"), vec![10]);
+        assert_eq!(placeholders("SELECT '?"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn it_writes_literals() {
+        assert_eq!(literal(ValueInner::NULL), "NULL");
+        assert_eq!(literal(ValueInner::Int(-42)), "-42");
+        assert_eq!(literal(ValueInner::UInt(u64::MAX)), "18446744073709551615");
+        assert_eq!(literal(ValueInner::Double(1.5)), "1.5");
+        assert_eq!(literal(ValueInner::Double(3.0)), "3.0");
+        assert_eq!(
+            literal(ValueInner::Bytes(b"it's a \"quote\"\\\n")),
+            r#"'it\'s a \"quote\"\\\n'"#
+        );
+        assert_eq!(literal(ValueInner::Bytes(&[0xff, 0x00])), "X'FF00'");
+        assert_eq!(
+            literal(ValueInner::Date(&[0xe4, 0x07, 0x02, 0x1d])),
+            "'2020-02-29'"
+        );
+        assert_eq!(
+            literal(ValueInner::Datetime(&[
+                0xe4, 0x07, 0x02, 0x1d, 0x17, 0x3b, 0x3a, 0x40, 0xe2, 0x01, 0x00
+            ])),
+            "'2020-02-29 23:59:58.123456'"
+        );
+        assert_eq!(
+            literal(ValueInner::Time(&[
+                0x01, 0x01, 0x00, 0x00, 0x00, 0x02, 0x03, 0x04
+            ])),
+            "'-26:03:04'"
+        );
+
+        let mut s = String::new();
+        let utf8 = CharacterSet::Utf8;
+        assert!(write_literal(
+            &mut s,
+            ValueInner::Bytes(b"it's"),
+            utf8,
+            true
+        ));
+        assert_eq!(s, "'it''s'");
+        assert!(!write_literal(
+            &mut s,
+            ValueInner::Double(f64::NAN),
+            utf8,
+            false
+        ));
+
+        let mut s = String::new();
+        let latin1 = CharacterSet::Latin1;
+        assert!(write_literal(
+            &mut s,
+            ValueInner::Bytes(b"caf\xe9"),
+            latin1,
+            false
+        ));
+        assert_eq!(s, "'café'");
+    }
+}
@@ -42,18 +42,8 @@
 //!
 //!     fn on_query(&mut self, _: &str, results: QueryResultWriter) -> io::Result<()> {
 //!         let cols = [
-//!             Column {
-//!                 table: "foo".to_string(),
-//!                 column: "a".to_string(),
-//!                 coltype: ColumnType::MYSQL_TYPE_LONGLONG,
-//!                 colflags: ColumnFlags::empty(),
-//!             },
-//!             Column {
-//!                 table: "foo".to_string(),
-//!                 column: "b".to_string(),
-//!                 coltype: ColumnType::MYSQL_TYPE_STRING,
-//!                 colflags: ColumnFlags::empty(),
-//!             },
+//!             Column::new("foo", "a", ColumnType::MYSQL_TYPE_LONGLONG),
+//!             Column::new("foo", "b", ColumnType::MYSQL_TYPE_STRING),
 //!         ];
 //!
 //!         let mut rw = results.start(&cols)?;
@@ -101,22 +91,51 @@ use std::io::prelude::*;
 use std::iter;
 use std::net;
 
+use crate::charset::Charsets;
+use crate::writers::ColumnMetadata;
+use chrono::FixedOffset;
 use myc::constants::CapabilityFlags;
 pub use rustls::Certificate;
 
 pub use crate::myc::constants::{ColumnFlags, ColumnType, StatusFlags};
 
+mod charset;
 mod commands;
+#[cfg(feature = "serde")]
+mod de;
+mod emulated;
 mod errorcodes;
+mod managed;
 mod packet;
 mod params;
 mod resultset;
+#[cfg(feature = "serde")]
+mod ser;
+mod session;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod tls;
 mod value;
+mod warnings;
 mod writers;
 
 /// Meta-information abot a single column, used either to describe a prepared statement parameter
 /// or an output column.
+///
+/// Besides constructing a `Column` directly, one can be built from
+/// [`Column::new`](struct.Column.html#method.new) and the `with_*` methods. Since 0.10, a
+/// `Column` has more fields than `table`, `column`, `coltype` and `colflags`, so struct literals
+/// must either list them all or end with `..Default::default()`.
+///
+/// ```
+/// # use msql_srv::{Column, ColumnFlags, ColumnType};
+/// let price = Column::new("orders", "price", ColumnType::MYSQL_TYPE_NEWDECIMAL)
+///     .with_schema("shop")
+///     .with_flags(ColumnFlags::NOT_NULL_FLAG)
+///     .with_length(12)
+///     .with_decimals(2);
+/// assert_eq!(price.decimals, 2);
+/// ```
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Column {
     /// This column's associated table.
@@ -133,13 +152,153 @@ pub struct Column {
     ///
     /// Of particular interest are `ColumnFlags::UNSIGNED_FLAG` and `ColumnFlags::NOT_NULL_FLAG`.
     pub colflags: ColumnFlags,
+    /// The schema (database) of this column's table.
+    pub schema: String,
+    /// The original (un-aliased) name of this column's table.
+    pub org_table: String,
+    /// The original (un-aliased) name of this column.
+    pub org_column: String,
+    /// The id of the collation of this column's values.
+    ///
+    /// Defaults to 33 (`utf8_general_ci`). Binary columns, such as `BLOB`s and numbers, should
+    /// use 63 (`binary`), which is how clients tell them apart from text columns.
+    pub character_set: u16,
+    /// The maximum display length of this column's values (e.g., 36 for a `CHAR(36)`).
+    pub column_length: u32,
+    /// The number of digits after the decimal point for `DECIMAL` and fractional temporal
+    /// columns (e.g., 2 for a `DECIMAL(10,2)`).
+    pub decimals: u8,
+    /// The members of this column's `ENUM` or `SET`, if any.
+    ///
+    /// These are not sent to the client, but strings and sets written to the column are checked
+    /// against them, and sets are ordered like them.
+    pub members: Vec<String>,
+}
+
+impl Default for Column {
+    fn default() -> Self {
+        Column {
+            table: String::new(),
+            column: String::new(),
+            coltype: ColumnType::MYSQL_TYPE_VAR_STRING,
+            colflags: ColumnFlags::empty(),
+            schema: String::new(),
+            org_table: String::new(),
+            org_column: String::new(),
+            character_set: myc::constants::UTF8_GENERAL_CI,
+            column_length: 1024,
+            decimals: 0,
+            members: Vec::new(),
+        }
+    }
+}
+
+impl Column {
+    /// Create a column named `column` of type `coltype` in the (possibly empty) `table`.
+    ///
+    /// All other properties take their default values, and can be changed using the `with_*`
+    /// methods, except that `JSON`, `BIT` and `GEOMETRY` columns use the `binary` collation (63),
+    /// and `ENUM` and `SET` columns have the `ENUM_FLAG` and `SET_FLAG`, as they do in MySQL.
+    pub fn new<T: Into<String>, C: Into<String>>(table: T, column: C, coltype: ColumnType) -> Self {
+        let character_set = match coltype {
+            ColumnType::MYSQL_TYPE_JSON
+            | ColumnType::MYSQL_TYPE_BIT
+            | ColumnType::MYSQL_TYPE_GEOMETRY => CharacterSet::Binary.collation(),
+            _ => myc::constants::UTF8_GENERAL_CI,
+        };
+        let colflags = match coltype {
+            ColumnType::MYSQL_TYPE_ENUM => ColumnFlags::ENUM_FLAG,
+            ColumnType::MYSQL_TYPE_SET => ColumnFlags::SET_FLAG,
+            _ => ColumnFlags::empty(),
+        };
+        Column {
+            table: table.into(),
+            column: column.into(),
+            coltype,
+            colflags,
+            character_set,
+            ..Default::default()
+        }
+    }
+
+    /// Set the flags associated with this column.
+    pub fn with_flags(mut self, colflags: ColumnFlags) -> Self {
+        self.colflags = colflags;
+        self
+    }
+
+    /// Set the schema (database) of this column's table.
+    pub fn with_schema<S: Into<String>>(mut self, schema: S) -> Self {
+        self.schema = schema.into();
+        self
+    }
+
+    /// Set the original (un-aliased) name of this column's table.
+    pub fn with_org_table<S: Into<String>>(mut self, org_table: S) -> Self {
+        self.org_table = org_table.into();
+        self
+    }
+
+    /// Set the original (un-aliased) name of this column.
+    pub fn with_org_column<S: Into<String>>(mut self, org_column: S) -> Self {
+        self.org_column = org_column.into();
+        self
+    }
+
+    /// Set the id of the collation of this column's values.
+    pub fn with_character_set(mut self, character_set: u16) -> Self {
+        self.character_set = character_set;
+        self
+    }
+
+    /// Set the maximum display length of this column's values.
+    pub fn with_length(mut self, column_length: u32) -> Self {
+        self.column_length = column_length;
+        self
+    }
+
+    /// Set the number of digits after the decimal point for this column's values.
+    pub fn with_decimals(mut self, decimals: u8) -> Self {
+        self.decimals = decimals;
+        self
+    }
+
+    /// Set the members of this column's `ENUM`, and its `ENUM_FLAG`.
+    ///
+    /// MySQL sends `ENUM` columns as `MYSQL_TYPE_STRING` with this flag.
+    pub fn with_enum<S: AsRef<str>>(mut self, members: &[S]) -> Self {
+        self.members = members.iter().map(|m| m.as_ref().to_owned()).collect();
+        self.colflags.insert(ColumnFlags::ENUM_FLAG);
+        self
+    }
+
+    /// Set the members of this column's `SET`, and its `SET_FLAG`.
+    ///
+    /// MySQL sends `SET` columns as `MYSQL_TYPE_STRING` with this flag.
+    pub fn with_set<S: AsRef<str>>(mut self, members: &[S]) -> Self {
+        self.members = members.iter().map(|m| m.as_ref().to_owned()).collect();
+        self.colflags.insert(ColumnFlags::SET_FLAG);
+        self
+    }
 }
 
+pub use crate::charset::CharacterSet;
+pub use crate::emulated::EmulatedStatements;
 pub use crate::errorcodes::ErrorKind;
-pub use crate::params::{ParamParser, ParamValue, Params};
+pub use crate::managed::{ManagedStatements, MysqlStatementShim, StatementWriter};
+pub use crate::params::{FromMysqlParams, ParamParser, ParamValue, Params, QueryAttribute};
 pub use crate::resultset::{InitWriter, QueryResultWriter, RowWriter, StatementMetaWriter};
+pub use crate::session::SessionStateChange;
 pub use crate::tls::TlsConfig;
-pub use crate::value::{ToMysqlValue, Value, ValueInner};
+#[cfg(feature = "json")]
+pub use crate::value::Json;
+#[cfg(feature = "geo-types")]
+pub use crate::value::Spatial;
+pub use crate::value::{
+    Bit, FromMysqlValue, Hint, MysqlDate, MysqlDateTime, MysqlTime, OwnedValue, ToMysqlValue,
+    Value, ValueInner,
+};
+pub use crate::warnings::{Warning, WarningLevel};
 
 /// Implementors of this trait can be used to drive a MySQL-compatible database backend.
 pub trait MysqlShim {
@@ -153,36 +312,137 @@ pub trait MysqlShim {
     /// The provided [`StatementMetaWriter`](struct.StatementMetaWriter.html) should be used to
     /// notify the client of the statement id assigned to the prepared statement, as well as to
     /// give metadata about the types of parameters and returned columns.
-    fn on_prepare(&mut self, query: &str, info: StatementMetaWriter<'_>)
-        -> Result<(), Self::Error>;
+    ///
+    /// The default implementation replies that prepared statements are not supported. See
+    /// [`ManagedStatements`](struct.ManagedStatements.html) for a way to support them without
+    /// handing out statement ids yourself, or
+    /// [`EmulatedStatements`](struct.EmulatedStatements.html) to serve them through
+    /// [`on_query`](trait.MysqlShim.html#tymethod.on_query).
+    fn on_prepare(
+        &mut self,
+        _query: &str,
+        info: StatementMetaWriter<'_>,
+    ) -> Result<(), Self::Error> {
+        info.error(
+            ErrorKind::ER_UNSUPPORTED_PS,
+            "This command is not supported in the prepared statement protocol yet".as_bytes(),
+        )
+        .map_err(Into::into)
+    }
 
     /// Called when the client executes a previously prepared statement.
     ///
     /// Any parameters included with the client's command is given in `params`.
     /// A response to the query should be given using the provided
     /// [`QueryResultWriter`](struct.QueryResultWriter.html).
+    ///
+    /// The default implementation replies that there is no such statement.
     fn on_execute(
         &mut self,
         id: u32,
-        params: ParamParser<'_>,
+        _params: ParamParser<'_>,
         results: QueryResultWriter<'_>,
-    ) -> Result<(), Self::Error>;
+    ) -> Result<(), Self::Error> {
+        results
+            .error(
+                ErrorKind::ER_UNKNOWN_STMT_HANDLER,
+                format!(
+                    "Unknown prepared statement handler ({}) given to mysqld_stmt_execute",
+                    id
+                )
+                .as_bytes(),
+            )
+            .map_err(Into::into)
+    }
 
     /// Called when the client wishes to deallocate resources associated with a previously prepared
     /// statement.
-    fn on_close(&mut self, stmt: u32);
+    ///
+    /// The default implementation does nothing.
+    fn on_close(&mut self, _stmt: u32) {}
 
     /// Called when the client issues a query for immediate execution.
     ///
     /// Results should be returned using the given
     /// [`QueryResultWriter`](struct.QueryResultWriter.html).
+    ///
+    /// Queries that change the session's character sets (such as `SET NAMES`) or time zone
+    /// (`SET time_zone`) are passed on like any other, and the library keeps track of the change
+    /// unless the shim replies with an error. Named time zones other than `UTC` cannot be tracked,
+    /// and leave the tracked time zone as it was.
     fn on_query(&mut self, query: &str, results: QueryResultWriter<'_>) -> Result<(), Self::Error>;
 
+    /// Called when the client issues a query for immediate execution, with the query as sent by
+    /// the client.
+    ///
+    /// Override this to handle queries that are not text, such as those that embed binary
+    /// literals. The default implementation decodes `query` in the client's character set (see
+    /// [`QueryResultWriter::client_character_set`](struct.QueryResultWriter.html#method.client_character_set))
+    /// and passes it to [`on_query`](trait.MysqlShim.html#tymethod.on_query), or replies with
+    /// `ER_INVALID_CHARACTER_STRING` if it is not valid in that character set.
+    fn on_query_bytes(
+        &mut self,
+        query: &[u8],
+        results: QueryResultWriter<'_>,
+    ) -> Result<(), Self::Error> {
+        match results.client_character_set().decode(query) {
+            Some(query) => self.on_query(&query, results),
+            None => charset::reject_query(results).map_err(Into::into),
+        }
+    }
+
+    /// Called when the client issues `SHOW WARNINGS`, or `SHOW COUNT(*) WARNINGS` if `count_only`
+    /// is set.
+    ///
+    /// `warnings` holds the warnings that were sent along with the most recent resultset (see
+    /// [`QueryResultWriter::add_warning`](struct.QueryResultWriter.html#method.add_warning)). The
+    /// default implementation replies with those warnings the same way MySQL does.
+    fn on_show_warnings(
+        &mut self,
+        warnings: &[Warning],
+        count_only: bool,
+        results: QueryResultWriter<'_>,
+    ) -> Result<(), Self::Error> {
+        crate::warnings::show_warnings(warnings, count_only, results).map_err(Into::into)
+    }
+
+    /// Whether the library should keep track of transactions on behalf of this shim.
+    ///
+    /// If this returns `true`, queries that begin or end a transaction (`BEGIN`,
+    /// `START TRANSACTION`, `COMMIT`, `ROLLBACK`) or change `autocommit` (`SET autocommit = ...`)
+    /// update the connection's status flags before they are passed to
+    /// [`on_query`](trait.MysqlShim.html#tymethod.on_query), so that `SERVER_STATUS_IN_TRANS` and
+    /// `SERVER_STATUS_AUTOCOMMIT` are reported to the client correctly. If the shim replies to
+    /// such a query with an error, the status flags it had before are restored. Shims that need finer
+    /// control can instead use
+    /// [`QueryResultWriter::set_status_flags`](struct.QueryResultWriter.html#method.set_status_flags).
+    fn track_transactions(&self) -> bool {
+        false
+    }
+
+    /// Whether clients may attach query attributes to their queries and executions.
+    ///
+    /// If this returns `true`, `CLIENT_QUERY_ATTRIBUTES` is offered to clients, and the attributes
+    /// of those that accept it are made available through
+    /// [`QueryResultWriter::query_attributes`](struct.QueryResultWriter.html#method.query_attributes).
+    fn supports_query_attributes(&self) -> bool {
+        false
+    }
+
     /// Called when client switches database.
     fn on_init(&mut self, _: &str, _: InitWriter<'_>) -> Result<(), Self::Error> {
         Ok(())
     }
 
+    /// Called when the client resets its session with `COM_RESET_CONNECTION`.
+    ///
+    /// All of the client's prepared statements have been closed (see
+    /// [`on_close`](trait.MysqlShim.html#tymethod.on_close)) by the time this is called, and the
+    /// library's own session state (warnings, status flags, time zone, character sets and
+    /// `resultset_metadata`) has been reset to what it was after the handshake. Backends that keep
+    /// further per-session state should discard it here.
+    fn on_reset(&mut self) {}
+
     /// Provides the TLS configuration, if we want to support TLS.
     fn tls_config(&self) -> Option<&TlsConfig> {
         None
@@ -198,6 +458,18 @@ pub struct MysqlIntermediary<B> {
     shim: B,
     reader: packet::PacketReader,
     writer: packet::PacketWriter,
+    client_capabilities: CapabilityFlags,
+    last_warnings: Vec<Warning>,
+    status: StatusFlags,
+    // `resultset_metadata` if the client negotiated CLIENT_OPTIONAL_RESULTSET_METADATA
+    optional_metadata: Option<bool>,
+    cache_metadata: bool,
+    query_attributes: bool,
+    charsets: Charsets,
+    // the character sets the client chose in its handshake, restored when it resets its session
+    handshake_charsets: Charsets,
+    // the session time zone, as set with `SET time_zone`
+    time_zone: FixedOffset,
 }
 
 impl<B: MysqlShim> MysqlIntermediary<B> {
@@ -227,6 +499,8 @@ struct StatementData {
     long_data: HashMap<u16, Vec<u8>>,
     bound_types: Vec<(myc::constants::ColumnType, bool)>,
     params: u16,
+    // the column definitions last sent to the client
+    columns: Vec<Column>,
 }
 
 impl<B: MysqlShim> MysqlIntermediary<B> {
@@ -243,6 +517,15 @@ impl<B: MysqlShim> MysqlIntermediary<B> {
             shim,
             reader: r,
             writer: w,
+            client_capabilities: CapabilityFlags::empty(),
+            last_warnings: Vec::new(),
+            status: StatusFlags::SERVER_STATUS_AUTOCOMMIT,
+            optional_metadata: None,
+            cache_metadata: false,
+            query_attributes: false,
+            charsets: Charsets::default(),
+            handshake_charsets: Charsets::default(),
+            time_zone: session::utc(),
         };
         mi = mi.init()?;
         mi.run()
@@ -262,47 +545,29 @@ impl<B: MysqlShim> MysqlIntermediary<B> {
         }
         self.writer.write_all(capabilities)?;
         self.writer.write_all(&[0x21])?; // UTF8_GENERAL_CI
-        self.writer.write_all(&[0x00, 0x00])?; // status flags
-        self.writer.write_all(&[0x00, 0x00])?; // extended capabilities
+        self.writer.write_all(&self.status.bits().to_le_bytes())?; // status flags
+
+        // extended capabilities: multi-results, PS multi-results, session tracking, optional
+        // metadata
+        let extended_capabilities = &mut [0x86, 0x02];
+        if self.shim.supports_query_attributes() {
+            extended_capabilities[1] |= 0x08; // query attributes
+        }
+        self.writer.write_all(extended_capabilities)?;
         self.writer.write_all(&[0x00])?; // no plugins
         self.writer.write_all(&[0x00; 6][..])?; // filler
-        self.writer.write_all(&[0x00; 4][..])?; // filler
+        self.writer
+            .write_all(&commands::MARIADB_CLIENT_CACHE_METADATA.to_le_bytes())?; // MariaDB extended capabilities
         self.writer.write_all(&b">o6^Wz!/kM}N\0"[..])?; // 4.1+ servers must extend salt
         self.writer.flush()?;
 
         {
-            let (seq, handshake) = self.reader.next()?.ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::ConnectionAborted,
-                    "peer terminated connection",
-                )
-            })?;
-            let handshake = commands::client_handshake(&handshake)
-                .map_err(|e| match e {
-                    nom::Err::Incomplete(_) => io::Error::new(
-                        io::ErrorKind::UnexpectedEof,
-                        "client sent incomplete handshake",
-                    ),
-                    nom::Err::Failure((input, nom_e_kind))
-                    | nom::Err::Error((input, nom_e_kind)) => {
-                        if let nom::error::ErrorKind::Eof = nom_e_kind {
-                            io::Error::new(
-                                io::ErrorKind::UnexpectedEof,
-                                format!("client did not complete handshake; got {:?}", input),
-                            )
-                        } else {
-                            io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                format!("bad client handshake; got {:?} ({:?})", input, nom_e_kind),
-                            )
-                        }
-                    }
-                })?
-                .1;
+            self.read_handshake()?;
 
-            self.writer.set_seq(seq + 1);
-
-            if handshake.capabilities.contains(CapabilityFlags::CLIENT_SSL) {
+            if self
+                .client_capabilities
+                .contains(CapabilityFlags::CLIENT_SSL)
+            {
                 let config = self.shim.tls_config().ok_or_else(|| {
                     io::Error::new(
                         io::ErrorKind::InvalidData,
@@ -322,38 +587,7 @@ impl<B: MysqlShim> MysqlIntermediary<B> {
                 self.reader.r = Box::new(stream1);
                 self.writer.w = Box::new(stream2);
 
-                let (seq, handshake) = self.reader.next()?.ok_or_else(|| {
-                    io::Error::new(
-                        io::ErrorKind::ConnectionAborted,
-                        "peer terminated connection",
-                    )
-                })?;
-                let _handshake = commands::client_handshake(&handshake)
-                    .map_err(|e| match e {
-                        nom::Err::Incomplete(_) => io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "client sent incomplete handshake",
-                        ),
-                        nom::Err::Failure((input, nom_e_kind))
-                        | nom::Err::Error((input, nom_e_kind)) => {
-                            if let nom::error::ErrorKind::Eof = nom_e_kind {
-                                io::Error::new(
-                                    io::ErrorKind::UnexpectedEof,
-                                    format!("client did not complete handshake; got {:?}", input),
-                                )
-                            } else {
-                                io::Error::new(
-                                    io::ErrorKind::InvalidData,
-                                    format!(
-                                        "bad client handshake; got {:?} ({:?})",
-                                        input, nom_e_kind
-                                    ),
-                                )
-                            }
-                        }
-                    })?
-                    .1;
-                self.writer.set_seq(seq + 1);
+                self.read_handshake()?;
 
                 if let Some(certs) = stream3.client_certs()? {
                     self.shim.after_tls_handshake(&certs);
@@ -374,12 +608,59 @@ impl<B: MysqlShim> MysqlIntermediary<B> {
             }
         }
 
-        writers::write_ok_packet(&mut self.writer, 0, 0, StatusFlags::empty())?;
+        writers::write_ok_packet(
+            &mut self.writer,
+            self.client_capabilities,
+            writers::OkPacket::new(0, 0, self.status),
+        )?;
         self.writer.flush()?;
 
         Ok(self)
     }
 
+    /// Read the client's handshake response, and settle the capabilities and character sets of
+    /// the connection from it.
+    fn read_handshake(&mut self) -> Result<(), B::Error> {
+        let (seq, handshake) = self.reader.next()?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "peer terminated connection",
+            )
+        })?;
+        let handshake = commands::client_handshake(&handshake)
+            .map_err(|e| match e {
+                nom::Err::Incomplete(_) => io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "client sent incomplete handshake",
+                ),
+                nom::Err::Failure((input, nom_e_kind)) | nom::Err::Error((input, nom_e_kind)) => {
+                    if let nom::error::ErrorKind::Eof = nom_e_kind {
+                        io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            format!("client did not complete handshake; got {:?}", input),
+                        )
+                    } else {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("bad client handshake; got {:?} ({:?})", input, nom_e_kind),
+                        )
+                    }
+                }
+            })?
+            .1;
+
+        self.writer.set_seq(seq + 1);
+        self.client_capabilities = handshake.capabilities;
+        self.optional_metadata = Some(true).filter(|_| handshake.optional_resultset_metadata);
+        self.cache_metadata =
+            handshake.mariadb_capabilities & commands::MARIADB_CLIENT_CACHE_METADATA != 0;
+        self.query_attributes = handshake.query_attributes && self.shim.supports_query_attributes();
+        self.charsets =
+            Charsets::new(CharacterSet::from_collation(handshake.collation).unwrap_or_default());
+        self.handshake_charsets = self.charsets;
+        Ok(())
+    }
+
     fn run(mut self) -> Result<(), B::Error> {
         use crate::commands::Command;
 
@@ -389,66 +670,220 @@ impl<B: MysqlShim> MysqlIntermediary<B> {
             let cmd = commands::parse(&packet).unwrap().1;
             match cmd {
                 Command::Query(q) => {
+                    let (attributes, q) = if self.query_attributes {
+                        match params::query_attributes(q) {
+                            Ok(split) => split,
+                            Err(_) => {
+                                writers::write_err(
+                                    ErrorKind::ER_MALFORMED_PACKET,
+                                    b"Malformed communication packet.",
+                                    &mut self.writer,
+                                )?;
+                                self.writer.flush()?;
+                                continue;
+                            }
+                        }
+                    } else {
+                        (Vec::new(), q)
+                    };
                     if q.starts_with(b"SELECT @@") || q.starts_with(b"select @@") {
-                        let w = QueryResultWriter::new(&mut self.writer, false);
+                        // these do not affect the warnings of the previous statement
+                        let mut scratch = Vec::new();
+                        let metadata =
+                            ColumnMetadata::for_query(self.optional_metadata, self.cache_metadata);
+                        let w = QueryResultWriter::new(
+                            &mut self.writer,
+                            false,
+                            self.client_capabilities,
+                            &mut scratch,
+                            &mut self.status,
+                            metadata,
+                            self.charsets,
+                        );
                         let var = &q[b"SELECT @@".len()..];
                         match var {
                             b"max_allowed_packet" => {
-                                let cols = &[Column {
-                                    table: String::new(),
-                                    column: "@@max_allowed_packet".to_owned(),
-                                    coltype: myc::constants::ColumnType::MYSQL_TYPE_LONG,
-                                    colflags: myc::constants::ColumnFlags::UNSIGNED_FLAG,
-                                }];
+                                let cols = &[Column::new(
+                                    "",
+                                    "@@max_allowed_packet",
+                                    myc::constants::ColumnType::MYSQL_TYPE_LONG,
+                                )
+                                .with_flags(myc::constants::ColumnFlags::UNSIGNED_FLAG)];
                                 let mut w = w.start(cols)?;
                                 w.write_row(iter::once(67108864u32))?;
                                 w.finish()?;
                             }
+                            b"warning_count" => {
+                                let cols = &[Column::new(
+                                    "",
+                                    "@@warning_count",
+                                    myc::constants::ColumnType::MYSQL_TYPE_LONGLONG,
+                                )
+                                .with_flags(myc::constants::ColumnFlags::UNSIGNED_FLAG)];
+                                let mut w = w.start(cols)?;
+                                w.write_row(iter::once(warnings::warning_count(
+                                    &self.last_warnings,
+                                )))?;
+                                w.finish()?;
+                            }
                             _ => {
                                 w.completed(0, 0)?;
                             }
                         }
                     } else if q.starts_with(b"USE ") || q.starts_with(b"use ") {
-                        let w = InitWriter {
-                            writer: &mut self.writer,
+                        let w = InitWriter::new(
+                            &mut self.writer,
+                            self.client_capabilities,
+                            self.status,
+                        );
+                        match self.charsets.client.decode(&q[b"USE ".len()..]) {
+                            Some(schema) => {
+                                let schema = schema.trim().trim_end_matches(';').trim_matches('`');
+                                self.shim.on_init(schema, w)?;
+                            }
+                            None => w.error(
+                                ErrorKind::ER_INVALID_CHARACTER_STRING,
+                                charset::invalid_string(self.charsets.client).as_bytes(),
+                            )?,
+                        }
+                    } else if let Some(count_only) = warnings::is_show_warnings(q) {
+                        // like in MySQL, SHOW WARNINGS does not reset the warnings it shows
+                        let mut scratch = Vec::new();
+                        let metadata =
+                            ColumnMetadata::for_query(self.optional_metadata, self.cache_metadata);
+                        let w = QueryResultWriter::new(
+                            &mut self.writer,
+                            false,
+                            self.client_capabilities,
+                            &mut scratch,
+                            &mut self.status,
+                            metadata,
+                            self.charsets,
+                        );
+                        self.shim
+                            .on_show_warnings(&self.last_warnings, count_only, w)?;
+                    } else if let (Some(_), Some(value)) = (
+                        self.optional_metadata,
+                        session::resultset_metadata_assignment(q),
+                    ) {
+                        let full = match &*value {
+                            "full" => Some(true),
+                            "none" => Some(false),
+                            _ => None,
                         };
-                        let schema = ::std::str::from_utf8(&q[b"USE ".len()..])
-                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-                        let schema = schema.trim().trim_end_matches(';').trim_matches('`');
-                        self.shim.on_init(&schema, w)?;
+                        if let Some(full) = full {
+                            self.optional_metadata = Some(full);
+                            writers::write_ok_packet(
+                                &mut self.writer,
+                                self.client_capabilities,
+                                writers::OkPacket::new(0, 0, self.status),
+                            )?;
+                        } else {
+                            writers::write_err(
+                                ErrorKind::ER_WRONG_VALUE_FOR_VAR,
+                                format!(
+                                    "Variable 'resultset_metadata' can't be set to the value of '{}'",
+                                    value
+                                )
+                                .as_bytes(),
+                                &mut self.writer,
+                            )?;
+                        }
                     } else {
-                        let w = QueryResultWriter::new(&mut self.writer, false);
-                        self.shim.on_query(
-                            ::std::str::from_utf8(q)
-                                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
-                            w,
-                        )?;
+                        // like in MySQL, a statement that fails does not change the session, so
+                        // the tracked changes are undone if the shim replies with an error
+                        let (status, charsets, time_zone) =
+                            (self.status, self.charsets, self.time_zone);
+                        if self.shim.track_transactions() {
+                            session::track_transaction(q, &mut self.status);
+                        }
+                        session::track_character_sets(q, &mut self.charsets);
+                        session::track_time_zone(q, &mut self.time_zone);
+                        let mut failed = false;
+                        let metadata =
+                            ColumnMetadata::for_query(self.optional_metadata, self.cache_metadata);
+                        let mut w = QueryResultWriter::new(
+                            &mut self.writer,
+                            false,
+                            self.client_capabilities,
+                            &mut self.last_warnings,
+                            &mut self.status,
+                            metadata,
+                            self.charsets,
+                        );
+                        w.query_attributes = attributes;
+                        w.time_zone = self.time_zone;
+                        w.failed = Some(&mut failed);
+                        self.shim.on_query_bytes(q, w)?;
+                        if failed {
+                            if self.shim.track_transactions() {
+                                self.status = status;
+                            }
+                            self.charsets = charsets;
+                            self.time_zone = time_zone;
+                        }
                     }
                 }
                 Command::Prepare(q) => {
                     let w = StatementMetaWriter {
                         writer: &mut self.writer,
                         stmts: &mut stmts,
+                        status: self.status,
+                        optional_metadata: self.optional_metadata,
+                        results_charset: self.charsets.results,
                     };
 
-                    self.shim.on_prepare(
-                        ::std::str::from_utf8(q)
-                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
-                        w,
-                    )?;
+                    match self.charsets.client.decode(q) {
+                        Some(q) => self.shim.on_prepare(&q, w)?,
+                        None => w.error(
+                            ErrorKind::ER_INVALID_CHARACTER_STRING,
+                            charset::invalid_string(self.charsets.client).as_bytes(),
+                        )?,
+                    }
                 }
-                Command::Execute { stmt, params } => {
+                Command::Execute {
+                    stmt,
+                    flags,
+                    params,
+                } => {
                     let state = stmts.get_mut(&stmt).ok_or_else(|| {
                         io::Error::new(
                             io::ErrorKind::InvalidData,
                             format!("asked to execute unknown statement {}", stmt),
                         )
                     })?;
+                    let mut columns = std::mem::take(&mut state.columns);
                     {
-                        let params = params::ParamParser::new(params, state);
-                        let w = QueryResultWriter::new(&mut self.writer, true);
-                        self.shim.on_execute(stmt, params, w)?;
+                        let metadata = if self.cache_metadata {
+                            ColumnMetadata::Cached(&mut columns)
+                        } else {
+                            ColumnMetadata::for_query(self.optional_metadata, self.cache_metadata)
+                        };
+                        let params =
+                            params::ParamParser::new(params, flags, self.query_attributes, state);
+                        if let Ok((mut params, attributes)) = params {
+                            params.time_zone = self.time_zone;
+                            let mut w = QueryResultWriter::new(
+                                &mut self.writer,
+                                true,
+                                self.client_capabilities,
+                                &mut self.last_warnings,
+                                &mut self.status,
+                                metadata,
+                                self.charsets,
+                            );
+                            w.query_attributes = attributes;
+                            w.time_zone = self.time_zone;
+                            self.shim.on_execute(stmt, params, w)?;
+                        } else {
+                            writers::write_err(
+                                ErrorKind::ER_WRONG_ARGUMENTS,
+                                b"Incorrect arguments to mysqld_stmt_execute",
+                                &mut self.writer,
+                            )?;
+                        }
                     }
+                    state.columns = columns;
                     state.long_data.clear();
                 }
                 Command::SendLongData { stmt, param, data } => {
@@ -470,27 +905,76 @@ impl<B: MysqlShim> MysqlIntermediary<B> {
                     stmts.remove(&stmt);
                     // NOTE: spec dictates no response from server
                 }
+                Command::ResetStatement(stmt) => match stmts.get_mut(&stmt) {
+                    Some(state) => {
+                        state.long_data.clear();
+                        writers::write_ok_packet(
+                            &mut self.writer,
+                            self.client_capabilities,
+                            writers::OkPacket::new(0, 0, self.status),
+                        )?;
+                    }
+                    None => {
+                        let msg = format!(
+                            "Unknown prepared statement handler ({}) given to mysqld_stmt_reset",
+                            stmt
+                        );
+                        writers::write_err(
+                            ErrorKind::ER_UNKNOWN_STMT_HANDLER,
+                            msg.as_bytes(),
+                            &mut self.writer,
+                        )?;
+                    }
+                },
+                Command::ResetConnection => {
+                    for (stmt, _) in stmts.drain() {
+                        self.shim.on_close(stmt);
+                    }
+                    self.last_warnings.clear();
+                    self.status = StatusFlags::SERVER_STATUS_AUTOCOMMIT;
+                    self.time_zone = session::utc();
+                    self.charsets = self.handshake_charsets;
+                    // `resultset_metadata` goes back to FULL
+                    self.optional_metadata = self.optional_metadata.map(|_| true);
+                    self.shim.on_reset();
+                    writers::write_ok_packet(
+                        &mut self.writer,
+                        self.client_capabilities,
+                        writers::OkPacket::new(0, 0, self.status),
+                    )?;
+                }
                 Command::ListFields(_) => {
-                    let cols = &[Column {
-                        table: String::new(),
-                        column: "not implemented".to_owned(),
-                        coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-                        colflags: myc::constants::ColumnFlags::UNSIGNED_FLAG,
-                    }];
-                    writers::write_column_definitions(cols, &mut self.writer, true, true)?;
+                    let cols = &[Column::new(
+                        "",
+                        "not implemented",
+                        myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+                    )
+                    .with_flags(myc::constants::ColumnFlags::UNSIGNED_FLAG)];
+                    writers::write_column_definitions(
+                        cols,
+                        &mut self.writer,
+                        true,
+                        true,
+                        self.status,
+                    )?;
                 }
                 Command::Init(schema) => {
-                    let w = InitWriter {
-                        writer: &mut self.writer,
-                    };
-                    self.shim.on_init(
-                        ::std::str::from_utf8(schema)
-                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
-                        w,
-                    )?;
+                    let w =
+                        InitWriter::new(&mut self.writer, self.client_capabilities, self.status);
+                    match self.charsets.client.decode(schema) {
+                        Some(schema) => self.shim.on_init(&schema, w)?,
+                        None => w.error(
+                            ErrorKind::ER_INVALID_CHARACTER_STRING,
+                            charset::invalid_string(self.charsets.client).as_bytes(),
+                        )?,
+                    }
                 }
                 Command::Ping => {
-                    writers::write_ok_packet(&mut self.writer, 0, 0, StatusFlags::empty())?;
+                    writers::write_ok_packet(
+                        &mut self.writer,
+                        self.client_capabilities,
+                        writers::OkPacket::new(0, 0, self.status),
+                    )?;
                 }
                 Command::Quit => {
                     break;
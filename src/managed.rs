@@ -0,0 +1,293 @@
+use crate::{Column, ErrorKind, MysqlShim, ParamParser, QueryResultWriter, StatementMetaWriter};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::io;
+
+/// Implements the [`MysqlShim`](trait.MysqlShim.html) methods that have nothing to do with
+/// prepared statements by handing them to `self.shim`, for adapters that handle prepared
+/// statements on behalf of the shim they wrap.
+macro_rules! forward_shim {
+    () => {
+        fn on_query(
+            &mut self,
+            query: &str,
+            results: $crate::QueryResultWriter<'_>,
+        ) -> Result<(), Self::Error> {
+            self.shim.on_query(query, results)
+        }
+
+        fn on_query_bytes(
+            &mut self,
+            query: &[u8],
+            results: $crate::QueryResultWriter<'_>,
+        ) -> Result<(), Self::Error> {
+            self.shim.on_query_bytes(query, results)
+        }
+
+        fn on_show_warnings(
+            &mut self,
+            warnings: &[$crate::Warning],
+            count_only: bool,
+            results: $crate::QueryResultWriter<'_>,
+        ) -> Result<(), Self::Error> {
+            self.shim.on_show_warnings(warnings, count_only, results)
+        }
+
+        fn track_transactions(&self) -> bool {
+            self.shim.track_transactions()
+        }
+
+        fn supports_query_attributes(&self) -> bool {
+            self.shim.supports_query_attributes()
+        }
+
+        fn on_init(
+            &mut self,
+            schema: &str,
+            writer: $crate::InitWriter<'_>,
+        ) -> Result<(), Self::Error> {
+            self.shim.on_init(schema, writer)
+        }
+
+        fn tls_config(&self) -> Option<&$crate::TlsConfig> {
+            self.shim.tls_config()
+        }
+
+        fn after_tls_handshake(&mut self, client_certs: &[$crate::Certificate]) {
+            self.shim.after_tls_handshake(client_certs)
+        }
+    };
+}
+pub(crate) use forward_shim;
+
+/// An extension of [`MysqlShim`](trait.MysqlShim.html) for backends that want the library to
+/// keep track of their prepared statements.
+///
+/// Rather than inventing statement identifiers and keeping a map from them to their own state,
+/// implementors store a `Statement` of their choosing (e.g., a parsed query plan) when a statement
+/// is prepared, and are handed that `Statement` back whenever it is executed. To serve clients
+/// with such a backend, wrap it in [`ManagedStatements`](struct.ManagedStatements.html), which
+/// calls the methods of this trait instead of the prepared statement methods of `MysqlShim`, and
+/// passes everything else on to the backend's `MysqlShim` implementation.
+pub trait MysqlStatementShim: MysqlShim {
+    /// The state kept for each prepared statement.
+    type Statement;
+
+    /// Called when the client issues a request to prepare `query` for later execution.
+    ///
+    /// The provided [`StatementWriter`](struct.StatementWriter.html) should be used to give
+    /// metadata about the types of parameters and returned columns, and to hand over the
+    /// `Statement` that is passed to later executions.
+    fn on_prepare(
+        &mut self,
+        query: &str,
+        info: StatementWriter<'_, Self::Statement>,
+    ) -> Result<(), Self::Error>;
+
+    /// Called when the client executes a previously prepared statement.
+    ///
+    /// Any parameters included with the client's command is given in `params`.
+    /// A response to the query should be given using the provided
+    /// [`QueryResultWriter`](struct.QueryResultWriter.html).
+    fn on_execute(
+        &mut self,
+        stmt: &mut Self::Statement,
+        params: ParamParser<'_>,
+        results: QueryResultWriter<'_>,
+    ) -> Result<(), Self::Error>;
+
+    /// Called when the client deallocates a previously prepared statement, or resets its session
+    /// (before [`MysqlShim::on_reset`](trait.MysqlShim.html#method.on_reset) is called).
+    ///
+    /// The default implementation simply drops it.
+    fn on_close(&mut self, _stmt: Self::Statement) {}
+}
+
+/// Prepared statements kept by the library, under the identifiers it hands out for them.
+pub(crate) struct Statements<T> {
+    by_id: HashMap<u32, T>,
+    next_id: u32,
+}
+
+impl<T> Statements<T> {
+    pub(crate) fn new() -> Self {
+        Statements {
+            by_id: HashMap::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Allocate an identifier for a statement that is being prepared, and a writer that keeps
+    /// the statement under it.
+    pub(crate) fn prepare<'a>(
+        &'a mut self,
+        info: StatementMetaWriter<'a>,
+    ) -> StatementWriter<'a, T> {
+        let id = self.allocate_id();
+        StatementWriter {
+            id,
+            info,
+            statements: &mut self.by_id,
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, id: u32) -> io::Result<&mut T> {
+        self.by_id.get_mut(&id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("asked to execute unknown statement {}", id),
+            )
+        })
+    }
+
+    pub(crate) fn remove(&mut self, id: u32) -> Option<T> {
+        self.by_id.remove(&id)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.by_id.clear()
+    }
+
+    pub(crate) fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        self.by_id.drain().map(|(_, stmt)| stmt)
+    }
+
+    fn allocate_id(&mut self) -> u32 {
+        // ids that are still in use are skipped, and 0 is never handed out
+        while self.next_id == 0 || self.by_id.contains_key(&self.next_id) {
+            self.next_id = self.next_id.wrapping_add(1);
+        }
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        id
+    }
+}
+
+/// Adapts a [`MysqlStatementShim`](trait.MysqlStatementShim.html) so that it can be served by a
+/// [`MysqlIntermediary`](struct.MysqlIntermediary.html).
+///
+/// Statement identifiers are allocated here, and each `Statement` lives until the client closes
+/// its statement, resets its session, or the connection ends.
+pub struct ManagedStatements<S: MysqlStatementShim> {
+    shim: S,
+    statements: Statements<S::Statement>,
+}
+
+impl<S: MysqlStatementShim> ManagedStatements<S> {
+    /// Manage the prepared statements of `shim`.
+    pub fn new(shim: S) -> Self {
+        ManagedStatements {
+            shim,
+            statements: Statements::new(),
+        }
+    }
+
+    /// The wrapped shim.
+    pub fn get_ref(&self) -> &S {
+        &self.shim
+    }
+
+    /// The wrapped shim.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.shim
+    }
+
+    /// Consume this adapter, and return the wrapped shim.
+    pub fn into_inner(self) -> S {
+        self.shim
+    }
+}
+
+impl<S: MysqlStatementShim> MysqlShim for ManagedStatements<S> {
+    type Error = S::Error;
+
+    fn on_prepare(&mut self, query: &str, info: StatementMetaWriter<'_>) -> Result<(), S::Error> {
+        let info = self.statements.prepare(info);
+        MysqlStatementShim::on_prepare(&mut self.shim, query, info)
+    }
+
+    fn on_execute(
+        &mut self,
+        id: u32,
+        params: ParamParser<'_>,
+        results: QueryResultWriter<'_>,
+    ) -> Result<(), S::Error> {
+        let stmt = self.statements.get_mut(id)?;
+        MysqlStatementShim::on_execute(&mut self.shim, stmt, params, results)
+    }
+
+    fn on_close(&mut self, id: u32) {
+        if let Some(stmt) = self.statements.remove(id) {
+            MysqlStatementShim::on_close(&mut self.shim, stmt);
+        }
+    }
+
+    fn on_reset(&mut self) {
+        // the intermediary closes every statement it knows of first; drop any that remain
+        for stmt in self.statements.drain() {
+            MysqlStatementShim::on_close(&mut self.shim, stmt);
+        }
+        self.shim.on_reset()
+    }
+
+    forward_shim!();
+}
+
+/// Convenience type for providing statement metadata to clients, and for handing the state of a
+/// newly prepared statement over to [`ManagedStatements`](struct.ManagedStatements.html).
+///
+/// This type should not be dropped without calling
+/// [`reply`](struct.StatementWriter.html#method.reply) or
+/// [`error`](struct.StatementWriter.html#method.error).
+#[must_use]
+pub struct StatementWriter<'a, T> {
+    id: u32,
+    info: StatementMetaWriter<'a>,
+    statements: &'a mut HashMap<u32, T>,
+}
+
+impl<'a, T> StatementWriter<'a, T> {
+    /// Reply to the client with the given meta-information, and keep `stmt` to be passed to
+    /// later executions of this statement.
+    ///
+    /// `params` is a set of [`Column`](struct.Column.html) descriptors for the parameters the
+    /// client must provide when executing the prepared statement. `columns` is a second set of
+    /// [`Column`](struct.Column.html) descriptors for the values that will be returned in each
+    /// row then the statement is later executed.
+    pub fn reply<'p, 'c, PI, CI>(self, stmt: T, params: PI, columns: CI) -> io::Result<()>
+    where
+        PI: IntoIterator<Item = &'p Column>,
+        CI: IntoIterator<Item = &'c Column>,
+        <PI as IntoIterator>::IntoIter: ExactSizeIterator,
+        <CI as IntoIterator>::IntoIter: ExactSizeIterator,
+    {
+        self.statements.insert(self.id, stmt);
+        self.info.reply(self.id, params, columns)
+    }
+
+    /// Reply to the client's `PREPARE` with an error.
+    pub fn error<E>(self, kind: ErrorKind, msg: &E) -> io::Result<()>
+    where
+        E: Borrow<[u8]> + ?Sized,
+    {
+        self.info.error(kind, msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_allocates_unused_ids() {
+        let mut statements = Statements::new();
+        assert_eq!(statements.allocate_id(), 1);
+        statements.by_id.insert(2, ());
+        assert_eq!(statements.allocate_id(), 3);
+
+        statements.next_id = u32::MAX;
+        assert_eq!(statements.allocate_id(), u32::MAX);
+        statements.by_id.insert(1, ());
+        assert_eq!(statements.allocate_id(), 3);
+    }
+}
@@ -1,30 +1,218 @@
+use crate::commands::PARAMETER_COUNT_AVAILABLE;
 use crate::myc;
-use crate::{StatementData, Value};
+use crate::myc::io::ReadMysqlExt;
+use crate::session;
+use crate::{FromMysqlValue, StatementData, Value};
+use chrono::FixedOffset;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io;
 
 /// A `ParamParser` decodes query parameters included in a client's `EXECUTE` command given
 /// type information for the expected parameters.
 ///
 /// Users should invoke [`iter`](struct.ParamParser.html#method.iter) method to iterate over the
 /// provided parameters.
+#[derive(Clone)]
 pub struct ParamParser<'a> {
     pub(crate) params: u16,
+    pub(crate) nullmap: &'a [u8],
     pub(crate) bytes: &'a [u8],
     pub(crate) long_data: &'a HashMap<u16, Vec<u8>>,
-    pub(crate) bound_types: &'a mut Vec<(myc::constants::ColumnType, bool)>,
+    pub(crate) bound_types: &'a [(myc::constants::ColumnType, bool)],
+    pub(crate) time_zone: FixedOffset,
 }
 
 impl<'a> ParamParser<'a> {
-    pub(crate) fn new(input: &'a [u8], stmt: &'a mut StatementData) -> Self {
-        ParamParser {
+    /// Parse the parameters of an `EXECUTE` of `stmt`, and any query attributes that follow them
+    /// if the client negotiated `CLIENT_QUERY_ATTRIBUTES`.
+    ///
+    /// Every value is checked to be decodable, so that iterating over the parameters will not
+    /// fail.
+    pub(crate) fn new(
+        input: &'a [u8],
+        flags: u8,
+        query_attributes: bool,
+        stmt: &'a mut StatementData,
+    ) -> io::Result<(Self, Vec<QueryAttribute<'a>>)> {
+        let mut input = input;
+        let count = if query_attributes
+            && (stmt.params > 0 || flags & PARAMETER_COUNT_AVAILABLE != 0)
+        {
+            u16::try_from(input.read_lenenc_int()?)
+                .ok()
+                .filter(|&count| count >= stmt.params)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad parameter count"))?
+        } else {
+            stmt.params
+        };
+        let header = Header::parse(input, count, query_attributes)?;
+
+        // newly bound types only replace the statement's once every value has been decoded
+        let attributes = {
+            let bound_types = header.bound_types.as_deref().unwrap_or(&stmt.bound_types);
+            let mut params = ParamParser {
+                params: stmt.params,
+                nullmap: header.nullmap,
+                bytes: header.values,
+                long_data: &stmt.long_data,
+                bound_types,
+                time_zone: session::utc(),
+            }
+            .into_iter();
+            while params.try_next()?.is_some() {}
+            let mut rest = &header.values[header.values.len() - params.input.len()..];
+            header.attributes(&mut rest, stmt.params, count, bound_types)?
+        };
+        if let Some(bound_types) = header.bound_types {
+            stmt.bound_types = bound_types;
+        }
+
+        let stmt: &'a StatementData = stmt;
+        let parser = ParamParser {
             params: stmt.params,
-            bytes: input,
+            nullmap: header.nullmap,
+            bytes: header.values,
             long_data: &stmt.long_data,
-            bound_types: &mut stmt.bound_types,
-        }
+            bound_types: &stmt.bound_types,
+            time_zone: session::utc(),
+        };
+        Ok((parser, attributes))
+    }
+}
+
+/// Split the query attributes that precede the query text of a `COM_QUERY` off `input`, if the
+/// client negotiated `CLIENT_QUERY_ATTRIBUTES`.
+pub(crate) fn query_attributes(mut input: &[u8]) -> io::Result<(Vec<QueryAttribute<'_>>, &[u8])> {
+    let count = u16::try_from(input.read_lenenc_int()?)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad parameter count"))?;
+    // the number of parameter sets, which is always 1
+    input.read_lenenc_int()?;
+    if count == 0 {
+        return Ok((Vec::new(), input));
+    }
+
+    let header = Header::parse(input, count, true)?;
+    let mut input = header.values;
+    let bound_types = header.bound_types.as_deref().unwrap_or(&[]);
+    let attributes = header.attributes(&mut input, 0, count, bound_types)?;
+    Ok((attributes, input))
+}
+
+impl<'a> ParamParser<'a> {
+    /// Convert the parameters to `T`, such as a tuple with one element per parameter.
+    ///
+    /// Returns an `InvalidData` error if the client sent a different number of parameters, or if
+    /// any of them cannot be converted (see
+    /// [`FromMysqlValue`](trait.FromMysqlValue.html)).
+    ///
+    /// ```no_run
+    /// # use msql_srv::ParamParser;
+    /// # use std::io;
+    /// # fn on_execute(params: ParamParser) -> io::Result<()> {
+    /// let (id, name, born): (i64, String, Option<chrono::NaiveDate>) = params.parse()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse<T: FromMysqlParams<'a>>(self) -> io::Result<T> {
+        T::from_params(self.into_iter())
+    }
+
+    /// Deserialize the parameters into `T` with serde. The fields of structs and tuples map
+    /// positionally to the statement's placeholders.
+    ///
+    /// Values are converted as by [`FromMysqlValue`](trait.FromMysqlValue.html), except that
+    /// booleans are read from integers, and dates and times are read from ISO 8601 strings.
+    /// Returns an `InvalidData` error if the client sent a different number of parameters, or if
+    /// any of them cannot be converted.
+    ///
+    /// ```no_run
+    /// # use msql_srv::ParamParser;
+    /// # use std::io;
+    /// #[derive(serde::Deserialize)]
+    /// struct InsertUser<'a> {
+    ///     id: u32,
+    ///     name: &'a str,
+    ///     email: Option<String>,
+    /// }
+    ///
+    /// # fn on_execute(params: ParamParser) -> io::Result<()> {
+    /// let args: InsertUser = params.deserialize()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn deserialize<T: serde::Deserialize<'a>>(self) -> io::Result<T> {
+        Ok(T::deserialize(crate::de::ParamsDeserializer::new(
+            self.into_iter(),
+        ))?)
+    }
+}
+
+/// Implementors of this trait can be extracted from all the parameters of an `EXECUTE` command at
+/// once, using [`ParamParser::parse`](struct.ParamParser.html#method.parse).
+///
+/// This is implemented for tuples of up to 12 [`FromMysqlValue`](trait.FromMysqlValue.html)
+/// types, which require exactly that many parameters, and for `Vec`s of them, which accept any
+/// number of parameters.
+pub trait FromMysqlParams<'a>: Sized {
+    /// Convert `params` to `Self`.
+    fn from_params(params: Params<'a>) -> io::Result<Self>;
+}
+
+/// Convert the `i`th (zero-based) parameter, pointing out which one it was if that fails.
+fn param<'a, T: FromMysqlValue<'a>>(i: usize, p: ParamValue<'a>) -> io::Result<T> {
+    p.try_get().map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("parameter {}: {}", i + 1, e),
+        )
+    })
+}
+
+impl<'a, T: FromMysqlValue<'a>> FromMysqlParams<'a> for Vec<T> {
+    fn from_params(params: Params<'a>) -> io::Result<Self> {
+        params.enumerate().map(|(i, p)| param(i, p)).collect()
     }
 }
 
+macro_rules! from_params_tuple {
+    ($($t:ident),*) => {
+        impl<'a, $($t: FromMysqlValue<'a>),*> FromMysqlParams<'a> for ($($t,)*) {
+            #[allow(unused_mut, unused_variables)]
+            fn from_params(params: Params<'a>) -> io::Result<Self> {
+                let expected = 0 $(+ { stringify!($t); 1 })*;
+                let values: Vec<_> = params.collect();
+                if values.len() != expected {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("expected {} parameters, got {}", expected, values.len()),
+                    ));
+                }
+                let mut values = values.into_iter().enumerate();
+                Ok(($({
+                    let (i, p) = values.next().unwrap();
+                    param::<$t>(i, p)?
+                },)*))
+            }
+        }
+    };
+}
+
+from_params_tuple!();
+from_params_tuple!(A);
+from_params_tuple!(A, B);
+from_params_tuple!(A, B, C);
+from_params_tuple!(A, B, C, D);
+from_params_tuple!(A, B, C, D, E);
+from_params_tuple!(A, B, C, D, E, F);
+from_params_tuple!(A, B, C, D, E, F, G);
+from_params_tuple!(A, B, C, D, E, F, G, H);
+from_params_tuple!(A, B, C, D, E, F, G, H, I);
+from_params_tuple!(A, B, C, D, E, F, G, H, I, J);
+from_params_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+from_params_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
 impl<'a> IntoIterator for ParamParser<'a> {
     type IntoIter = Params<'a>;
     type Item = ParamValue<'a>;
@@ -32,10 +220,11 @@ impl<'a> IntoIterator for ParamParser<'a> {
         Params {
             params: self.params,
             input: self.bytes,
-            nullmap: None,
+            nullmap: self.nullmap,
             col: 0,
             long_data: self.long_data,
             bound_types: self.bound_types,
+            time_zone: self.time_zone,
         }
     }
 }
@@ -44,10 +233,11 @@ impl<'a> IntoIterator for ParamParser<'a> {
 pub struct Params<'a> {
     params: u16,
     input: &'a [u8],
-    nullmap: Option<&'a [u8]>,
+    nullmap: &'a [u8],
     col: u16,
     long_data: &'a HashMap<u16, Vec<u8>>,
-    bound_types: &'a mut Vec<(myc::constants::ColumnType, bool)>,
+    bound_types: &'a [(myc::constants::ColumnType, bool)],
+    time_zone: FixedOffset,
 }
 
 /// A single parameter value provided by a client when issuing an `EXECUTE` command.
@@ -56,63 +246,355 @@ pub struct ParamValue<'a> {
     pub value: Value<'a>,
     /// The column type assigned to this parameter.
     pub coltype: myc::constants::ColumnType,
+    /// The session time zone, as set by the client with `SET time_zone`.
+    pub time_zone: FixedOffset,
 }
 
-impl<'a> Iterator for Params<'a> {
-    type Item = ParamValue<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.nullmap.is_none() {
-            let nullmap_len = (self.params as usize + 7) / 8;
-            let (nullmap, rest) = self.input.split_at(nullmap_len);
-            self.nullmap = Some(nullmap);
-            self.input = rest;
-
-            if !rest.is_empty() && rest[0] != 0x00 {
-                let (typmap, rest) = rest[1..].split_at(2 * self.params as usize);
-                self.bound_types.clear();
-                for i in 0..self.params as usize {
-                    self.bound_types.push((
-                        myc::constants::ColumnType::from(typmap[2 * i as usize]),
-                        (typmap[2 * i as usize + 1] & 128) != 0,
+impl<'a> ParamValue<'a> {
+    /// Convert this parameter to `T`, taking `DATETIME` and `TIMESTAMP` values to be in the
+    /// session time zone.
+    ///
+    /// See [`FromMysqlValue`](trait.FromMysqlValue.html).
+    pub fn try_get<T: FromMysqlValue<'a>>(&self) -> io::Result<T> {
+        T::from_mysql_value_in(self.value, self.time_zone)
+    }
+}
+
+/// A named value attached by the client to a query or to the execution of a prepared statement,
+/// as supported by MySQL 8.0.23 and later clients (`CLIENT_QUERY_ATTRIBUTES`).
+///
+/// Query attributes are typically used to pass along metadata such as tracing context. They are
+/// available from
+/// [`QueryResultWriter::query_attributes`](struct.QueryResultWriter.html#method.query_attributes).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueryAttribute<'a> {
+    /// The name of this attribute.
+    pub name: &'a str,
+    /// The value of this attribute.
+    pub value: Value<'a>,
+    /// The column type of this attribute's value.
+    pub coltype: myc::constants::ColumnType,
+}
+
+/// The NULL bitmap, and the types and names if any, that precede a set of binary-encoded values.
+struct Header<'a> {
+    nullmap: &'a [u8],
+    // the types the client bound, if it sent new ones
+    bound_types: Option<Vec<(myc::constants::ColumnType, bool)>>,
+    names: Vec<&'a str>,
+    values: &'a [u8],
+}
+
+impl<'a> Header<'a> {
+    /// Parse the header for `count` values.
+    fn parse(input: &'a [u8], count: u16, named: bool) -> io::Result<Self> {
+        let nullmap_len = (count as usize + 7) / 8;
+        if input.len() < nullmap_len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "EOF while reading parameter NULL bitmap",
+            ));
+        }
+        let (nullmap, mut rest) = input.split_at(nullmap_len);
+        let mut bound_types = None;
+        let mut names = Vec::new();
+
+        if let Some((&new_params_bound, tail)) = rest.split_first() {
+            rest = tail;
+            if new_params_bound != 0x00 {
+                let bound_types = bound_types.get_or_insert_with(Vec::new);
+                for _ in 0..count {
+                    if rest.len() < 2 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "EOF while reading parameter types",
+                        ));
+                    }
+                    bound_types.push((
+                        myc::constants::ColumnType::from(rest[0]),
+                        (rest[1] & 128) != 0,
                     ));
+                    rest = &rest[2..];
+
+                    if named {
+                        let len = rest.read_lenenc_int()?;
+                        if rest.len() < len as usize {
+                            return Err(io::Error::new(
+                                io::ErrorKind::UnexpectedEof,
+                                "EOF while reading parameter names",
+                            ));
+                        }
+                        let (name, tail) = rest.split_at(len as usize);
+                        names.push(
+                            std::str::from_utf8(name)
+                                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+                        );
+                        rest = tail;
+                    }
                 }
-                self.input = rest;
             }
         }
 
+        Ok(Header {
+            nullmap,
+            bound_types,
+            names,
+            values: rest,
+        })
+    }
+
+    /// Decode the values from `first` up to `count` from `input` as query attributes.
+    fn attributes(
+        &self,
+        input: &mut &'a [u8],
+        first: u16,
+        count: u16,
+        bound_types: &[(myc::constants::ColumnType, bool)],
+    ) -> io::Result<Vec<QueryAttribute<'a>>> {
+        (first..count)
+            .map(|col| {
+                let name = *self.names.get(col as usize).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "no name is known for attribute")
+                })?;
+                let (coltype, unsigned) = bound_types[col as usize];
+                let value = if is_null(self.nullmap, col) {
+                    Value::null()
+                } else {
+                    Value::parse_from(input, coltype, unsigned)?
+                };
+                Ok(QueryAttribute {
+                    name,
+                    value,
+                    coltype,
+                })
+            })
+            .collect()
+    }
+}
+
+// https://web.archive.org/web/20170404144156/https://dev.mysql.com/doc/internals/en/null-bitmap.html
+// NULL-bitmap-byte = ((field-pos + offset) / 8)
+// NULL-bitmap-bit  = ((field-pos + offset) % 8)
+fn is_null(nullmap: &[u8], col: u16) -> bool {
+    (nullmap[col as usize / 8] & 1u8 << (col % 8)) != 0
+}
+
+impl<'a> Params<'a> {
+    fn try_next(&mut self) -> io::Result<Option<ParamValue<'a>>> {
         if self.col >= self.params {
-            return None;
-        }
-        let pt = &self.bound_types[self.col as usize];
-
-        // https://web.archive.org/web/20170404144156/https://dev.mysql.com/doc/internals/en/null-bitmap.html
-        // NULL-bitmap-byte = ((field-pos + offset) / 8)
-        // NULL-bitmap-bit  = ((field-pos + offset) % 8)
-        if let Some(nullmap) = self.nullmap {
-            let byte = self.col as usize / 8;
-            if byte >= nullmap.len() {
-                return None;
-            }
-            if (nullmap[byte] & 1u8 << (self.col % 8)) != 0 {
-                self.col += 1;
-                return Some(ParamValue {
-                    value: Value::null(),
-                    coltype: pt.0,
-                });
-            }
-        } else {
-            unreachable!();
+            return Ok(None);
         }
+        let pt = *self.bound_types.get(self.col as usize).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "no type is known for parameter")
+        })?;
 
-        let v = if let Some(data) = self.long_data.get(&self.col) {
+        let v = if is_null(self.nullmap, self.col) {
+            Value::null()
+        } else if let Some(data) = self.long_data.get(&self.col) {
             Value::bytes(&data[..])
         } else {
-            Value::parse_from(&mut self.input, pt.0, pt.1).unwrap()
+            Value::parse_from(&mut self.input, pt.0, pt.1)?
         };
         self.col += 1;
-        Some(ParamValue {
+        Ok(Some(ParamValue {
             value: v,
             coltype: pt.0,
-        })
+            time_zone: self.time_zone,
+        }))
+    }
+}
+
+impl<'a> Iterator for Params<'a> {
+    type Item = ParamValue<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        // the parameters are validated before they are handed out
+        self.try_next()
+            .expect("parameters were validated before execution")
+    }
+}
+
+/// A prepared statement whose parameters are bound to `types`, for tests.
+#[cfg(test)]
+pub(crate) fn statement(types: &[myc::constants::ColumnType]) -> StatementData {
+    StatementData {
+        params: types.len() as u16,
+        bound_types: types.iter().map(|&t| (t, false)).collect(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::constants::ColumnType;
+
+    #[test]
+    fn it_parses_bound_types() {
+        let mut stmt = statement(&[]);
+        stmt.params = 2;
+        let input = &[
+            0x02, 0x01, 0x08, 0x00, 0x06, 0x00, 0x2a, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let (parser, attributes) = ParamParser::new(input, 0, false, &mut stmt).unwrap();
+        assert!(attributes.is_empty());
+        let params: Vec<_> = parser.into_iter().collect();
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[0].coltype, ColumnType::MYSQL_TYPE_LONGLONG);
+        assert_eq!(Into::<i64>::into(params[0].value), 42);
+        assert!(params[1].value.is_null());
+    }
+
+    #[test]
+    fn it_falls_back_to_declared_types() {
+        let mut stmt = statement(&[ColumnType::MYSQL_TYPE_LONG]);
+        let input = &[0x00, 0x00, 0x2a, 0, 0, 0];
+        let (parser, _) = ParamParser::new(input, 0, false, &mut stmt).unwrap();
+        let params: Vec<_> = parser.into_iter().collect();
+        assert_eq!(params.len(), 1);
+        assert_eq!(params[0].coltype, ColumnType::MYSQL_TYPE_LONG);
+        assert_eq!(Into::<i32>::into(params[0].value), 42);
+    }
+
+    #[test]
+    fn it_parses_tuples() {
+        let mut stmt = statement(&[]);
+        stmt.params = 3;
+        let mut input = vec![0x04, 0x01, 0x08, 0x00, 0xfd, 0x00, 0x0a, 0x00];
+        input.extend(&[0x2a, 0, 0, 0, 0, 0, 0, 0, 0x02]);
+        input.extend(b"42");
+
+        let (parser, _) = ParamParser::new(&input, 0, false, &mut stmt).unwrap();
+        let (a, b, c): (i64, i32, Option<chrono::NaiveDate>) = parser.parse().unwrap();
+        assert_eq!((a, b, c), (42, 42, None));
+
+        let (parser, _) = ParamParser::new(&input, 0, false, &mut stmt).unwrap();
+        let all: Vec<Option<u8>> = parser.parse().unwrap();
+        assert_eq!(all, vec![Some(42), Some(42), None]);
+
+        let (parser, _) = ParamParser::new(&input, 0, false, &mut stmt).unwrap();
+        let e = parser.parse::<(i64, i32)>().unwrap_err();
+        assert_eq!(e.to_string(), "expected 2 parameters, got 3");
+
+        let (parser, _) = ParamParser::new(&input, 0, false, &mut stmt).unwrap();
+        let e = parser.parse::<(i64, i32, String)>().unwrap_err();
+        assert!(e.to_string().starts_with("parameter 3: "));
+    }
+
+    #[test]
+    fn it_parses_datetimes_in_the_session_time_zone() {
+        use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+
+        let mut stmt = statement(&[ColumnType::MYSQL_TYPE_DATETIME]);
+        let input = [0x00, 0x00, 7, 0xe4, 0x07, 3, 1, 1, 0, 0];
+        let (mut parser, _) = ParamParser::new(&input, 0, false, &mut stmt).unwrap();
+        let (dt,): (DateTime<Utc>,) = parser.clone().parse().unwrap();
+        assert_eq!(dt, Utc.with_ymd_and_hms(2020, 3, 1, 1, 0, 0).unwrap());
+
+        parser.time_zone = FixedOffset::east_opt(2 * 3600).unwrap();
+        let (dt,): (Option<DateTime<Utc>>,) = parser.clone().parse().unwrap();
+        assert_eq!(
+            dt,
+            Some(Utc.with_ymd_and_hms(2020, 2, 29, 23, 0, 0).unwrap())
+        );
+        let param = parser.into_iter().next().unwrap();
+        assert_eq!(param.try_get::<DateTime<Utc>>().unwrap(), dt.unwrap());
+        assert_eq!(
+            param.value.try_get::<DateTime<Utc>>().unwrap(),
+            Utc.with_ymd_and_hms(2020, 3, 1, 1, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_rejects_bad_params() {
+        // no types known
+        let mut stmt = statement(&[]);
+        stmt.params = 1;
+        assert!(ParamParser::new(&[0x00, 0x00, 0x2a, 0, 0, 0], 0, false, &mut stmt).is_err());
+
+        // truncated value
+        let mut stmt = statement(&[ColumnType::MYSQL_TYPE_LONG]);
+        assert!(ParamParser::new(&[0x00, 0x00, 0x2a, 0], 0, false, &mut stmt).is_err());
+
+        // truncated types
+        let mut stmt = statement(&[ColumnType::MYSQL_TYPE_LONG, ColumnType::MYSQL_TYPE_LONG]);
+        assert!(ParamParser::new(&[0x00, 0x01, 0x03, 0x00], 0, false, &mut stmt).is_err());
+        assert_eq!(stmt.bound_types, [(ColumnType::MYSQL_TYPE_LONG, false); 2]);
+
+        // newly bound types with a truncated value do not replace the statement's
+        let mut stmt = statement(&[ColumnType::MYSQL_TYPE_LONG]);
+        let input = [0x00, 0x01, 0x08, 0x00, 0x2a, 0, 0, 0];
+        assert!(ParamParser::new(&input, 0, false, &mut stmt).is_err());
+        assert_eq!(stmt.bound_types, [(ColumnType::MYSQL_TYPE_LONG, false)]);
+        let (parser, _) =
+            ParamParser::new(&[0x00, 0x00, 0x2a, 0, 0, 0], 0, false, &mut stmt).unwrap();
+        assert_eq!(parser.parse::<(i32,)>().unwrap(), (42,));
+
+        // missing NULL bitmap
+        let mut stmt = statement(&[ColumnType::MYSQL_TYPE_LONG]);
+        assert!(ParamParser::new(&[], 0, false, &mut stmt).is_err());
+
+        // fewer values than parameters
+        let mut stmt = statement(&[ColumnType::MYSQL_TYPE_LONG]);
+        assert!(ParamParser::new(&[0x00, 0x00, 0x00], 0, true, &mut stmt).is_err());
+    }
+
+    #[test]
+    fn it_parses_query_attributes() {
+        let mut input = vec![0x02, 0x01, 0x02, 0x01];
+        input.extend(&[0xfd, 0x00, 0x0b]);
+        input.extend(b"traceparent");
+        input.extend(&[0x08, 0x80, 0x04]);
+        input.extend(b"user");
+        input.extend(&[0x03]);
+        input.extend(b"abc");
+        input.extend(b"SELECT 1");
+
+        let (attributes, query) = query_attributes(&input).unwrap();
+        assert_eq!(query, &b"SELECT 1"[..]);
+        assert_eq!(attributes.len(), 2);
+        assert_eq!(attributes[0].name, "traceparent");
+        assert_eq!(attributes[0].coltype, ColumnType::MYSQL_TYPE_VAR_STRING);
+        assert_eq!(Into::<&str>::into(attributes[0].value), "abc");
+        assert_eq!(attributes[1].name, "user");
+        assert!(attributes[1].value.is_null());
+
+        let (attributes, query) = query_attributes(&b"\x00\x01SELECT 1"[..]).unwrap();
+        assert!(attributes.is_empty());
+        assert_eq!(query, &b"SELECT 1"[..]);
+
+        assert!(query_attributes(&[0x01, 0x01, 0x00, 0x01, 0xfd]).is_err());
+    }
+
+    #[test]
+    fn it_parses_execute_attributes() {
+        // one parameter, followed by one attribute
+        let mut stmt = statement(&[ColumnType::MYSQL_TYPE_LONG]);
+        let mut input = vec![0x02, 0x00, 0x01, 0x03, 0x00, 0x00, 0xfd, 0x00, 0x02];
+        input.extend(b"id");
+        input.extend(&[0x2a, 0, 0, 0, 0x01]);
+        input.extend(b"x");
+        let (parser, attributes) = ParamParser::new(&input, 0, true, &mut stmt).unwrap();
+        assert_eq!(attributes.len(), 1);
+        assert_eq!(attributes[0].name, "id");
+        assert_eq!(Into::<&str>::into(attributes[0].value), "x");
+        let params: Vec<_> = parser.into_iter().collect();
+        assert_eq!(params.len(), 1);
+        assert_eq!(Into::<i32>::into(params[0].value), 42);
+
+        // no parameters, so the count is only sent if the client says so
+        let mut stmt = statement(&[]);
+        let (parser, attributes) = ParamParser::new(&[], 0, true, &mut stmt).unwrap();
+        assert!(attributes.is_empty());
+        assert_eq!(parser.into_iter().count(), 0);
+
+        let mut input = vec![0x01, 0x00, 0x01, 0x08, 0x80, 0x01];
+        input.extend(b"n");
+        input.extend(&[7, 0, 0, 0, 0, 0, 0, 0]);
+        let (parser, attributes) =
+            ParamParser::new(&input, PARAMETER_COUNT_AVAILABLE, true, &mut stmt).unwrap();
+        assert_eq!(attributes.len(), 1);
+        assert_eq!(attributes[0].name, "n");
+        assert_eq!(Into::<u64>::into(attributes[0].value), 7);
+        assert_eq!(parser.into_iter().count(), 0);
     }
 }
@@ -1,22 +1,73 @@
-use crate::myc::constants::{ColumnFlags, StatusFlags};
+use crate::charset::Charsets;
+use crate::myc::constants::{CapabilityFlags, ColumnFlags, ColumnType, StatusFlags};
 use crate::packet::PacketWriter;
-use crate::value::ToMysqlValue;
-use crate::writers;
-use crate::{Column, ErrorKind, StatementData};
+use crate::session;
+use crate::value::{coerce, fit_decimal, members, Hint, ToMysqlValue};
+use crate::warnings::warning_count;
+use crate::writers::{self, ColumnMetadata, OkPacket};
+use crate::{
+    Bit, CharacterSet, Column, ErrorKind, QueryAttribute, SessionStateChange, StatementData,
+    Warning, WarningLevel,
+};
 use byteorder::WriteBytesExt;
-use std::borrow::Borrow;
+use chrono::{FixedOffset, TimeZone};
+use std::borrow::{Borrow, Cow};
 use std::collections::HashMap;
 use std::io::{self, Write};
 
 /// Convenience type for responding to a client `USE <db>` command.
 pub struct InitWriter<'a> {
     pub(crate) writer: &'a mut PacketWriter,
+    client_capabilities: CapabilityFlags,
+    status: StatusFlags,
+    info: Vec<u8>,
+    session_state_changes: Vec<SessionStateChange>,
 }
 
 impl<'a> InitWriter<'a> {
+    pub(crate) fn new(
+        writer: &'a mut PacketWriter,
+        client_capabilities: CapabilityFlags,
+        status: StatusFlags,
+    ) -> Self {
+        InitWriter {
+            writer,
+            client_capabilities,
+            status,
+            info: Vec::new(),
+            session_state_changes: Vec::new(),
+        }
+    }
+
+    /// Set the human-readable info string included in the OK packet sent by
+    /// [`ok`](struct.InitWriter.html#method.ok).
+    pub fn set_info<E>(&mut self, info: &E)
+    where
+        E: Borrow<[u8]> + ?Sized,
+    {
+        self.info = info.borrow().to_vec();
+    }
+
+    /// Report a change to the session state (such as
+    /// [`SessionStateChange::Schema`](enum.SessionStateChange.html#variant.Schema)) in the OK
+    /// packet sent by [`ok`](struct.InitWriter.html#method.ok).
+    ///
+    /// Changes are only sent to clients that support `CLIENT_SESSION_TRACK`.
+    pub fn add_session_state_change(&mut self, change: SessionStateChange) {
+        self.session_state_changes.push(change);
+    }
+
     /// Tell client that database context has been changed
     pub fn ok(self) -> io::Result<()> {
-        writers::write_ok_packet(self.writer, 0, 0, StatusFlags::empty())
+        writers::write_ok_packet(
+            self.writer,
+            self.client_capabilities,
+            OkPacket {
+                info: &self.info,
+                session_state_changes: &self.session_state_changes,
+                ..OkPacket::new(0, 0, self.status)
+            },
+        )
     }
 
     /// Tell client that there was a problem changing the database context.
@@ -40,6 +91,9 @@ impl<'a> InitWriter<'a> {
 pub struct StatementMetaWriter<'a> {
     pub(crate) writer: &'a mut PacketWriter,
     pub(crate) stmts: &'a mut HashMap<u32, StatementData>,
+    pub(crate) status: StatusFlags,
+    pub(crate) optional_metadata: Option<bool>,
+    pub(crate) results_charset: CharacterSet,
 }
 
 impl<'a> StatementMetaWriter<'a> {
@@ -50,22 +104,39 @@ impl<'a> StatementMetaWriter<'a> {
     /// parameters the client must provide when executing the prepared statement. `columns` is a
     /// second set of [`Column`](struct.Column.html) descriptors for the values that will be
     /// returned in each row then the statement is later executed.
-    pub fn reply<PI, CI>(self, id: u32, params: PI, columns: CI) -> io::Result<()>
+    pub fn reply<'p, 'c, PI, CI>(self, id: u32, params: PI, columns: CI) -> io::Result<()>
     where
-        PI: IntoIterator<Item = &'a Column>,
-        CI: IntoIterator<Item = &'a Column>,
+        PI: IntoIterator<Item = &'p Column>,
+        CI: IntoIterator<Item = &'c Column>,
         <PI as IntoIterator>::IntoIter: ExactSizeIterator,
         <CI as IntoIterator>::IntoIter: ExactSizeIterator,
     {
-        let params = params.into_iter();
+        let params: Vec<&Column> = params.into_iter().collect();
+        let columns: Vec<Column> = columns.into_iter().cloned().collect();
+        let columns = self.results_charset.describe(&columns).unwrap_or(columns);
+        writers::write_prepare_ok(
+            id,
+            params.iter().copied(),
+            &columns,
+            self.writer,
+            self.status,
+            self.optional_metadata,
+        )?;
+        // until the client binds its own, parameters have the types we declared for them
+        let bound_types = params
+            .iter()
+            .map(|c| (c.coltype, c.colflags.contains(ColumnFlags::UNSIGNED_FLAG)))
+            .collect();
         self.stmts.insert(
             id,
             StatementData {
                 params: params.len() as u16,
+                bound_types,
+                columns,
                 ..Default::default()
             },
         );
-        writers::write_prepare_ok(id, params, columns, self.writer)
+        Ok(())
     }
 
     /// Reply to the client's `PREPARE` with an error.
@@ -78,8 +149,16 @@ impl<'a> StatementMetaWriter<'a> {
 }
 
 enum Finalizer {
-    Ok { rows: u64, last_insert_id: u64 },
-    EOF,
+    Ok {
+        rows: u64,
+        last_insert_id: u64,
+        info: Vec<u8>,
+        session_state_changes: Vec<SessionStateChange>,
+        warnings: Vec<Warning>,
+    },
+    EOF {
+        warnings: Vec<Warning>,
+    },
 }
 
 /// Convenience type for providing query results to clients.
@@ -104,20 +183,74 @@ pub struct QueryResultWriter<'a> {
     // XXX: specialization instead?
     pub(crate) is_bin: bool,
     pub(crate) writer: &'a mut PacketWriter,
+    pub(crate) client_capabilities: CapabilityFlags,
     last_end: Option<Finalizer>,
+
+    // attached to the next OK packet
+    info: Vec<u8>,
+    session_state_changes: Vec<SessionStateChange>,
+
+    // attached to the next OK or EOF packet
+    warnings: Vec<Warning>,
+    // the warnings of the most recently finished resultset, kept for SHOW WARNINGS
+    last_warnings: &'a mut Vec<Warning>,
+    // the connection's status flags
+    status: &'a mut StatusFlags,
+    // how column definitions are sent
+    metadata: ColumnMetadata<'a>,
+    charsets: Charsets,
+    pub(crate) query_attributes: Vec<QueryAttribute<'a>>,
+    pub(crate) time_zone: FixedOffset,
+    // set if the response ends with an error
+    pub(crate) failed: Option<&'a mut bool>,
 }
 
 impl<'a> QueryResultWriter<'a> {
-    pub(crate) fn new(writer: &'a mut PacketWriter, is_bin: bool) -> Self {
+    pub(crate) fn new(
+        writer: &'a mut PacketWriter,
+        is_bin: bool,
+        client_capabilities: CapabilityFlags,
+        last_warnings: &'a mut Vec<Warning>,
+        status: &'a mut StatusFlags,
+        metadata: ColumnMetadata<'a>,
+        charsets: Charsets,
+    ) -> Self {
         QueryResultWriter {
             is_bin,
             writer,
+            client_capabilities,
             last_end: None,
+            info: Vec::new(),
+            session_state_changes: Vec::new(),
+            warnings: Vec::new(),
+            last_warnings,
+            status,
+            metadata,
+            charsets,
+            query_attributes: Vec::new(),
+            time_zone: session::utc(),
+            failed: None,
+        }
+    }
+
+    fn ok_finalizer(&mut self, rows: u64, last_insert_id: u64) -> Finalizer {
+        Finalizer::Ok {
+            rows,
+            last_insert_id,
+            info: std::mem::take(&mut self.info),
+            session_state_changes: std::mem::take(&mut self.session_state_changes),
+            warnings: std::mem::take(&mut self.warnings),
+        }
+    }
+
+    fn eof_finalizer(&mut self) -> Finalizer {
+        Finalizer::EOF {
+            warnings: std::mem::take(&mut self.warnings),
         }
     }
 
     fn finalize(&mut self, more_exists: bool) -> io::Result<()> {
-        let mut status = StatusFlags::empty();
+        let mut status = *self.status;
         if more_exists {
             status.set(StatusFlags::SERVER_MORE_RESULTS_EXISTS, true);
         }
@@ -126,11 +259,108 @@ impl<'a> QueryResultWriter<'a> {
             Some(Finalizer::Ok {
                 rows,
                 last_insert_id,
-            }) => writers::write_ok_packet(self.writer, rows, last_insert_id, status),
-            Some(Finalizer::EOF) => writers::write_eof_packet(self.writer, status),
+                info,
+                session_state_changes,
+                warnings,
+            }) => {
+                *self.last_warnings = warnings;
+                writers::write_ok_packet(
+                    self.writer,
+                    self.client_capabilities,
+                    OkPacket {
+                        warnings: warning_count(self.last_warnings),
+                        info: &info,
+                        session_state_changes: &session_state_changes,
+                        ..OkPacket::new(rows, last_insert_id, status)
+                    },
+                )
+            }
+            Some(Finalizer::EOF { warnings }) => {
+                *self.last_warnings = warnings;
+                writers::write_eof_packet(self.writer, warning_count(self.last_warnings), status)
+            }
         }
     }
 
+    /// Set the human-readable info string included in the OK packet of the next resultset
+    /// completed with [`complete_one`](struct.QueryResultWriter.html#method.complete_one) or
+    /// [`completed`](struct.QueryResultWriter.html#method.completed).
+    ///
+    /// This is where MySQL reports messages such as `Rows matched: 1  Changed: 1  Warnings: 0`.
+    pub fn set_info<E>(&mut self, info: &E)
+    where
+        E: Borrow<[u8]> + ?Sized,
+    {
+        self.info = info.borrow().to_vec();
+    }
+
+    /// Report a change to the session state in the OK packet of the next resultset completed
+    /// with [`complete_one`](struct.QueryResultWriter.html#method.complete_one) or
+    /// [`completed`](struct.QueryResultWriter.html#method.completed).
+    ///
+    /// Changes are only sent to clients that support `CLIENT_SESSION_TRACK`.
+    pub fn add_session_state_change(&mut self, change: SessionStateChange) {
+        self.session_state_changes.push(change);
+    }
+
+    /// Attach a warning to the next resultset completed with
+    /// [`complete_one`](struct.QueryResultWriter.html#method.complete_one) or
+    /// [`completed`](struct.QueryResultWriter.html#method.completed).
+    ///
+    /// The client is told how many warnings were produced, and can retrieve them with a subsequent
+    /// `SHOW WARNINGS` (see [`MysqlShim::on_show_warnings`](trait.MysqlShim.html#method.on_show_warnings)).
+    pub fn add_warning(&mut self, warning: Warning) {
+        self.warnings.push(warning);
+    }
+
+    /// The query attributes the client attached to this query or execution.
+    ///
+    /// These are only sent by clients that support `CLIENT_QUERY_ATTRIBUTES` (MySQL 8.0.23 and
+    /// later), for example to propagate tracing context, and only if the shim opts in with
+    /// [`MysqlShim::supports_query_attributes`](trait.MysqlShim.html#method.supports_query_attributes).
+    pub fn query_attributes(&self) -> &[QueryAttribute<'a>] {
+        &self.query_attributes
+    }
+
+    /// The session time zone, as set by the client with `SET time_zone`.
+    ///
+    /// Time zone aware values, such as `chrono::DateTime<Utc>`, are converted to this time zone
+    /// when they are written to `TIMESTAMP` columns. Named time zones other than `UTC` are not
+    /// understood, and leave the time zone unchanged.
+    pub fn time_zone(&self) -> FixedOffset {
+        self.time_zone
+    }
+
+    /// The character set the client sends queries in (`character_set_client`).
+    pub fn client_character_set(&self) -> CharacterSet {
+        self.charsets.client
+    }
+
+    /// The character set the client expects text results in (`character_set_results`).
+    ///
+    /// Strings written with [`RowWriter::write_col`](struct.RowWriter.html#method.write_col) are
+    /// re-encoded in this character set, and columns are described accordingly.
+    pub fn results_character_set(&self) -> CharacterSet {
+        self.charsets.results
+    }
+
+    /// The status flags currently reported to the client in OK and EOF packets.
+    pub fn status_flags(&self) -> StatusFlags {
+        *self.status
+    }
+
+    /// Set the status flags reported to the client in OK and EOF packets.
+    ///
+    /// Unlike most other state on `QueryResultWriter`, these flags belong to the connection: they
+    /// are included in every response from now on, until they are changed again. Use this to
+    /// report connection state such as `SERVER_STATUS_IN_TRANS` or `SERVER_STATUS_AUTOCOMMIT`
+    /// (which is set by default). `SERVER_MORE_RESULTS_EXISTS` and `SERVER_SESSION_STATE_CHANGED`
+    /// are managed automatically, and are ignored if given.
+    pub fn set_status_flags(&mut self, flags: StatusFlags) {
+        *self.status = flags
+            - (StatusFlags::SERVER_MORE_RESULTS_EXISTS | StatusFlags::SERVER_SESSION_STATE_CHANGED);
+    }
+
     /// Start a resultset response to the client that conforms to the given `columns`.
     ///
     /// Note that if no columns are emitted, any written rows are ignored.
@@ -138,7 +368,102 @@ impl<'a> QueryResultWriter<'a> {
     /// See [`RowWriter`](struct.RowWriter.html).
     pub fn start(mut self, columns: &'a [Column]) -> io::Result<RowWriter<'a>> {
         self.finalize(true)?;
-        RowWriter::new(self, columns)
+        RowWriter::new(self, Cow::Borrowed(columns), false)
+    }
+
+    /// Like [`start`](struct.QueryResultWriter.html#method.start), but for columns that are only
+    /// known while the response is being written.
+    #[cfg(feature = "serde")]
+    pub(crate) fn start_owned(mut self, columns: Vec<Column>) -> io::Result<RowWriter<'a>> {
+        self.finalize(true)?;
+        RowWriter::new(self, Cow::Owned(columns), false)
+    }
+
+    /// Send `rows` as a resultset, with one column per field of `T`, and indicate to the client
+    /// that no more rows are coming.
+    ///
+    /// `T` must serialize as a struct whose fields are integers, `bool`s (sent as `TINYINT`),
+    /// floats, strings, bytes, unit enum variants (sent as their names), or `Option`s of those.
+    /// Each column is named after its field, and its type is that of the field's first non-NULL
+    /// value. Columns are `NOT NULL` unless their field is an `Option`.
+    ///
+    /// The columns are sent before any row, so up to 16 rows are held back until every column has
+    /// had a value. Columns that still have had none are sent as strings, and later values are
+    /// converted to text for them. If there are no rows at all, no resultset is sent, as if the
+    /// query returned none. Use
+    /// [`serialize_rows_with`](struct.QueryResultWriter.html#method.serialize_rows_with) to give
+    /// the columns yourself. A row with other fields, or with a value that cannot be converted to
+    /// its column's type, is an error.
+    ///
+    /// ```no_run
+    /// # use msql_srv::QueryResultWriter;
+    /// # use std::io;
+    /// #[derive(serde::Serialize)]
+    /// struct User {
+    ///     id: u32,
+    ///     name: String,
+    ///     email: Option<String>,
+    /// }
+    ///
+    /// # fn on_query(results: QueryResultWriter, users: Vec<User>) -> io::Result<()> {
+    /// results.serialize_rows(users)
+    /// # }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn serialize_rows<I>(self, rows: I) -> io::Result<()>
+    where
+        I: IntoIterator,
+        I::Item: serde::Serialize,
+    {
+        crate::ser::write_rows(self, rows)
+    }
+
+    /// Send `rows` as a resultset with the given `columns`, and indicate to the client that no
+    /// more rows are coming.
+    ///
+    /// Like [`serialize_rows`](struct.QueryResultWriter.html#method.serialize_rows), but the
+    /// columns are sent as given even if there are no rows. Each row must have the fields of
+    /// `columns`, in the same order, and values are converted to the type of their column as by a
+    /// [lenient](struct.RowWriter.html#method.set_lenient) `RowWriter`. Strings in `DATETIME` and
+    /// `TIMESTAMP` columns may separate the date and time with `T`, as serialized `chrono` values
+    /// do.
+    #[cfg(feature = "serde")]
+    pub fn serialize_rows_with<I>(self, columns: Vec<Column>, rows: I) -> io::Result<()>
+    where
+        I: IntoIterator,
+        I::Item: serde::Serialize,
+    {
+        crate::ser::write_rows_with(self, columns, rows)
+    }
+
+    /// Start the resultset that reports the values of a stored procedure's `OUT` and `INOUT`
+    /// parameters to the client, as MySQL does at the end of the response to an `EXECUTE` of a
+    /// `CALL` statement.
+    ///
+    /// `columns` describes the parameters, and exactly one row with their values should be
+    /// written. Since the parameters are always the last resultset, calling
+    /// [`RowWriter::finish`](struct.RowWriter.html#method.finish) also completes the response.
+    ///
+    /// An error is returned if this is not the response to an `EXECUTE`, or if the client does
+    /// not support `CLIENT_PS_MULTI_RESULTS`.
+    pub fn start_out_params(mut self, columns: &'a [Column]) -> io::Result<RowWriter<'a>> {
+        if !self.is_bin {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "OUT parameters can only be sent in response to EXECUTE",
+            ));
+        }
+        if !self
+            .client_capabilities
+            .contains(CapabilityFlags::CLIENT_PS_MULTI_RESULTS)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "client does not support OUT parameters",
+            ));
+        }
+        self.finalize(true)?;
+        RowWriter::new(self, Cow::Borrowed(columns), true)
     }
 
     /// Send an empty resultset response to the client indicating that `rows` rows were affected by
@@ -146,10 +471,7 @@ impl<'a> QueryResultWriter<'a> {
     /// a client's most recent insertion.
     pub fn complete_one(mut self, rows: u64, last_insert_id: u64) -> io::Result<Self> {
         self.finalize(true)?;
-        self.last_end = Some(Finalizer::Ok {
-            rows,
-            last_insert_id,
-        });
+        self.last_end = Some(self.ok_finalizer(rows, last_insert_id));
         Ok(self)
     }
 
@@ -161,11 +483,23 @@ impl<'a> QueryResultWriter<'a> {
     }
 
     /// Reply to the client's query with an error.
+    ///
+    /// Like in MySQL, the error is also reported by a subsequent `SHOW WARNINGS`.
     pub fn error<E>(mut self, kind: ErrorKind, msg: &E) -> io::Result<()>
     where
         E: Borrow<[u8]> + ?Sized,
     {
         self.finalize(true)?;
+        let mut warnings = std::mem::take(&mut self.warnings);
+        warnings.push(Warning::new(
+            WarningLevel::Error,
+            kind,
+            String::from_utf8_lossy(msg.borrow()),
+        ));
+        *self.last_warnings = warnings;
+        if let Some(failed) = self.failed.as_mut() {
+            **failed = true;
+        }
         writers::write_err(kind, msg.borrow(), self.writer)
     }
 
@@ -199,26 +533,38 @@ pub struct RowWriter<'a> {
     result: Option<QueryResultWriter<'a>>,
     bitmap_len: usize,
     data: Vec<u8>,
-    columns: &'a [Column],
+    columns: Cow<'a, [Column]>,
+    // whether this resultset holds OUT parameters
+    out_params: bool,
 
     // next column to write for the current row
     // NOTE: (ab)used to track number of *rows* for a zero-column resultset
     col: usize,
 
+    // whether values are converted to the type of their column
+    lenient: bool,
+
     finished: bool,
 }
 
 impl<'a> RowWriter<'a> {
-    fn new(result: QueryResultWriter<'a>, columns: &'a [Column]) -> io::Result<RowWriter<'a>> {
+    fn new(
+        result: QueryResultWriter<'a>,
+        columns: Cow<'a, [Column]>,
+        out_params: bool,
+    ) -> io::Result<RowWriter<'a>> {
         let bitmap_len = (columns.len() + 7 + 2) / 8;
         let mut rw = RowWriter {
             result: Some(result),
             columns,
+            out_params,
             bitmap_len,
             data: Vec::new(),
 
             col: 0,
 
+            lenient: false,
+
             finished: false,
         };
         rw.start()?;
@@ -228,7 +574,26 @@ impl<'a> RowWriter<'a> {
     #[inline]
     fn start(&mut self) -> io::Result<()> {
         if !self.columns.is_empty() {
-            writers::column_definitions(self.columns, self.result.as_mut().unwrap().writer)?;
+            let result = self.result.as_mut().unwrap();
+            let mut status = *result.status;
+            if self.out_params {
+                status |=
+                    StatusFlags::SERVER_PS_OUT_PARAMS | StatusFlags::SERVER_MORE_RESULTS_EXISTS;
+            }
+            match result.charsets.results.describe(&self.columns) {
+                Some(columns) => writers::column_definitions(
+                    &columns,
+                    result.writer,
+                    status,
+                    &mut result.metadata,
+                )?,
+                None => writers::column_definitions(
+                    &self.columns,
+                    result.writer,
+                    status,
+                    &mut result.metadata,
+                )?,
+            }
         }
         Ok(())
     }
@@ -252,8 +617,58 @@ impl<'a> RowWriter<'a> {
             return Ok(());
         }
 
+        let c = self.columns.get(self.col);
+        let binary = c.map_or(false, |c| {
+            c.character_set == CharacterSet::Binary.collation()
+        });
+        match v.hint() {
+            Hint::None => {}
+            Hint::Set(set) => return self.write_col(members::join(c, set)),
+            Hint::Text(text) => {
+                if let Some(c) = c {
+                    members::check(c, text)?;
+                }
+                // like MySQL, never convert the text of binary columns, such as BLOBs
+                let charset = self.result.as_ref().unwrap().charsets.results;
+                if charset.transcodes() && !binary {
+                    return self.write_col(&*charset.encode(text));
+                }
+            }
+            Hint::Utc(utc) => {
+                let timestamp = c.map_or(false, |c| c.coltype == ColumnType::MYSQL_TYPE_TIMESTAMP);
+                if timestamp {
+                    let time_zone = self.result.as_ref().unwrap().time_zone;
+                    return self.write_col(time_zone.from_utc_datetime(&utc).naive_local());
+                }
+                return self.write_col(utc);
+            }
+            Hint::Binary(bytes) => {
+                if binary {
+                    return self.write_col(bytes);
+                }
+            }
+            Hint::Decimal(text) => {
+                // the binary protocol encoding of decimals already fits them to their column
+                let decimal = c.filter(|c| {
+                    c.coltype == ColumnType::MYSQL_TYPE_DECIMAL
+                        || c.coltype == ColumnType::MYSQL_TYPE_NEWDECIMAL
+                });
+                if let Some(c) = decimal.filter(|_| !self.result.as_ref().unwrap().is_bin) {
+                    let fitted = fit_decimal(&text, c)?;
+                    return self.write_col(fitted);
+                }
+            }
+            Hint::Bool(b) => {
+                // like in the binary protocol, BIT columns get the bit itself rather than "1"
+                if c.map_or(false, |c| c.coltype == ColumnType::MYSQL_TYPE_BIT) {
+                    return self.write_col(Bit::<1>(u64::from(b)));
+                }
+            }
+        }
+
         if self.result.as_mut().unwrap().is_bin {
-            if self.col == 0 {
+            // the row may already be started if writing its first value failed
+            if self.col == 0 && self.data.is_empty() {
                 self.result.as_mut().unwrap().writer.write_u8(0x00)?;
 
                 // leave space for nullmap
@@ -282,6 +697,10 @@ impl<'a> RowWriter<'a> {
                     // NULL-bitmap-bit  = ((field-pos + offset) % 8)
                     self.data[(self.col + 2) / 8] |= 1u8 << ((self.col + 2) % 8);
                 }
+            } else if self.lenient {
+                let mut text = Vec::new();
+                v.to_mysql_text(&mut text)?;
+                coerce::coerce(&text, &mut self.data, c)?;
             } else {
                 v.to_mysql_bin(&mut self.data, c)?;
             }
@@ -292,6 +711,28 @@ impl<'a> RowWriter<'a> {
         Ok(())
     }
 
+    /// Attach a warning to this resultset.
+    ///
+    /// The number of warnings is sent to the client once the resultset is finished.
+    pub fn add_warning(&mut self, warning: Warning) {
+        self.result.as_mut().unwrap().add_warning(warning);
+    }
+
+    /// Set whether values written with [`write_col`](struct.RowWriter.html#method.write_col) are
+    /// converted to the type of their column.
+    ///
+    /// By default, writing a value whose type does not match its column in the binary protocol
+    /// (such as an `i64` to a `MYSQL_TYPE_DOUBLE` column, or a `String` to a `MYSQL_TYPE_LONG`
+    /// column) fails. Lenient writers instead convert values through their text form, the way
+    /// MySQL casts them, so that `"42"` and `42.0` can both be written to an integer column. Only
+    /// conversions that would lose information, such as of `42.5` to an integer column, or that
+    /// do not make sense, such as of `"forty-two"`, still fail.
+    ///
+    /// Values are always sent as text in the text protocol, so this has no effect there.
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
     /// Indicate that no more column data will be written for the current row.
     pub fn end_row(&mut self) -> io::Result<()> {
         if self.columns.is_empty() {
@@ -358,13 +799,12 @@ impl<'a> RowWriter<'a> {
         if self.columns.is_empty() {
             // response to no column query is always an OK packet
             // we've kept track of the number of rows in col (hacky, I know)
-            self.result.as_mut().unwrap().last_end = Some(Finalizer::Ok {
-                rows: self.col as u64,
-                last_insert_id: 0,
-            });
+            let result = self.result.as_mut().unwrap();
+            result.last_end = Some(result.ok_finalizer(self.col as u64, 0));
         } else {
             // we wrote out at least one row
-            self.result.as_mut().unwrap().last_end = Some(Finalizer::EOF);
+            let result = self.result.as_mut().unwrap();
+            result.last_end = Some(result.eof_finalizer());
         }
 
         Ok(())
@@ -372,6 +812,11 @@ impl<'a> RowWriter<'a> {
 
     /// Indicate to the client that no more rows are coming.
     pub fn finish(mut self) -> io::Result<()> {
+        if self.out_params {
+            // the OUT parameters are followed by the OK packet that ends the response
+            return self.finish_one()?.completed(0, 0);
+        }
+
         self.finish_inner()?;
 
         self.finish_completed()?;
@@ -382,22 +827,27 @@ impl<'a> RowWriter<'a> {
     /// End this resultset response, and indicate to the client that no more rows are coming.
     pub fn finish_one(mut self) -> io::Result<QueryResultWriter<'a>> {
         self.finish_inner()?;
-        // we know that dropping self will see self.finished == true,
-        // and so Drop won't try to use self.result.
+        self.finish_completed()?;
+        // we know that dropping self will see self.result == None,
+        // and so Drop won't try to use it.
         Ok(self.result.take().unwrap())
     }
 
     /// End this resultset response, and indicate to the client there was an error.
-    pub fn finish_error<E>(self, kind: ErrorKind, msg: &E) -> io::Result<()>
+    pub fn finish_error<E>(mut self, kind: ErrorKind, msg: &E) -> io::Result<()>
     where
         E: Borrow<[u8]>,
     {
-        self.finish_one()?.error(kind, msg)
+        self.finish_inner()?;
+        self.result.take().unwrap().error(kind, msg)
     }
 }
 
 impl<'a> Drop for RowWriter<'a> {
     fn drop(&mut self) {
-        self.finish_inner().unwrap();
+        if self.result.is_some() {
+            self.finish_inner().unwrap();
+            self.finish_completed().unwrap();
+        }
     }
 }
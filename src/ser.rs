@@ -0,0 +1,651 @@
+//! Serialization of resultset rows with serde.
+
+use crate::de::Error;
+use crate::myc::constants::{ColumnFlags, ColumnType};
+use crate::{CharacterSet, Column, MysqlDateTime, QueryResultWriter, RowWriter};
+use serde::ser::{self, Impossible, Serialize};
+use std::io;
+
+/// The value of one column of a serialized row.
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Null,
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Cell {
+    /// The type and flags of the column that holds this value, unless it is NULL.
+    fn column_type(&self) -> Option<(ColumnType, ColumnFlags)> {
+        let unsigned = ColumnFlags::UNSIGNED_FLAG;
+        Some(match *self {
+            Cell::Null => return None,
+            Cell::I8(_) => (ColumnType::MYSQL_TYPE_TINY, ColumnFlags::empty()),
+            Cell::I16(_) => (ColumnType::MYSQL_TYPE_SHORT, ColumnFlags::empty()),
+            Cell::I32(_) => (ColumnType::MYSQL_TYPE_LONG, ColumnFlags::empty()),
+            Cell::I64(_) => (ColumnType::MYSQL_TYPE_LONGLONG, ColumnFlags::empty()),
+            Cell::U8(_) => (ColumnType::MYSQL_TYPE_TINY, unsigned),
+            Cell::U16(_) => (ColumnType::MYSQL_TYPE_SHORT, unsigned),
+            Cell::U32(_) => (ColumnType::MYSQL_TYPE_LONG, unsigned),
+            Cell::U64(_) => (ColumnType::MYSQL_TYPE_LONGLONG, unsigned),
+            Cell::F32(_) => (ColumnType::MYSQL_TYPE_FLOAT, ColumnFlags::empty()),
+            Cell::F64(_) => (ColumnType::MYSQL_TYPE_DOUBLE, ColumnFlags::empty()),
+            Cell::Text(_) => (ColumnType::MYSQL_TYPE_VAR_STRING, ColumnFlags::empty()),
+            Cell::Bytes(_) => (ColumnType::MYSQL_TYPE_BLOB, ColumnFlags::BINARY_FLAG),
+        })
+    }
+
+    fn write(self, rw: &mut RowWriter<'_>, c: &Column) -> io::Result<()> {
+        let datetime = c.coltype == ColumnType::MYSQL_TYPE_DATETIME
+            || c.coltype == ColumnType::MYSQL_TYPE_TIMESTAMP;
+        match self {
+            // datetimes serialize as text, often in ISO 8601 form, which is sent the way MySQL
+            // sends datetimes instead
+            Cell::Text(ref v) if datetime => match MysqlDateTime::parse(v) {
+                Some(dt) => rw.write_col(dt),
+                None => rw.write_col(v),
+            },
+            Cell::Null => rw.write_col(None::<u8>),
+            Cell::I8(v) => rw.write_col(v),
+            Cell::I16(v) => rw.write_col(v),
+            Cell::I32(v) => rw.write_col(v),
+            Cell::I64(v) => rw.write_col(v),
+            Cell::U8(v) => rw.write_col(v),
+            Cell::U16(v) => rw.write_col(v),
+            Cell::U32(v) => rw.write_col(v),
+            Cell::U64(v) => rw.write_col(v),
+            Cell::F32(v) => rw.write_col(v),
+            Cell::F64(v) => rw.write_col(v),
+            Cell::Text(v) => rw.write_col(v),
+            Cell::Bytes(v) => rw.write_col(v),
+        }
+    }
+}
+
+/// A serialized row, with the name and value of each of its columns.
+#[derive(Debug, Default)]
+struct Row {
+    names: Vec<&'static str>,
+    cells: Vec<Cell>,
+    // whether each field may be NULL, such as an `Option`
+    nullable: Vec<bool>,
+}
+
+fn serialize<T: Serialize>(row: &T) -> io::Result<Row> {
+    row.serialize(RowSerializer(Row::default()))
+        .map_err(Into::into)
+}
+
+/// How many rows are held back, at most, to find the types of columns whose first values are NULL.
+const LOOKAHEAD: usize = 16;
+
+/// Send `rows` as a resultset. See
+/// [`QueryResultWriter::serialize_rows`](struct.QueryResultWriter.html#method.serialize_rows).
+pub(crate) fn write_rows<I>(results: QueryResultWriter<'_>, rows: I) -> io::Result<()>
+where
+    I: IntoIterator,
+    I::Item: Serialize,
+{
+    let mut rows = rows.into_iter().map(|row| serialize(&row));
+    // rows are held back until every column has had a value that tells its type
+    let mut pending = Vec::new();
+    let columns = loop {
+        match rows.next() {
+            Some(row) => {
+                pending.push(row?);
+                let complete = pending.len() == LOOKAHEAD;
+                if let Some(columns) = columns(&pending, complete) {
+                    break columns;
+                }
+            }
+            None if pending.is_empty() => return results.completed(0, 0),
+            None => break columns(&pending, true).unwrap_or_default(),
+        }
+    };
+    write(results, columns, pending.into_iter().map(Ok).chain(rows))
+}
+
+/// Send `rows` as a resultset with the given columns. See
+/// [`QueryResultWriter::serialize_rows_with`](struct.QueryResultWriter.html#method.serialize_rows_with).
+pub(crate) fn write_rows_with<I>(
+    results: QueryResultWriter<'_>,
+    columns: Vec<Column>,
+    rows: I,
+) -> io::Result<()>
+where
+    I: IntoIterator,
+    I::Item: Serialize,
+{
+    write(
+        results,
+        columns,
+        rows.into_iter().map(|row| serialize(&row)),
+    )
+}
+
+fn write<I>(results: QueryResultWriter<'_>, columns: Vec<Column>, rows: I) -> io::Result<()>
+where
+    I: Iterator<Item = io::Result<Row>>,
+{
+    let mut rw = results.start_owned(columns.clone())?;
+    // values are converted to their column's type, such as strings to DATETIME columns
+    rw.set_lenient(true);
+    for row in rows {
+        let row = row?;
+        check(&columns, &row)?;
+        for (cell, c) in row.cells.into_iter().zip(&columns) {
+            cell.write(&mut rw, c)?;
+        }
+        rw.end_row()?;
+    }
+    rw.finish()
+}
+
+/// The columns of a resultset that starts with `rows`, named after the fields of the first row.
+///
+/// Each column has the type of its first non-NULL value. Until `rows` is `complete`, there are no
+/// columns if one of them has had no such value yet; after that, such columns are strings.
+fn columns(rows: &[Row], complete: bool) -> Option<Vec<Column>> {
+    let first = rows.first()?;
+    first
+        .names
+        .iter()
+        .enumerate()
+        .map(|(i, &name)| {
+            let value = rows
+                .iter()
+                .filter_map(|row| row.cells.get(i))
+                .find_map(Cell::column_type);
+            let (coltype, flags) = match value {
+                Some(column) => column,
+                None if complete => (ColumnType::MYSQL_TYPE_VAR_STRING, ColumnFlags::empty()),
+                None => return None,
+            };
+            let mut column = Column::new("", name, coltype).with_flags(flags);
+            if !first.nullable[i] {
+                column.colflags |= ColumnFlags::NOT_NULL_FLAG;
+            }
+            if coltype != ColumnType::MYSQL_TYPE_VAR_STRING {
+                column.character_set = CharacterSet::Binary.collation();
+            }
+            Some(column)
+        })
+        .collect()
+}
+
+/// Check that `row` has the columns of `columns`, and no NULL values in `NOT NULL` columns, before
+/// any of it is written. Whether each value can be written to its column is left to
+/// [`RowWriter::write_col`](struct.RowWriter.html#method.write_col).
+fn check(columns: &[Column], row: &Row) -> io::Result<()> {
+    let bad = |msg: String| Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+    if row.cells.len() != columns.len() {
+        return bad(format!(
+            "row has {} columns, expected {}",
+            row.cells.len(),
+            columns.len()
+        ));
+    }
+    for ((c, cell), name) in columns.iter().zip(&row.cells).zip(&row.names) {
+        if c.column != *name {
+            return bad(format!("row has column {}, expected {}", name, c.column));
+        }
+        if *cell == Cell::Null && c.colflags.contains(ColumnFlags::NOT_NULL_FLAG) {
+            return bad(format!("given NULL value for NOT NULL column {}", c.column));
+        }
+    }
+    Ok(())
+}
+
+fn unsupported<T>(what: &str) -> Result<T, Error> {
+    Err(Error(format!("cannot serialize {} as a row", what)))
+}
+
+/// Serializes a struct into a `Row`, one column per field.
+struct RowSerializer(Row);
+
+impl ser::Serializer for RowSerializer {
+    type Ok = Row;
+    type Error = Error;
+    type SerializeSeq = Impossible<Row, Error>;
+    type SerializeTuple = Impossible<Row, Error>;
+    type SerializeTupleStruct = Impossible<Row, Error>;
+    type SerializeTupleVariant = Impossible<Row, Error>;
+    type SerializeMap = Impossible<Row, Error>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<Row, Error>;
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self, Error> {
+        Ok(RowSerializer(Row {
+            names: Vec::with_capacity(len),
+            cells: Vec::with_capacity(len),
+            nullable: Vec::with_capacity(len),
+        }))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Row, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _: bool) -> Result<Row, Error> {
+        unsupported("a bool")
+    }
+    fn serialize_i8(self, _: i8) -> Result<Row, Error> {
+        unsupported("an integer")
+    }
+    fn serialize_i16(self, _: i16) -> Result<Row, Error> {
+        unsupported("an integer")
+    }
+    fn serialize_i32(self, _: i32) -> Result<Row, Error> {
+        unsupported("an integer")
+    }
+    fn serialize_i64(self, _: i64) -> Result<Row, Error> {
+        unsupported("an integer")
+    }
+    fn serialize_u8(self, _: u8) -> Result<Row, Error> {
+        unsupported("an integer")
+    }
+    fn serialize_u16(self, _: u16) -> Result<Row, Error> {
+        unsupported("an integer")
+    }
+    fn serialize_u32(self, _: u32) -> Result<Row, Error> {
+        unsupported("an integer")
+    }
+    fn serialize_u64(self, _: u64) -> Result<Row, Error> {
+        unsupported("an integer")
+    }
+    fn serialize_f32(self, _: f32) -> Result<Row, Error> {
+        unsupported("a float")
+    }
+    fn serialize_f64(self, _: f64) -> Result<Row, Error> {
+        unsupported("a float")
+    }
+    fn serialize_char(self, _: char) -> Result<Row, Error> {
+        unsupported("a char")
+    }
+    fn serialize_str(self, _: &str) -> Result<Row, Error> {
+        unsupported("a string")
+    }
+    fn serialize_bytes(self, _: &[u8]) -> Result<Row, Error> {
+        unsupported("bytes")
+    }
+    fn serialize_none(self) -> Result<Row, Error> {
+        unsupported("an option")
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, _: &T) -> Result<Row, Error> {
+        unsupported("an option")
+    }
+    fn serialize_unit(self) -> Result<Row, Error> {
+        unsupported("a unit")
+    }
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Row, Error> {
+        unsupported("a unit struct")
+    }
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+    ) -> Result<Row, Error> {
+        unsupported("an enum")
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Row, Error> {
+        unsupported("an enum")
+    }
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        unsupported("a sequence")
+    }
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        unsupported("a tuple")
+    }
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        unsupported("a tuple struct")
+    }
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        unsupported("an enum")
+    }
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        unsupported("a map")
+    }
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        unsupported("an enum")
+    }
+}
+
+impl ser::SerializeStruct for RowSerializer {
+    type Ok = Row;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let mut nullable = false;
+        let cell = value
+            .serialize(CellSerializer(&mut nullable))
+            .map_err(|e| Error(format!("field {}: {}", key, e)))?;
+        self.0.names.push(key);
+        self.0.cells.push(cell);
+        self.0.nullable.push(nullable);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Row, Error> {
+        Ok(self.0)
+    }
+}
+
+/// Serializes the value of a single field, and notes whether the field may be NULL.
+struct CellSerializer<'n>(&'n mut bool);
+
+impl<'n> CellSerializer<'n> {
+    fn cell(self, cell: Cell) -> Result<Cell, Error> {
+        Ok(cell)
+    }
+}
+
+fn not_a_value<T>(what: &str) -> Result<T, Error> {
+    Err(Error(format!(
+        "cannot serialize {} as a column value",
+        what
+    )))
+}
+
+impl<'n> ser::Serializer for CellSerializer<'n> {
+    type Ok = Cell;
+    type Error = Error;
+    type SerializeSeq = Impossible<Cell, Error>;
+    type SerializeTuple = Impossible<Cell, Error>;
+    type SerializeTupleStruct = Impossible<Cell, Error>;
+    type SerializeTupleVariant = Impossible<Cell, Error>;
+    type SerializeMap = Impossible<Cell, Error>;
+    type SerializeStruct = Impossible<Cell, Error>;
+    type SerializeStructVariant = Impossible<Cell, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Error> {
+        // MySQL has no boolean type, and uses TINYINT(1) instead
+        self.cell(Cell::I8(i8::from(v)))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> {
+        self.cell(Cell::I8(v))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> {
+        self.cell(Cell::I16(v))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> {
+        self.cell(Cell::I32(v))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Error> {
+        self.cell(Cell::I64(v))
+    }
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> {
+        self.cell(Cell::U8(v))
+    }
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> {
+        self.cell(Cell::U16(v))
+    }
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> {
+        self.cell(Cell::U32(v))
+    }
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Error> {
+        self.cell(Cell::U64(v))
+    }
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Error> {
+        self.cell(Cell::F32(v))
+    }
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Error> {
+        self.cell(Cell::F64(v))
+    }
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Error> {
+        self.cell(Cell::Text(v.to_string()))
+    }
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Error> {
+        self.cell(Cell::Text(v.to_owned()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Error> {
+        self.cell(Cell::Bytes(v.to_vec()))
+    }
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        self.serialize_unit()
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Self::Ok, Error> {
+        *self.0 = true;
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        *self.0 = true;
+        self.cell(Cell::Null)
+    }
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, Error> {
+        self.serialize_unit()
+    }
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        // as stored in ENUM columns
+        self.serialize_str(variant)
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Self::Ok, Error> {
+        not_a_value("an enum with data")
+    }
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        not_a_value("a sequence")
+    }
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        not_a_value("a tuple")
+    }
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        not_a_value("a tuple struct")
+    }
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        not_a_value("an enum with data")
+    }
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        not_a_value("a map")
+    }
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Error> {
+        not_a_value("a struct")
+    }
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        not_a_value("an enum with data")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    enum Role {
+        Admin,
+        User,
+    }
+
+    #[derive(Serialize)]
+    struct User<'a> {
+        id: u32,
+        name: &'a str,
+        email: Option<String>,
+        admin: bool,
+        role: Role,
+    }
+
+    #[test]
+    fn it_serializes_structs() {
+        let user = User {
+            id: 42,
+            name: "jon",
+            email: None,
+            admin: true,
+            role: Role::User,
+        };
+        let row = user.serialize(RowSerializer(Row::default())).unwrap();
+        assert_eq!(row.names, ["id", "name", "email", "admin", "role"]);
+        assert_eq!(
+            row.cells,
+            [
+                Cell::U32(42),
+                Cell::Text("jon".to_owned()),
+                Cell::Null,
+                Cell::I8(1),
+                Cell::Text("User".to_owned()),
+            ]
+        );
+        assert_eq!(
+            row.cells[0].column_type(),
+            Some((ColumnType::MYSQL_TYPE_LONG, ColumnFlags::UNSIGNED_FLAG))
+        );
+
+        assert!((1, 2).serialize(RowSerializer(Row::default())).is_err());
+    }
+
+    #[test]
+    fn it_takes_columns_from_rows() {
+        let jon = User {
+            id: 42,
+            name: "jon",
+            email: None,
+            admin: true,
+            role: Role::User,
+        };
+        let ana = User {
+            id: 43,
+            name: "ana",
+            email: Some("ana@example.com".to_owned()),
+            admin: false,
+            role: Role::Admin,
+        };
+        let rows = [serialize(&jon).unwrap(), serialize(&ana).unwrap()];
+
+        // the type of email is not known until the second row
+        assert!(columns(&rows[..1], false).is_none());
+        let described = |columns: Vec<Column>| -> Vec<_> {
+            columns
+                .into_iter()
+                .map(|c| (c.column, c.coltype, c.colflags, c.character_set))
+                .collect()
+        };
+        let not_null = ColumnFlags::NOT_NULL_FLAG;
+        let binary = CharacterSet::Binary.collation();
+        let utf8 = Column::default().character_set;
+        let id = (
+            "id".to_owned(),
+            ColumnType::MYSQL_TYPE_LONG,
+            ColumnFlags::UNSIGNED_FLAG | not_null,
+            binary,
+        );
+        assert_eq!(
+            described(columns(&rows, false).unwrap()),
+            [
+                id.clone(),
+                (
+                    "name".to_owned(),
+                    ColumnType::MYSQL_TYPE_VAR_STRING,
+                    not_null,
+                    utf8
+                ),
+                (
+                    "email".to_owned(),
+                    ColumnType::MYSQL_TYPE_VAR_STRING,
+                    ColumnFlags::empty(),
+                    utf8
+                ),
+                (
+                    "admin".to_owned(),
+                    ColumnType::MYSQL_TYPE_TINY,
+                    not_null,
+                    binary
+                ),
+                (
+                    "role".to_owned(),
+                    ColumnType::MYSQL_TYPE_VAR_STRING,
+                    not_null,
+                    utf8
+                ),
+            ]
+        );
+
+        // a column without any values is sent as strings
+        let email = described(columns(&rows[..1], true).unwrap()).remove(2);
+        assert_eq!(
+            email,
+            (
+                "email".to_owned(),
+                ColumnType::MYSQL_TYPE_VAR_STRING,
+                ColumnFlags::empty(),
+                utf8
+            )
+        );
+        assert_eq!(described(columns(&rows[1..], false).unwrap())[0], id);
+        assert!(columns(&[], true).is_none());
+    }
+}
@@ -0,0 +1,547 @@
+use crate::charset::Charsets;
+use crate::myc::constants::{SessionStateType, StatusFlags};
+use crate::myc::io::WriteMysqlExt;
+use crate::CharacterSet;
+use byteorder::WriteBytesExt;
+use chrono::FixedOffset;
+use std::io::{self, Write};
+
+/// A change to the session state that should be reported to the client as part of an OK packet.
+///
+/// Session state changes are only sent to clients that negotiated `CLIENT_SESSION_TRACK` during
+/// the handshake; for all other clients they are silently discarded. See the
+/// [MySQL documentation](https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_basic_ok_packet.html)
+/// for the meaning of the individual entries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionStateChange {
+    /// A tracked system variable was assigned a new value.
+    SystemVariable {
+        /// The name of the system variable.
+        name: String,
+        /// The new value of the system variable.
+        value: String,
+    },
+    /// The default schema was changed.
+    Schema(String),
+    /// Whether any tracked session state (such as user variables or temporary tables) changed.
+    StateChange(bool),
+    /// The set of GTIDs generated by the statement.
+    Gtids(String),
+    /// The characteristics of the current transaction, as statements that would restore them
+    /// (e.g., `SET TRANSACTION ISOLATION LEVEL READ COMMITTED; START TRANSACTION READ ONLY;`).
+    TransactionCharacteristics(String),
+    /// The eight-character transaction state string reported by `session_track_transaction_info`.
+    TransactionState(String),
+}
+
+impl SessionStateChange {
+    fn kind(&self) -> SessionStateType {
+        match *self {
+            SessionStateChange::SystemVariable { .. } => {
+                SessionStateType::SESSION_TRACK_SYSTEM_VARIABLES
+            }
+            SessionStateChange::Schema(_) => SessionStateType::SESSION_TRACK_SCHEMA,
+            SessionStateChange::StateChange(_) => SessionStateType::SESSION_TRACK_STATE_CHANGE,
+            SessionStateChange::Gtids(_) => SessionStateType::SESSION_TRACK_GTIDS,
+            SessionStateChange::TransactionCharacteristics(_) => {
+                SessionStateType::SESSION_TRACK_TRANSACTION_CHARACTERISTICS
+            }
+            SessionStateChange::TransactionState(_) => {
+                SessionStateType::SESSION_TRACK_TRANSACTION_STATE
+            }
+        }
+    }
+
+    /// Encode this change as a single type-prefixed entry of the session state block.
+    pub(crate) fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut data = Vec::new();
+        match *self {
+            SessionStateChange::SystemVariable {
+                ref name,
+                ref value,
+            } => {
+                data.write_lenenc_str(name.as_bytes())?;
+                data.write_lenenc_str(value.as_bytes())?;
+            }
+            SessionStateChange::StateChange(changed) => {
+                data.write_lenenc_str(if changed { b"1" } else { b"0" })?;
+            }
+            SessionStateChange::Gtids(ref gtids) => {
+                data.write_u8(0x00)?; // encoding specification
+                data.write_lenenc_str(gtids.as_bytes())?;
+            }
+            SessionStateChange::Schema(ref s)
+            | SessionStateChange::TransactionCharacteristics(ref s)
+            | SessionStateChange::TransactionState(ref s) => {
+                data.write_lenenc_str(s.as_bytes())?;
+            }
+        }
+
+        w.write_u8(self.kind() as u8)?;
+        w.write_lenenc_str(&data)?;
+        Ok(())
+    }
+}
+
+/// Update `status` to reflect the effect of `query` on the current transaction and on
+/// `autocommit`, as MySQL would.
+///
+/// Only the statements that explicitly begin or end a transaction are recognized; statements that
+/// cause an implicit commit are not.
+pub(crate) fn track_transaction(query: &[u8], status: &mut StatusFlags) {
+    let query = match lowercase_statement(query, &["begin", "start", "commit", "rollback", "set"]) {
+        Some(q) => q,
+        None => return,
+    };
+    let words: Vec<_> = query.split_whitespace().collect();
+    let in_trans =
+        StatusFlags::SERVER_STATUS_IN_TRANS | StatusFlags::SERVER_STATUS_IN_TRANS_READONLY;
+    match words.as_slice() {
+        ["begin"] | ["begin", "work"] => {
+            status.remove(in_trans);
+            status.insert(StatusFlags::SERVER_STATUS_IN_TRANS);
+        }
+        ["start", "transaction", modes @ ..] => {
+            status.remove(in_trans);
+            status.insert(StatusFlags::SERVER_STATUS_IN_TRANS);
+            let modes = modes.join(" ");
+            if modes.split(',').any(|m| m.trim() == "read only") {
+                status.insert(StatusFlags::SERVER_STATUS_IN_TRANS_READONLY);
+            }
+        }
+        ["commit", rest @ ..] | ["rollback", rest @ ..] => {
+            let rest = match rest {
+                ["work", rest @ ..] => rest,
+                rest => rest,
+            };
+            match rest {
+                // rolling back to a savepoint leaves the transaction open
+                ["to", ..] => {}
+                // as does chaining a new transaction onto the old one
+                ["and", "chain", ..] => {}
+                _ => status.remove(in_trans),
+            }
+        }
+        ["set", ..] => {
+            for (var, value) in session_assignments(&query) {
+                if var != "autocommit" {
+                    continue;
+                }
+                match value {
+                    // enabling autocommit commits any ongoing transaction, but setting it when
+                    // it is already on does not
+                    "1" | "on" | "true"
+                        if !status.contains(StatusFlags::SERVER_STATUS_AUTOCOMMIT) =>
+                    {
+                        status.remove(in_trans);
+                        status.insert(StatusFlags::SERVER_STATUS_AUTOCOMMIT);
+                    }
+                    "0" | "off" | "false" => status.remove(StatusFlags::SERVER_STATUS_AUTOCOMMIT),
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// If `query` is a `SET` statement that only assigns `resultset_metadata`, the assigned value.
+pub(crate) fn resultset_metadata_assignment(query: &[u8]) -> Option<String> {
+    let query = lowercase_statement(query, &["set"])?;
+    match session_assignments(&query).as_slice() {
+        [("resultset_metadata", value)] => Some(value.trim_matches('\'').to_owned()),
+        _ => None,
+    }
+}
+
+/// Keep track of the character sets of the session as `SET NAMES`, `SET CHARACTER SET`, and
+/// assignments to `character_set_client` and `character_set_results` change them.
+///
+/// Character sets that are not known are ignored, as are statements that are not valid UTF-8.
+pub(crate) fn track_character_sets(query: &[u8], charsets: &mut Charsets) {
+    let query = match lowercase_statement(query, &["set"]) {
+        Some(q) => q,
+        None => return,
+    };
+    let charset = |name: &str| {
+        let name = name.split_whitespace().next().unwrap_or("");
+        CharacterSet::from_name(name.trim_matches(|c| c == '\'' || c == '"' || c == '`'))
+    };
+
+    let assignments = match query.strip_prefix("set") {
+        Some(rest) if rest.starts_with(char::is_whitespace) => rest,
+        _ => return,
+    };
+    for assignment in split_assignments(assignments).into_iter().map(str::trim) {
+        let both = assignment
+            .strip_prefix("names ")
+            .or_else(|| assignment.strip_prefix("character set "))
+            .or_else(|| assignment.strip_prefix("charset "));
+        if let Some(name) = both {
+            if let Some(cs) = charset(name) {
+                *charsets = Charsets::new(cs);
+            }
+        }
+    }
+    for (var, value) in session_assignments(&query) {
+        match var {
+            "character_set_client" => {
+                if let Some(cs) = charset(value) {
+                    charsets.client = cs;
+                }
+            }
+            // NULL means that results are sent as they are
+            "character_set_results" if value == "null" => charsets.results = CharacterSet::Utf8,
+            "character_set_results" => {
+                if let Some(cs) = charset(value) {
+                    charsets.results = cs;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The time zone of a new session, which is also what `SYSTEM` refers to.
+pub(crate) fn utc() -> FixedOffset {
+    FixedOffset::east_opt(0).unwrap()
+}
+
+/// Update the session time zone if `query` assigns `time_zone`.
+pub(crate) fn track_time_zone(query: &[u8], time_zone: &mut FixedOffset) {
+    let query = match lowercase_statement(query, &["set"]) {
+        Some(q) => q,
+        None => return,
+    };
+    for (var, value) in session_assignments(&query) {
+        if var != "time_zone" {
+            continue;
+        }
+        if let Some(offset) = parse_time_zone(value) {
+            *time_zone = offset;
+        }
+    }
+}
+
+/// The offset a value assigned to `time_zone` stands for, if it is `UTC` or an offset.
+fn parse_time_zone(value: &str) -> Option<FixedOffset> {
+    match value.trim_matches(|c| c == '\'' || c == '"') {
+        "system" | "utc" | "default" => Some(utc()),
+        value => parse_offset(value),
+    }
+}
+
+/// Parse a time zone offset such as `+05:30` or `-8:00`.
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    let (sign, rest) = match offset.as_bytes().first()? {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+    let mut parts = rest.splitn(2, ':');
+    let hours: i32 = parts.next()?.parse().ok()?;
+    let minutes: i32 = parts.next()?.parse().ok()?;
+    if !(0..60).contains(&minutes) || hours > 14 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// The trimmed, lowercase `query`, if it is valid UTF-8 and its first word is one of `keywords`.
+///
+/// The first word is checked before anything else, so that other queries are neither validated
+/// nor copied.
+fn lowercase_statement(query: &[u8], keywords: &[&str]) -> Option<String> {
+    let start = query.iter().position(|b| !b.is_ascii_whitespace())?;
+    let query = &query[start..];
+    let end = query
+        .iter()
+        .position(|&b| b.is_ascii_whitespace() || b == b';')
+        .unwrap_or(query.len());
+    if !keywords
+        .iter()
+        .any(|k| query[..end].eq_ignore_ascii_case(k.as_bytes()))
+    {
+        return None;
+    }
+    let query = std::str::from_utf8(query).ok()?;
+    Some(query.trim().trim_end_matches(';').to_ascii_lowercase())
+}
+
+/// The session variables assigned by `query`, if it is a (trimmed, lowercase) `SET` statement.
+fn session_assignments(query: &str) -> Vec<(&str, &str)> {
+    let assignments = match query.strip_prefix("set") {
+        Some(rest) if rest.starts_with(char::is_whitespace) => rest,
+        _ => return Vec::new(),
+    };
+    split_assignments(assignments)
+        .into_iter()
+        .filter_map(|assignment| {
+            let mut parts = assignment.splitn(2, '=');
+            let var = parts
+                .next()?
+                .trim()
+                .trim_start_matches("session ")
+                .trim_start_matches("local ")
+                .trim_start_matches("@@session.")
+                .trim_start_matches("@@local.")
+                .trim_start_matches("@@")
+                .trim();
+            let value = parts.next()?.trim();
+            Some((var, value))
+        })
+        .collect()
+}
+
+/// Split the assignments of a `SET` statement at the commas between them, leaving the commas in
+/// quoted strings and identifiers alone.
+fn split_assignments(assignments: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in assignments.char_indices() {
+        match quote {
+            _ if escaped => escaped = false,
+            Some(q) if c == q => quote = None,
+            Some(q) if q != '`' && c == '\\' => escaped = true,
+            Some(_) => {}
+            None if c == '\'' || c == '"' || c == '`' => quote = Some(c),
+            None if c == ',' => {
+                parts.push(&assignments[start..i]);
+                start = i + 1;
+            }
+            None => {}
+        }
+    }
+    parts.push(&assignments[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::packets::SessionStateInfo;
+
+    fn roundtrip(change: SessionStateChange) -> Vec<u8> {
+        let mut buf = Vec::new();
+        change.write_to(&mut buf).unwrap();
+        let info = SessionStateInfo::parse(&buf).unwrap();
+        assert_eq!(info.data_type(), change.kind());
+        buf
+    }
+
+    #[test]
+    fn it_encodes_system_variables() {
+        let buf = roundtrip(SessionStateChange::SystemVariable {
+            name: "autocommit".to_owned(),
+            value: "OFF".to_owned(),
+        });
+        assert_eq!(
+            buf,
+            &[
+                0x00, 0x0f, 0x0a, b'a', b'u', b't', b'o', b'c', b'o', b'm', b'm', b'i', b't', 0x03,
+                b'O', b'F', b'F'
+            ][..]
+        );
+    }
+
+    #[test]
+    fn it_encodes_schema() {
+        let buf = roundtrip(SessionStateChange::Schema("test".to_owned()));
+        assert_eq!(buf, &[0x01, 0x05, 0x04, b't', b'e', b's', b't'][..]);
+    }
+
+    #[test]
+    fn it_encodes_state_change() {
+        let buf = roundtrip(SessionStateChange::StateChange(true));
+        assert_eq!(buf, &[0x02, 0x02, 0x01, b'1'][..]);
+    }
+
+    #[test]
+    fn it_encodes_gtids() {
+        let buf = roundtrip(SessionStateChange::Gtids("a:1".to_owned()));
+        assert_eq!(buf, &[0x03, 0x05, 0x00, 0x03, b'a', b':', b'1'][..]);
+    }
+
+    fn track(queries: &[&str]) -> StatusFlags {
+        let mut status = StatusFlags::SERVER_STATUS_AUTOCOMMIT;
+        for q in queries {
+            track_transaction(q.as_bytes(), &mut status);
+        }
+        status
+    }
+
+    #[test]
+    fn it_recognizes_statements_by_their_first_word() {
+        assert_eq!(
+            lowercase_statement(b"  SET autocommit = 1;", &["set"]).as_deref(),
+            Some("set autocommit = 1")
+        );
+        assert_eq!(
+            lowercase_statement(b"Begin;", &["begin"]).as_deref(),
+            Some("begin")
+        );
+        assert_eq!(lowercase_statement(b"SETTLE", &["set"]), None);
+        assert_eq!(lowercase_statement(b"SELECT 'set'", &["set"]), None);
+        assert_eq!(lowercase_statement(b"   ", &["set"]), None);
+    }
+
+    #[test]
+    fn it_tracks_transactions() {
+        let in_trans = StatusFlags::SERVER_STATUS_AUTOCOMMIT | StatusFlags::SERVER_STATUS_IN_TRANS;
+        assert_eq!(track(&["BEGIN"]), in_trans);
+        assert_eq!(track(&["begin work;"]), in_trans);
+        assert_eq!(track(&["START TRANSACTION"]), in_trans);
+        assert_eq!(
+            track(&["START TRANSACTION WITH CONSISTENT SNAPSHOT, READ ONLY"]),
+            in_trans | StatusFlags::SERVER_STATUS_IN_TRANS_READONLY
+        );
+        assert_eq!(
+            track(&["START TRANSACTION READ ONLY", "COMMIT"]),
+            StatusFlags::SERVER_STATUS_AUTOCOMMIT
+        );
+        assert_eq!(
+            track(&["BEGIN", "ROLLBACK"]),
+            StatusFlags::SERVER_STATUS_AUTOCOMMIT
+        );
+        assert_eq!(track(&["BEGIN", "ROLLBACK TO SAVEPOINT a"]), in_trans);
+        assert_eq!(track(&["BEGIN", "COMMIT AND CHAIN"]), in_trans);
+        assert_eq!(
+            track(&["BEGIN", "COMMIT WORK AND NO CHAIN"]),
+            StatusFlags::SERVER_STATUS_AUTOCOMMIT
+        );
+        assert_eq!(track(&["SELECT 1"]), StatusFlags::SERVER_STATUS_AUTOCOMMIT);
+        // autocommit was already on, so nothing is committed
+        assert_eq!(track(&["BEGIN", "SET autocommit = 1"]), in_trans);
+    }
+
+    #[test]
+    fn it_tracks_autocommit() {
+        assert_eq!(track(&["SET autocommit = 0"]), StatusFlags::empty());
+        assert_eq!(
+            track(&["set @@session.autocommit=OFF;"]),
+            StatusFlags::empty()
+        );
+        assert_eq!(
+            track(&["SET NAMES utf8, SESSION autocommit = 0"]),
+            StatusFlags::empty()
+        );
+        assert_eq!(
+            track(&["SET autocommit = 0", "BEGIN", "SET @@autocommit = 1"]),
+            StatusFlags::SERVER_STATUS_AUTOCOMMIT
+        );
+        assert_eq!(
+            track(&["SET sql_mode = ''"]),
+            StatusFlags::SERVER_STATUS_AUTOCOMMIT
+        );
+        assert_eq!(
+            track(&["SET @a = 'x,y', autocommit = 0"]),
+            StatusFlags::empty()
+        );
+        assert_eq!(
+            track(&["SET @a = 'x, autocommit = 0'"]),
+            StatusFlags::SERVER_STATUS_AUTOCOMMIT
+        );
+    }
+
+    #[test]
+    fn it_splits_assignments_outside_quotes() {
+        assert_eq!(
+            split_assignments(" @a = 'x,y', b = \"1,2\", `c,d` = 3"),
+            [" @a = 'x,y'", " b = \"1,2\"", " `c,d` = 3"]
+        );
+        assert_eq!(
+            split_assignments(" @a = 'it''s, \\'quoted\\'', b = 1"),
+            [" @a = 'it''s, \\'quoted\\''", " b = 1"]
+        );
+        assert_eq!(split_assignments(" a = 1"), [" a = 1"]);
+    }
+
+    fn track_charsets(queries: &[&str]) -> Charsets {
+        let mut charsets = Charsets::default();
+        for q in queries {
+            track_character_sets(q.as_bytes(), &mut charsets);
+        }
+        charsets
+    }
+
+    #[test]
+    fn it_tracks_character_sets() {
+        let latin1 = Charsets::new(CharacterSet::Latin1);
+        assert_eq!(track_charsets(&["SET NAMES latin1"]), latin1);
+        assert_eq!(
+            track_charsets(&["set names 'latin1' collate 'latin1_swedish_ci';"]),
+            latin1
+        );
+        assert_eq!(track_charsets(&["SET CHARACTER SET latin1"]), latin1);
+        assert_eq!(
+            track_charsets(&["SET NAMES latin1", "SET NAMES utf8mb4"]),
+            Charsets::default()
+        );
+        assert_eq!(
+            track_charsets(&["SET NAMES latin1", "SET character_set_results = NULL"]),
+            Charsets {
+                client: CharacterSet::Latin1,
+                results: CharacterSet::Utf8,
+            }
+        );
+        assert_eq!(
+            track_charsets(&["SET @@session.character_set_client = latin1, autocommit = 0"]),
+            Charsets {
+                client: CharacterSet::Latin1,
+                results: CharacterSet::Utf8,
+            }
+        );
+        assert_eq!(track_charsets(&["SET NAMES gbk"]), Charsets::default());
+        assert_eq!(
+            track_charsets(&["SELECT 'SET NAMES latin1'"]),
+            Charsets::default()
+        );
+    }
+
+    #[test]
+    fn it_tracks_time_zones() {
+        let track = |queries: &[&str]| {
+            let mut time_zone = utc();
+            for q in queries {
+                track_time_zone(q.as_bytes(), &mut time_zone);
+            }
+            time_zone.local_minus_utc()
+        };
+        assert_eq!(track(&["SET time_zone = '+05:30'"]), 19_800);
+        assert_eq!(track(&["set @@session.time_zone='-8:00';"]), -28_800);
+        assert_eq!(
+            track(&["SET time_zone = '+01:00'", "SET time_zone = 'UTC'"]),
+            0
+        );
+        assert_eq!(
+            track(&["SET time_zone = '+01:00'", "SET time_zone = SYSTEM"]),
+            0
+        );
+        assert_eq!(
+            track(&["SET time_zone = '+01:00'", "SET time_zone = 'Europe/Oslo'"]),
+            3_600
+        );
+        assert_eq!(track(&["SET time_zone = '+15:00'"]), 0);
+        assert_eq!(track(&["SELECT '+01:00'"]), 0);
+    }
+
+    #[test]
+    fn it_recognizes_resultset_metadata() {
+        assert_eq!(
+            resultset_metadata_assignment(b"SET resultset_metadata = NONE"),
+            Some("none".to_owned())
+        );
+        assert_eq!(
+            resultset_metadata_assignment(b"set @@session.resultset_metadata='FULL';"),
+            Some("full".to_owned())
+        );
+        assert_eq!(
+            resultset_metadata_assignment(b"SET resultset_metadata = NONE, autocommit = 0"),
+            None
+        );
+        assert_eq!(resultset_metadata_assignment(b"SET autocommit = 0"), None);
+        assert_eq!(resultset_metadata_assignment(b"SELECT 1"), None);
+    }
+}
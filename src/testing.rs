@@ -0,0 +1,493 @@
+//! Helpers for testing shims without a socket or a MySQL client.
+//!
+//! [`query`] and [`execute`] hand a [`QueryResultWriter`] to a closure, such as one that calls
+//! a shim's `on_query` or `on_execute`, and decode what was written to it the way a client would.
+//! [`decode`] does the same for bytes captured elsewhere.
+//!
+//! ```
+//! use msql_srv::testing::{self, Response};
+//! use msql_srv::{Column, ColumnType, OwnedValue};
+//!
+//! let cols = [Column::new("", "n", ColumnType::MYSQL_TYPE_LONGLONG)];
+//! let responses = testing::execute(|w| {
+//!     let mut w = w.start(&cols)?;
+//!     w.write_row(&[42i64])?;
+//!     w.finish()
+//! })
+//! .unwrap();
+//!
+//! match &responses[..] {
+//!     [Response::ResultSet(rs)] => {
+//!         assert_eq!(rs.columns[0].column, "n");
+//!         assert_eq!(rs.rows, [[OwnedValue::Int(42)]]);
+//!     }
+//!     _ => unreachable!(),
+//! }
+//! ```
+//!
+//! This module is only available with the `testing` feature.
+
+use crate::charset::Charsets;
+use crate::myc::constants::{CapabilityFlags, ColumnFlags, ColumnType, StatusFlags};
+use crate::myc::io::ReadMysqlExt;
+use crate::packet::{PacketReader, PacketWriter};
+use crate::writers::ColumnMetadata;
+use crate::{Column, ErrorKind, OwnedValue, QueryResultWriter, Value};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+/// The capabilities of the client that [`query`] and [`execute`] write responses for.
+const CAPABILITIES: CapabilityFlags = CapabilityFlags::from_bits_truncate(
+    CapabilityFlags::CLIENT_PROTOCOL_41.bits()
+        | CapabilityFlags::CLIENT_MULTI_RESULTS.bits()
+        | CapabilityFlags::CLIENT_PS_MULTI_RESULTS.bits(),
+);
+
+/// A resultset with columns, and the rows that were sent for them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultSet {
+    /// The columns of the resultset. Their [`members`](Column::members) are always empty, since
+    /// those are not sent to the client.
+    pub columns: Vec<Column>,
+    /// The rows of the resultset. Values of the text protocol are either `NULL` or `Bytes`.
+    pub rows: Vec<Vec<OwnedValue>>,
+    /// The number of warnings reported at the end of the resultset.
+    pub warnings: u16,
+    /// The status flags reported at the end of the resultset.
+    pub status: StatusFlags,
+}
+
+/// A resultset without columns, as sent by an OK packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completed {
+    /// The number of affected rows.
+    pub rows: u64,
+    /// The id of the last inserted row.
+    pub last_insert_id: u64,
+    /// The number of warnings.
+    pub warnings: u16,
+    /// The status flags.
+    pub status: StatusFlags,
+    /// The human-readable info string.
+    pub info: Vec<u8>,
+}
+
+/// An error sent by an ERR packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerError {
+    /// The kind of error.
+    pub kind: ErrorKind,
+    /// The SQLSTATE sent along with the error.
+    pub sqlstate: String,
+    /// The error message.
+    pub message: String,
+}
+
+/// One of the responses sent to the client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    /// A resultset with columns.
+    ///
+    /// A resultset that was cut short by an error, such as with
+    /// [`RowWriter::finish_error`](crate::RowWriter::finish_error), holds the rows sent before
+    /// the error, no warnings and empty status flags, and is followed by the error.
+    ResultSet(ResultSet),
+    /// A resultset without columns.
+    Completed(Completed),
+    /// An error, which is always the last response.
+    Error(ServerError),
+}
+
+impl Response {
+    fn more_results(&self) -> bool {
+        let status = match *self {
+            Response::ResultSet(ref rs) => rs.status,
+            Response::Completed(ref c) => c.status,
+            Response::Error(_) => return false,
+        };
+        status.contains(StatusFlags::SERVER_MORE_RESULTS_EXISTS)
+    }
+}
+
+#[derive(Clone, Default)]
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn record<F, E>(is_bin: bool, f: F) -> Result<Vec<Response>, E>
+where
+    F: for<'a> FnOnce(QueryResultWriter<'a>) -> Result<(), E>,
+    E: From<io::Error>,
+{
+    let buf = Buffer::default();
+    let mut writer = PacketWriter::new(buf.clone());
+    let mut warnings = Vec::new();
+    let mut status = StatusFlags::SERVER_STATUS_AUTOCOMMIT;
+    f(QueryResultWriter::new(
+        &mut writer,
+        is_bin,
+        CAPABILITIES,
+        &mut warnings,
+        &mut status,
+        ColumnMetadata::Always,
+        Charsets::default(),
+    ))?;
+    writer.flush()?;
+
+    let data = RefCell::borrow(&buf.0);
+    Ok(decode(&data[..], is_bin)?)
+}
+
+/// Decode what `f` writes in response to a query, in the text protocol.
+pub fn query<F, E>(f: F) -> Result<Vec<Response>, E>
+where
+    F: for<'a> FnOnce(QueryResultWriter<'a>) -> Result<(), E>,
+    E: From<io::Error>,
+{
+    record(false, f)
+}
+
+/// Decode what `f` writes in response to the execution of a prepared statement, in the binary
+/// protocol.
+pub fn execute<F, E>(f: F) -> Result<Vec<Response>, E>
+where
+    F: for<'a> FnOnce(QueryResultWriter<'a>) -> Result<(), E>,
+    E: From<io::Error>,
+{
+    record(true, f)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn lenenc_str<'a>(input: &mut &'a [u8]) -> io::Result<&'a [u8]> {
+    let len = input.read_lenenc_int()?;
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|&len| len <= input.len())
+        .ok_or_else(|| invalid("truncated length-encoded string"))?;
+    let (s, rest) = input.split_at(len);
+    *input = rest;
+    Ok(s)
+}
+
+fn lenenc_string(input: &mut &[u8]) -> io::Result<String> {
+    lenenc_str(input).map(|s| String::from_utf8_lossy(s).into_owned())
+}
+
+fn is_eof(packet: &[u8]) -> bool {
+    packet.first() == Some(&0xFE) && packet.len() < 9
+}
+
+fn decode_ok(mut packet: &[u8]) -> io::Result<Completed> {
+    packet.read_u8()?;
+    Ok(Completed {
+        rows: packet.read_lenenc_int()?,
+        last_insert_id: packet.read_lenenc_int()?,
+        status: StatusFlags::from_bits_truncate(packet.read_u16::<LittleEndian>()?),
+        warnings: packet.read_u16::<LittleEndian>()?,
+        info: packet.to_vec(),
+    })
+}
+
+fn decode_eof(mut packet: &[u8]) -> io::Result<(u16, StatusFlags)> {
+    packet.read_u8()?;
+    let warnings = packet.read_u16::<LittleEndian>()?;
+    let status = StatusFlags::from_bits_truncate(packet.read_u16::<LittleEndian>()?);
+    Ok((warnings, status))
+}
+
+fn decode_err(mut packet: &[u8]) -> io::Result<ServerError> {
+    packet.read_u8()?;
+    let kind = ErrorKind::from(packet.read_u16::<LittleEndian>()?);
+    if packet.len() < 6 || packet[0] != b'#' {
+        return Err(invalid("ERR packet without SQLSTATE"));
+    }
+    Ok(ServerError {
+        kind,
+        sqlstate: String::from_utf8_lossy(&packet[1..6]).into_owned(),
+        message: String::from_utf8_lossy(&packet[6..]).into_owned(),
+    })
+}
+
+fn decode_column(mut packet: &[u8]) -> io::Result<Column> {
+    if lenenc_str(&mut packet)? != b"def" {
+        return Err(invalid("column definition without catalog"));
+    }
+    let schema = lenenc_string(&mut packet)?;
+    let table = lenenc_string(&mut packet)?;
+    let org_table = lenenc_string(&mut packet)?;
+    let column = lenenc_string(&mut packet)?;
+    let org_column = lenenc_string(&mut packet)?;
+    if packet.read_lenenc_int()? != 0xC {
+        return Err(invalid("column definition with unexpected length"));
+    }
+    let character_set = packet.read_u16::<LittleEndian>()?;
+    let column_length = packet.read_u32::<LittleEndian>()?;
+    let coltype = ColumnType::from(packet.read_u8()?);
+    let colflags = ColumnFlags::from_bits_truncate(packet.read_u16::<LittleEndian>()?);
+    let decimals = packet.read_u8()?;
+    Ok(Column {
+        table,
+        column,
+        coltype,
+        colflags,
+        schema,
+        org_table,
+        org_column,
+        character_set,
+        column_length,
+        decimals,
+        members: Vec::new(),
+    })
+}
+
+fn decode_text_row(mut packet: &[u8], columns: &[Column]) -> io::Result<Vec<OwnedValue>> {
+    let mut row = Vec::with_capacity(columns.len());
+    for _ in columns {
+        if packet.first() == Some(&0xFB) {
+            packet = &packet[1..];
+            row.push(OwnedValue::NULL);
+        } else {
+            row.push(OwnedValue::Bytes(lenenc_str(&mut packet)?.to_vec()));
+        }
+    }
+    if !packet.is_empty() {
+        return Err(invalid("row has more values than columns"));
+    }
+    Ok(row)
+}
+
+fn decode_binary_row(packet: &[u8], columns: &[Column]) -> io::Result<Vec<OwnedValue>> {
+    let bitmap_len = (columns.len() + 7 + 2) / 8;
+    if packet.first() != Some(&0x00) || packet.len() < 1 + bitmap_len {
+        return Err(invalid("malformed binary row"));
+    }
+    let (bitmap, mut values) = packet[1..].split_at(bitmap_len);
+    let mut row = Vec::with_capacity(columns.len());
+    for (i, c) in columns.iter().enumerate() {
+        let bit = i + 2;
+        if bitmap[bit / 8] & (1 << (bit % 8)) != 0 {
+            row.push(OwnedValue::NULL);
+        } else {
+            let unsigned = c.colflags.contains(ColumnFlags::UNSIGNED_FLAG);
+            let v = Value::parse_from(&mut values, c.coltype, unsigned)?;
+            row.push(OwnedValue::from(v));
+        }
+    }
+    if !values.is_empty() {
+        return Err(invalid("row has more values than columns"));
+    }
+    Ok(row)
+}
+
+/// Decode the responses held by `data`, which are packets written for a client with the
+/// capabilities of the one [`query`] and [`execute`] write for, and with rows in the binary
+/// protocol if `binary` is set.
+///
+/// Responses are decoded for as long as they indicate that more results exist, and it is an
+/// error for `data` to end before that, or to hold anything after.
+pub fn decode(data: &[u8], binary: bool) -> io::Result<Vec<Response>> {
+    let mut reader = PacketReader::new(io::Cursor::new(data.to_vec()));
+    let mut packets = Vec::new();
+    while let Some((_, p)) = reader.next()? {
+        packets.push(p.to_vec());
+    }
+    let mut packets = packets.iter().map(|p| &p[..]).peekable();
+    // a writer that is dropped without a response writes nothing
+    let mut more = packets.peek().is_some();
+    let mut next = || {
+        packets
+            .next()
+            .ok_or_else(|| invalid("response ended early"))
+    };
+
+    let mut responses = Vec::new();
+    while more {
+        let mut packet = next()?;
+        let response = match packet.first() {
+            Some(0x00) => Response::Completed(decode_ok(packet)?),
+            Some(0xFF) => Response::Error(decode_err(packet)?),
+            _ => {
+                let count = packet.read_lenenc_int()?;
+                let columns = (0..count)
+                    .map(|_| decode_column(next()?))
+                    .collect::<io::Result<Vec<_>>>()?;
+                if !is_eof(next()?) {
+                    return Err(invalid("column definitions not followed by EOF"));
+                }
+
+                let mut rows = Vec::new();
+                loop {
+                    let packet = next()?;
+                    if is_eof(packet) {
+                        let (warnings, status) = decode_eof(packet)?;
+                        break Response::ResultSet(ResultSet {
+                            columns,
+                            rows,
+                            warnings,
+                            status,
+                        });
+                    } else if packet.first() == Some(&0xFF) {
+                        responses.push(Response::ResultSet(ResultSet {
+                            columns,
+                            rows,
+                            warnings: 0,
+                            status: StatusFlags::empty(),
+                        }));
+                        break Response::Error(decode_err(packet)?);
+                    } else if binary {
+                        rows.push(decode_binary_row(packet, &columns)?);
+                    } else {
+                        rows.push(decode_text_row(packet, &columns)?);
+                    }
+                }
+            }
+        };
+        more = response.more_results();
+        responses.push(response);
+    }
+
+    if next().is_ok() {
+        return Err(invalid("packets after the last response"));
+    }
+    Ok(responses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Warning, WarningLevel};
+
+    fn columns() -> Vec<Column> {
+        vec![
+            Column::new("t", "a", ColumnType::MYSQL_TYPE_LONGLONG),
+            Column::new("t", "b", ColumnType::MYSQL_TYPE_VAR_STRING).with_length(10),
+        ]
+    }
+
+    #[test]
+    fn it_decodes_text_resultsets() {
+        let cols = columns();
+        let r = query(|w| {
+            let mut w = w.start(&cols)?;
+            w.write_row([Some(1i64), None])?;
+            w.write_col(None::<i64>)?;
+            w.write_col("x")?;
+            w.add_warning(Warning::new(
+                WarningLevel::Note,
+                ErrorKind::ER_UNKNOWN_ERROR,
+                "hm",
+            ));
+            w.finish()
+        })
+        .unwrap();
+        let rs = match &r[..] {
+            [Response::ResultSet(rs)] => rs,
+            r => panic!("unexpected responses {:?}", r),
+        };
+        assert_eq!(rs.columns, cols);
+        assert_eq!(
+            rs.rows,
+            [
+                [OwnedValue::Bytes(b"1".to_vec()), OwnedValue::NULL],
+                [OwnedValue::NULL, OwnedValue::Bytes(b"x".to_vec())],
+            ]
+        );
+        assert_eq!(rs.warnings, 1);
+        assert_eq!(rs.status, StatusFlags::SERVER_STATUS_AUTOCOMMIT);
+    }
+
+    #[test]
+    fn it_decodes_binary_resultsets() {
+        let cols = columns();
+        let r = execute(|w| {
+            let mut w = w.start(&cols)?;
+            w.write_col(None::<i64>)?;
+            w.write_col("x")?;
+            w.end_row()?;
+            w.write_col(-1i64)?;
+            w.write_col("")?;
+            w.finish()
+        })
+        .unwrap();
+        match &r[..] {
+            [Response::ResultSet(rs)] => {
+                assert_eq!(
+                    rs.rows,
+                    [
+                        [OwnedValue::NULL, OwnedValue::Bytes(b"x".to_vec())],
+                        [OwnedValue::Int(-1), OwnedValue::Bytes(Vec::new())],
+                    ]
+                );
+            }
+            r => panic!("unexpected responses {:?}", r),
+        }
+    }
+
+    #[test]
+    fn it_decodes_multiple_results() {
+        let cols = columns();
+        let r = query(|mut w| {
+            w.set_info(b"first");
+            let w = w.complete_one(3, 7)?;
+            let mut w = w.start(&cols)?;
+            w.write_row([Some(1i64), None])?;
+            w.finish_error(ErrorKind::ER_NO_SUCH_TABLE, b"gone")
+        })
+        .unwrap();
+        assert_eq!(r.len(), 3);
+        assert_eq!(
+            r[0],
+            Response::Completed(Completed {
+                rows: 3,
+                last_insert_id: 7,
+                warnings: 0,
+                status: StatusFlags::SERVER_STATUS_AUTOCOMMIT
+                    | StatusFlags::SERVER_MORE_RESULTS_EXISTS,
+                info: b"first".to_vec(),
+            })
+        );
+        match &r[1] {
+            Response::ResultSet(rs) => assert_eq!(rs.rows.len(), 1),
+            r => panic!("unexpected response {:?}", r),
+        }
+        assert_eq!(
+            r[2],
+            Response::Error(ServerError {
+                kind: ErrorKind::ER_NO_SUCH_TABLE,
+                sqlstate: "42S02".to_owned(),
+                message: "gone".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn it_rejects_malformed_responses() {
+        let data = [1, 0, 0, 0, 0x00];
+        assert!(decode(&data, false).is_err());
+        // an OK packet followed by another
+        let ok = [7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(decode(&ok, false).unwrap().len(), 1);
+        assert!(decode(&[&ok[..], &ok[..]].concat(), false).is_err());
+        assert!(decode(&[], false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn it_passes_on_errors() {
+        let r = query(|_| Err(invalid("oops")));
+        assert_eq!(r.unwrap_err().to_string(), "oops");
+    }
+}
@@ -0,0 +1,120 @@
+//! Support for `BIT` values.
+
+use super::decode::{bad_conversion, Value, ValueInner};
+use super::encode::bad;
+use crate::myc::constants::ColumnType;
+use crate::myc::io::WriteMysqlExt;
+use crate::{Column, FromMysqlValue, ToMysqlValue};
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+/// A `BIT(N)` value, which holds the `N` lowest bits of a `u64`.
+///
+/// `BIT` values are sent as big-endian bytes, as many as it takes to hold `N` bits, in both the
+/// text and the binary protocol. Values with bits set above the `N` lowest are rejected.
+///
+/// ```
+/// # use msql_srv::{Bit, Column, ColumnType, ToMysqlValue};
+/// let c = Column::new("", "flags", ColumnType::MYSQL_TYPE_BIT).with_length(10);
+/// let mut data = Vec::new();
+/// Bit::<10>(0b10_0000_0001).to_mysql_bin(&mut data, &c).unwrap();
+/// assert_eq!(data, [2, 0b10, 0b0000_0001]);
+/// assert!(Bit::<10>(1 << 10).to_mysql_bin(&mut Vec::new(), &c).is_err());
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bit<const N: usize>(pub u64);
+
+impl<const N: usize> Bit<N> {
+    /// Whether `N` is a valid width for a `BIT` column, and the value fits in it.
+    pub fn is_valid(&self) -> bool {
+        match N {
+            1..=63 => self.0 >> N == 0,
+            64 => true,
+            _ => false,
+        }
+    }
+
+    fn to_bytes(self) -> io::Result<Vec<u8>> {
+        if !self.is_valid() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:#b} is out of range for BIT({})", self.0, N),
+            ));
+        }
+        Ok(self.0.to_be_bytes()[8 - (N + 7) / 8..].to_vec())
+    }
+}
+
+impl<const N: usize> ToMysqlValue for Bit<N> {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_lenenc_str(&self.to_bytes()?).map(|_| ())
+    }
+
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        if c.coltype != ColumnType::MYSQL_TYPE_BIT {
+            return Err(bad(self, c));
+        }
+        self.to_mysql_text(w)
+    }
+}
+
+impl<'a, const N: usize> FromMysqlValue<'a> for Bit<N> {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        let bits = match v.into_inner() {
+            ValueInner::Bytes(b) if b.len() <= 8 => {
+                b.iter().fold(0, |bits, &b| bits << 8 | u64::from(b))
+            }
+            ValueInner::UInt(u) => u,
+            ValueInner::Int(i) => u64::try_from(i).map_err(|_| bad_conversion::<Self>(v))?,
+            _ => return Err(bad_conversion::<Self>(v)),
+        };
+        Some(Bit(bits))
+            .filter(Bit::is_valid)
+            .ok_or_else(|| bad_conversion::<Self>(v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::value::{read_bin_value, Value as MycValue};
+
+    #[test]
+    fn it_encodes_bits() {
+        let c = Column::new("", "b", ColumnType::MYSQL_TYPE_BIT);
+        assert_eq!(c.character_set, 63);
+
+        let mut data = Vec::new();
+        Bit::<1>(1).to_mysql_bin(&mut data, &c).unwrap();
+        assert_eq!(data, [1, 1]);
+
+        let mut data = Vec::new();
+        Bit::<64>(u64::MAX).to_mysql_bin(&mut data, &c).unwrap();
+        assert_eq!(
+            read_bin_value(&mut &data[..], ColumnType::MYSQL_TYPE_BIT, false).unwrap(),
+            MycValue::Bytes(vec![0xff; 8])
+        );
+
+        let mut data = Vec::new();
+        Bit::<17>(0x1_0203).to_mysql_text(&mut data).unwrap();
+        assert_eq!(data, [3, 1, 2, 3]);
+
+        assert!(Bit::<8>(256).to_mysql_text(&mut Vec::new()).is_err());
+        assert!(Bit::<0>(0).to_mysql_text(&mut Vec::new()).is_err());
+        assert!(Bit::<65>(0).to_mysql_text(&mut Vec::new()).is_err());
+        let c = Column::new("", "n", ColumnType::MYSQL_TYPE_LONG);
+        assert!(Bit::<8>(1).to_mysql_bin(&mut Vec::new(), &c).is_err());
+    }
+
+    #[test]
+    fn it_decodes_bits() {
+        let b = |v: ValueInner<'_>| Value(v).try_get::<Bit<10>>();
+        assert_eq!(b(ValueInner::Bytes(&[2, 1])).unwrap(), Bit(0x201));
+        assert_eq!(b(ValueInner::Bytes(&[])).unwrap(), Bit(0));
+        assert_eq!(b(ValueInner::Int(5)).unwrap(), Bit(5));
+        assert!(b(ValueInner::Bytes(&[4, 0])).is_err());
+        assert!(b(ValueInner::Bytes(&[0; 9])).is_err());
+        assert!(b(ValueInner::Int(-1)).is_err());
+        assert!(b(ValueInner::Double(1.0)).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+//! Conversion of values to the type of the column they are written to, for lenient
+//! [`RowWriter`](crate::RowWriter)s.
+//!
+//! Values are converted through their text form, much like MySQL's own `CAST`, and only
+//! conversions that would lose information, or that make no sense, fail.
+
+use super::encode::fit_decimal;
+use super::temporal::{MysqlDateTime, MysqlTime};
+use crate::myc::constants::{ColumnFlags, ColumnType};
+use crate::myc::io::{ReadMysqlExt, WriteMysqlExt};
+use crate::{Column, ToMysqlValue};
+use std::io::{self, Write};
+
+fn bad(s: &str, c: &Column) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "cannot convert {:?} to {:?} for column {}",
+            s, c.coltype, c.column
+        ),
+    )
+}
+
+/// The integer held by `s`, if it holds one, even if it is written as a float, as in `2.0`.
+fn integer(s: &str) -> Option<i128> {
+    if let Ok(i) = s.parse() {
+        return Some(i);
+    }
+    let f: f64 = s.parse().ok()?;
+    // every float beyond this range is lossy as an integer anyway
+    if f.fract() == 0.0 && f.abs() < 2f64.powi(64) {
+        Some(f as i128)
+    } else {
+        None
+    }
+}
+
+/// The float held by `s`, if it holds one that `f64` represents exactly enough.
+fn float(s: &str) -> Option<f64> {
+    let f: f64 = s.parse().ok()?;
+    match s.parse::<i128>() {
+        // large integers must survive the round trip
+        Ok(i) if f as i128 != i => None,
+        _ if !f.is_finite() => None,
+        _ => Some(f),
+    }
+}
+
+/// Encode `s` as a value of the type of `c` in the binary protocol.
+fn coerce_str<W: Write>(s: &str, w: &mut W, c: &Column) -> io::Result<()> {
+    let unsigned = c.colflags.contains(ColumnFlags::UNSIGNED_FLAG);
+    let int_width = match c.coltype {
+        ColumnType::MYSQL_TYPE_TINY => Some(1),
+        ColumnType::MYSQL_TYPE_SHORT | ColumnType::MYSQL_TYPE_YEAR => Some(2),
+        ColumnType::MYSQL_TYPE_INT24 | ColumnType::MYSQL_TYPE_LONG => Some(4),
+        ColumnType::MYSQL_TYPE_LONGLONG => Some(8),
+        _ => None,
+    };
+    if let Some(width) = int_width {
+        let bits = 8 * width;
+        let (min, max) = if unsigned {
+            (0, (1i128 << bits) - 1)
+        } else {
+            (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+        };
+        let i = integer(s.trim())
+            .filter(|i| (min..=max).contains(i))
+            .ok_or_else(|| bad(s, c))?;
+        return w.write_all(&i.to_le_bytes()[..width]);
+    }
+
+    match c.coltype {
+        ColumnType::MYSQL_TYPE_DOUBLE => {
+            let f = float(s.trim()).ok_or_else(|| bad(s, c))?;
+            f.to_mysql_bin(w, c)
+        }
+        ColumnType::MYSQL_TYPE_FLOAT => {
+            let f = float(s.trim())
+                .map(|f| f as f32)
+                .filter(|f| f.is_finite())
+                .ok_or_else(|| bad(s, c))?;
+            f.to_mysql_bin(w, c)
+        }
+        ColumnType::MYSQL_TYPE_DECIMAL | ColumnType::MYSQL_TYPE_NEWDECIMAL => {
+            let fitted = fit_decimal(s.trim(), c).map_err(|_| bad(s, c))?;
+            w.write_lenenc_str(fitted.as_bytes()).map(|_| ())
+        }
+        ColumnType::MYSQL_TYPE_DATE => match MysqlDateTime::parse(s.trim()) {
+            Some(dt) if dt.is_valid() && dt.is_midnight() => dt.date().to_mysql_bin(w, c),
+            _ => Err(bad(s, c)),
+        },
+        ColumnType::MYSQL_TYPE_DATETIME | ColumnType::MYSQL_TYPE_TIMESTAMP => {
+            match MysqlDateTime::parse(s.trim()) {
+                Some(dt) if dt.is_valid() => dt.to_mysql_bin(w, c),
+                _ => Err(bad(s, c)),
+            }
+        }
+        ColumnType::MYSQL_TYPE_TIME => match MysqlTime::parse(s.trim()) {
+            Some(t) => t.to_mysql_bin(w, c),
+            None => Err(bad(s, c)),
+        },
+        _ => Err(bad(s, c)),
+    }
+}
+
+/// Encode the value whose text protocol encoding is `text` as a value of the type of `c` in the
+/// binary protocol.
+pub(crate) fn coerce<W: Write>(mut text: &[u8], w: &mut W, c: &Column) -> io::Result<()> {
+    let len = text.read_lenenc_int()? as usize;
+    let bytes = text.get(..len).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "truncated text-encoded value")
+    })?;
+
+    match c.coltype {
+        // any bytes can be stored in string and binary columns
+        ColumnType::MYSQL_TYPE_STRING
+        | ColumnType::MYSQL_TYPE_VAR_STRING
+        | ColumnType::MYSQL_TYPE_VARCHAR
+        | ColumnType::MYSQL_TYPE_BLOB
+        | ColumnType::MYSQL_TYPE_TINY_BLOB
+        | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+        | ColumnType::MYSQL_TYPE_LONG_BLOB
+        | ColumnType::MYSQL_TYPE_ENUM
+        | ColumnType::MYSQL_TYPE_SET
+        | ColumnType::MYSQL_TYPE_JSON
+        | ColumnType::MYSQL_TYPE_BIT
+        | ColumnType::MYSQL_TYPE_GEOMETRY => return bytes.to_mysql_bin(w, c),
+        _ => {}
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(s) => coerce_str(s, w, c),
+        Err(_) => Err(bad(&String::from_utf8_lossy(bytes), c)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::value::{read_bin_value, Value as MycValue};
+    use chrono::NaiveDate;
+
+    fn coerced<T: ToMysqlValue>(v: T, c: &Column) -> io::Result<MycValue> {
+        let mut text = Vec::new();
+        v.to_mysql_text(&mut text)?;
+        let mut data = Vec::new();
+        coerce(&text, &mut data, c)?;
+        let unsigned = c.colflags.contains(ColumnFlags::UNSIGNED_FLAG);
+        read_bin_value(&mut &data[..], c.coltype, unsigned)
+    }
+
+    fn column(coltype: ColumnType) -> Column {
+        Column::new("", "c", coltype)
+    }
+
+    #[test]
+    fn it_coerces_numbers() {
+        let long = column(ColumnType::MYSQL_TYPE_LONG);
+        assert_eq!(coerced("42", &long).unwrap(), MycValue::Int(42));
+        assert_eq!(coerced(" -7 ", &long).unwrap(), MycValue::Int(-7));
+        assert_eq!(coerced(2.0f64, &long).unwrap(), MycValue::Int(2));
+        assert!(coerced(2.5f64, &long).is_err());
+        assert!(coerced(i64::from(i32::MAX) + 1, &long).is_err());
+        assert!(coerced("forty-two", &long).is_err());
+
+        let tiny = column(ColumnType::MYSQL_TYPE_TINY).with_flags(ColumnFlags::UNSIGNED_FLAG);
+        assert_eq!(coerced(255u64, &tiny).unwrap(), MycValue::Int(255));
+        assert!(coerced(-1i64, &tiny).is_err());
+
+        let double = column(ColumnType::MYSQL_TYPE_DOUBLE);
+        assert_eq!(coerced(3i64, &double).unwrap(), MycValue::Double(3.0));
+        assert_eq!(coerced("0.25", &double).unwrap(), MycValue::Double(0.25));
+        assert!(coerced(i64::MAX, &double).is_err());
+
+        let float = column(ColumnType::MYSQL_TYPE_FLOAT);
+        assert_eq!(coerced(0.5f64, &float).unwrap(), MycValue::Float(0.5));
+        assert!(coerced(1e300f64, &float).is_err());
+
+        let decimal = column(ColumnType::MYSQL_TYPE_NEWDECIMAL);
+        assert_eq!(
+            coerced(-12i64, &decimal).unwrap(),
+            MycValue::Bytes(b"-12".to_vec())
+        );
+        assert!(coerced("inf", &decimal).is_err());
+    }
+
+    #[test]
+    fn it_coerces_temporal_values() {
+        let midnight = NaiveDate::from_ymd_opt(2020, 2, 29)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+
+        let date = column(ColumnType::MYSQL_TYPE_DATE);
+        assert_eq!(
+            coerced(midnight, &date).unwrap(),
+            MycValue::Date(2020, 2, 29, 0, 0, 0, 0)
+        );
+        assert!(coerced(midnight + chrono::Duration::hours(1), &date).is_err());
+
+        let datetime = column(ColumnType::MYSQL_TYPE_DATETIME);
+        assert_eq!(
+            coerced("2020-02-29 12:00:00", &datetime).unwrap(),
+            MycValue::Date(2020, 2, 29, 12, 0, 0, 0)
+        );
+        assert_eq!(
+            coerced(midnight.date(), &datetime).unwrap(),
+            MycValue::Date(2020, 2, 29, 0, 0, 0, 0)
+        );
+        assert!(coerced(42i64, &datetime).is_err());
+
+        let time = column(ColumnType::MYSQL_TYPE_TIME);
+        assert_eq!(
+            coerced("-01:02:03", &time).unwrap(),
+            MycValue::Time(true, 0, 1, 2, 3, 0)
+        );
+        assert!(coerced("25:61:00", &time).is_err());
+    }
+
+    #[test]
+    fn it_coerces_to_strings() {
+        let text = column(ColumnType::MYSQL_TYPE_VAR_STRING);
+        assert_eq!(
+            coerced(42i64, &text).unwrap(),
+            MycValue::Bytes(b"42".to_vec())
+        );
+        assert_eq!(
+            coerced(NaiveDate::from_ymd_opt(2020, 2, 29).unwrap(), &text).unwrap(),
+            MycValue::Bytes(b"2020-02-29".to_vec())
+        );
+        assert!(coerced(1i64, &column(ColumnType::MYSQL_TYPE_NULL)).is_err());
+    }
+}
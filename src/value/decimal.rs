@@ -0,0 +1,134 @@
+//! Support for exact `DECIMAL` values with `rust_decimal`.
+
+use super::decode::{bad_conversion, text, Value, ValueInner};
+use super::encode::{bad, fit_decimal, Hint};
+use crate::myc::constants::ColumnType;
+use crate::myc::io::WriteMysqlExt;
+use crate::{Column, FromMysqlValue, ToMysqlValue};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+impl ToMysqlValue for Decimal {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_lenenc_str(self.to_string().as_bytes()).map(|_| ())
+    }
+
+    /// Decimals are sent as text, with as many digits after the decimal point as the column has
+    /// `decimals`. An error is returned if the value does not fit the column's precision and scale
+    /// (as given by its `column_length` and `decimals`, if they are set; trailing zeros after the
+    /// decimal point do not count), or if it is
+    /// negative and the column is `UNSIGNED`. [`RowWriter`](crate::RowWriter) applies the same
+    /// checks to `DECIMAL` columns in the text protocol.
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        match c.coltype {
+            ColumnType::MYSQL_TYPE_DECIMAL | ColumnType::MYSQL_TYPE_NEWDECIMAL => {}
+            _ => return Err(bad(self, c)),
+        }
+        let text = fit_decimal(&self.to_string(), c)?;
+        w.write_lenenc_str(text.as_bytes()).map(|_| ())
+    }
+
+    fn hint(&self) -> Hint<'_> {
+        Hint::Decimal(self.to_string())
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for Decimal {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        match v.into_inner() {
+            ValueInner::Int(i) => Ok(Decimal::from(i)),
+            ValueInner::UInt(u) => Ok(Decimal::from(u)),
+            // only accept doubles that survive the round trip
+            ValueInner::Double(d) => Decimal::try_from(d)
+                .ok()
+                .filter(|x| x.to_f64() == Some(d))
+                .ok_or_else(|| bad_conversion::<Self>(v)),
+            // DECIMAL parameters are sent as text
+            ValueInner::Bytes(_) => {
+                Decimal::from_str_exact(text::<Self>(v)?).map_err(|_| bad_conversion::<Self>(v))
+            }
+            _ => Err(bad_conversion::<Self>(v)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::constants::ColumnFlags;
+    use crate::myc::value::{read_bin_value, Value as MycValue};
+    use crate::value::bin;
+    use std::str::FromStr;
+
+    fn column(length: u32, decimals: u8) -> Column {
+        Column::new("", "d", ColumnType::MYSQL_TYPE_NEWDECIMAL)
+            .with_length(length)
+            .with_decimals(decimals)
+    }
+
+    fn decimal(d: &str) -> Decimal {
+        Decimal::from_str(d).unwrap()
+    }
+
+    #[test]
+    fn it_encodes_decimals() {
+        // DECIMAL(5, 2)
+        let c = column(7, 2);
+        let data = bin(decimal("-123.4"), &c).unwrap();
+        assert_eq!(
+            read_bin_value(&mut &data[..], ColumnType::MYSQL_TYPE_NEWDECIMAL, false).unwrap(),
+            MycValue::Bytes(b"-123.40".to_vec())
+        );
+        assert_eq!(&bin(decimal("0.5"), &c).unwrap()[1..], b"0.50");
+        assert!(bin(decimal("1234"), &c).is_err());
+        assert!(bin(decimal("1.234"), &c).is_err());
+        assert!(bin(
+            decimal("1"),
+            &Column::new("", "d", ColumnType::MYSQL_TYPE_LONG)
+        )
+        .is_err());
+
+        // DECIMAL(3, 0) UNSIGNED
+        let c = column(3, 0).with_flags(ColumnFlags::UNSIGNED_FLAG);
+        assert_eq!(&bin(decimal("999"), &c).unwrap()[1..], b"999");
+        assert!(bin(decimal("-1"), &c).is_err());
+
+        // no declared precision
+        assert_eq!(
+            &bin(decimal("123456789.5"), &column(0, 1)).unwrap()[1..],
+            b"123456789.5"
+        );
+
+        // trailing zeros, as products keep them
+        let product = decimal("0.5") * decimal("3.0");
+        assert_eq!(product.to_string(), "1.50");
+        assert_eq!(&bin(product, &column(12, 1)).unwrap()[1..], b"1.5");
+
+        // a column without precision or scale, as made by Column::new
+        let c = Column::new("", "d", ColumnType::MYSQL_TYPE_NEWDECIMAL);
+        assert_eq!(&bin(decimal("-2.25"), &c).unwrap()[1..], b"-2.25");
+
+        let mut data = Vec::new();
+        Decimal::from_str("1.50")
+            .unwrap()
+            .to_mysql_text(&mut data)
+            .unwrap();
+        assert_eq!(data, b"\x041.50");
+    }
+
+    #[test]
+    fn it_decodes_decimals() {
+        let d = |v: ValueInner<'_>| Decimal::from_mysql_value(Value(v));
+        assert_eq!(
+            d(ValueInner::Bytes(b"-123.40")).unwrap(),
+            Decimal::new(-12340, 2)
+        );
+        assert_eq!(d(ValueInner::Int(-3)).unwrap(), Decimal::from(-3));
+        assert_eq!(d(ValueInner::Double(0.5)).unwrap(), Decimal::new(5, 1));
+        assert!(d(ValueInner::Double(f64::NAN)).is_err());
+        assert!(d(ValueInner::Bytes(b"1.2.3")).is_err());
+        assert!(d(ValueInner::Bytes(b"1234567890123456789012345678901")).is_err());
+    }
+}
@@ -5,7 +5,7 @@ use std::io;
 
 /// MySQL value as provided when executing prepared statements.
 #[derive(Debug, PartialEq, Copy, Clone)]
-pub struct Value<'a>(ValueInner<'a>);
+pub struct Value<'a>(pub(super) ValueInner<'a>);
 
 /// A representation of a concrete, typed MySQL value.
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -205,7 +205,8 @@ impl<'a> Into<&'a str> for Value<'a> {
     }
 }
 
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+/// Panics on zero dates such as `0000-00-00`. Use `try_get::<MysqlDate>()` to accept those.
 impl<'a> Into<NaiveDate> for Value<'a> {
     fn into(self) -> NaiveDate {
         if let ValueInner::Date(mut v) = self.0 {
@@ -221,6 +222,8 @@ impl<'a> Into<NaiveDate> for Value<'a> {
     }
 }
 
+/// Panics on zero dates such as `0000-00-00 00:00:00`. Use `try_get::<MysqlDateTime>()` to accept
+/// those.
 impl<'a> Into<NaiveDateTime> for Value<'a> {
     fn into(self) -> NaiveDateTime {
         if let ValueInner::Datetime(mut v) = self.0 {
@@ -282,10 +285,265 @@ impl<'a> Into<Duration> for Value<'a> {
     }
 }
 
+/// Implementors of this trait can be extracted from a [`Value`](struct.Value.html) provided by a
+/// client.
+///
+/// Unlike the `Into` conversions on `Value`, these conversions do not panic when the client sent a
+/// value of an unexpected type. Instead, they return an `InvalidData` error, so that the client
+/// can be told about its mistake. Values are converted when no information is lost: integers
+/// are range-checked, integers and floats widen losslessly, strings are parsed for clients that
+/// bind every parameter as text, and `NULL` is only accepted by `Option`.
+pub trait FromMysqlValue<'a>: Sized {
+    /// Convert `v` to `Self`, or return an error if it cannot be converted without loss.
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self>;
+
+    /// Convert `v` to `Self` like [`from_mysql_value`](#tymethod.from_mysql_value), but take
+    /// `DATETIME` and `TIMESTAMP` values to be in `time_zone`.
+    ///
+    /// Only types that hold an instant, such as `DateTime<Utc>`, depend on the time zone;
+    /// `from_mysql_value` takes their values to be in UTC. Parameters are converted in the
+    /// session time zone by [`ParamValue::try_get`](struct.ParamValue.html#method.try_get) and
+    /// [`ParamParser::parse`](struct.ParamParser.html#method.parse).
+    fn from_mysql_value_in(v: Value<'a>, time_zone: FixedOffset) -> io::Result<Self> {
+        let _ = time_zone;
+        Self::from_mysql_value(v)
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Convert this value to `T`, or return an error if it cannot be converted without loss.
+    ///
+    /// See [`FromMysqlValue`](trait.FromMysqlValue.html).
+    pub fn try_get<T: FromMysqlValue<'a>>(self) -> io::Result<T> {
+        T::from_mysql_value(self)
+    }
+}
+
+pub(super) fn bad_conversion<T>(v: Value<'_>) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("cannot convert {:?} to {}", v.0, std::any::type_name::<T>()),
+    )
+}
+
+/// The text of a value that the client sent as a string.
+pub(super) fn text<'a, T>(v: Value<'a>) -> io::Result<&'a str> {
+    match v.0 {
+        ValueInner::Bytes(b) => std::str::from_utf8(b)
+            .map(str::trim)
+            .map_err(|_| bad_conversion::<T>(v)),
+        _ => Err(bad_conversion::<T>(v)),
+    }
+}
+
+impl<'a, T: FromMysqlValue<'a>> FromMysqlValue<'a> for Option<T> {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        if v.is_null() {
+            Ok(None)
+        } else {
+            T::from_mysql_value(v).map(Some)
+        }
+    }
+
+    fn from_mysql_value_in(v: Value<'a>, time_zone: FixedOffset) -> io::Result<Self> {
+        if v.is_null() {
+            Ok(None)
+        } else {
+            T::from_mysql_value_in(v, time_zone).map(Some)
+        }
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for Value<'a> {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        Ok(v)
+    }
+}
+
+macro_rules! from_integer {
+    ($($t:ty),*) => {
+        $(
+        impl<'a> FromMysqlValue<'a> for $t {
+            fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+                use std::convert::TryFrom;
+                match v.0 {
+                    ValueInner::Int(i) => <$t>::try_from(i).map_err(|_| bad_conversion::<$t>(v)),
+                    ValueInner::UInt(u) => <$t>::try_from(u).map_err(|_| bad_conversion::<$t>(v)),
+                    ValueInner::Bytes(_) => text::<$t>(v)?
+                        .parse()
+                        .map_err(|_| bad_conversion::<$t>(v)),
+                    _ => Err(bad_conversion::<$t>(v)),
+                }
+            }
+        }
+        )*
+    };
+}
+
+from_integer!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+macro_rules! from_float {
+    ($($t:ty),*) => {
+        $(
+        impl<'a> FromMysqlValue<'a> for $t {
+            fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+                // only accept values that survive the round trip
+                let f = match v.0 {
+                    ValueInner::Double(d) if d as $t as f64 == d || d.is_nan() => d as $t,
+                    // compared in i128, since casting back to i64 or u64 saturates
+                    ValueInner::Int(i) if i as $t as i128 == i128::from(i) => i as $t,
+                    ValueInner::UInt(u) if u as $t as i128 == i128::from(u) => u as $t,
+                    ValueInner::Bytes(_) => text::<$t>(v)?
+                        .parse()
+                        .map_err(|_| bad_conversion::<$t>(v))?,
+                    _ => return Err(bad_conversion::<$t>(v)),
+                };
+                Ok(f)
+            }
+        }
+        )*
+    };
+}
+
+from_float!(f32, f64);
+
+impl<'a> FromMysqlValue<'a> for &'a [u8] {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        match v.0 {
+            ValueInner::Bytes(b) => Ok(b),
+            _ => Err(bad_conversion::<Self>(v)),
+        }
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for Vec<u8> {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        <&[u8]>::from_mysql_value(v).map(<[u8]>::to_vec)
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for &'a str {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        match v.0 {
+            ValueInner::Bytes(b) => std::str::from_utf8(b).map_err(|_| bad_conversion::<Self>(v)),
+            _ => Err(bad_conversion::<Self>(v)),
+        }
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for String {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        <&str>::from_mysql_value(v).map(str::to_owned)
+    }
+}
+
+/// The date and time held by a binary-encoded `DATE`, `DATETIME` or `TIMESTAMP`.
+fn datetime<T>(v: Value<'_>) -> io::Result<NaiveDateTime> {
+    let mut b = match v.0 {
+        ValueInner::Date(b) | ValueInner::Datetime(b) => b,
+        ValueInner::Bytes(_) => {
+            let s = text::<T>(v)?;
+            return NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f")
+                .or_else(|_| {
+                    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                        .map(|d| d.and_hms_opt(0, 0, 0).unwrap())
+                })
+                .map_err(|_| bad_conversion::<T>(v));
+        }
+        _ => return Err(bad_conversion::<T>(v)),
+    };
+    if !matches!(b.len(), 4 | 7 | 11) {
+        return Err(bad_conversion::<T>(v));
+    }
+    let y = i32::from(b.read_u16::<LittleEndian>()?);
+    let mo = u32::from(b.read_u8()?);
+    let d = u32::from(b.read_u8()?);
+    let (h, mi, s) = if b.is_empty() {
+        (0, 0, 0)
+    } else {
+        (
+            u32::from(b.read_u8()?),
+            u32::from(b.read_u8()?),
+            u32::from(b.read_u8()?),
+        )
+    };
+    let us = if b.is_empty() {
+        0
+    } else {
+        b.read_u32::<LittleEndian>()?
+    };
+    NaiveDate::from_ymd_opt(y, mo, d)
+        .and_then(|d| d.and_hms_micro_opt(h, mi, s, us))
+        .ok_or_else(|| bad_conversion::<T>(v))
+}
+
+impl<'a> FromMysqlValue<'a> for NaiveDate {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        let dt = datetime::<Self>(v)?;
+        if dt.time() == chrono::NaiveTime::from_hms_opt(0, 0, 0).unwrap() {
+            Ok(dt.date())
+        } else {
+            Err(bad_conversion::<Self>(v))
+        }
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for NaiveDateTime {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        datetime::<Self>(v)
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for DateTime<Utc> {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        datetime::<Self>(v).map(|dt| Utc.from_utc_datetime(&dt))
+    }
+
+    fn from_mysql_value_in(v: Value<'a>, time_zone: FixedOffset) -> io::Result<Self> {
+        time_zone
+            .from_local_datetime(&datetime::<Self>(v)?)
+            .single()
+            .map(|dt| dt.with_timezone(&Utc))
+            .ok_or_else(|| bad_conversion::<Self>(v))
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for Duration {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        let mut b = match v.0 {
+            ValueInner::Time(b) if matches!(b.len(), 0 | 8 | 12) => b,
+            _ => return Err(bad_conversion::<Self>(v)),
+        };
+        if b.is_empty() {
+            return Ok(Duration::from_secs(0));
+        }
+        if b.read_u8()? != 0 {
+            // negative durations cannot be represented
+            return Err(bad_conversion::<Self>(v));
+        }
+        let days = u64::from(b.read_u32::<LittleEndian>()?);
+        let hours = u64::from(b.read_u8()?);
+        let minutes = u64::from(b.read_u8()?);
+        let seconds = u64::from(b.read_u8()?);
+        let micros = if b.is_empty() {
+            0
+        } else {
+            b.read_u32::<LittleEndian>()?
+        };
+        if micros >= 1_000_000 {
+            return Err(bad_conversion::<Self>(v));
+        }
+        Ok(Duration::new(
+            days * 86_400 + hours * 3_600 + minutes * 60 + seconds,
+            micros * 1_000,
+        ))
+    }
+}
+
 #[cfg(test)]
 #[allow(unused_imports)]
 mod tests {
-    use super::Value;
+    use super::{FromMysqlValue, Value, ValueInner};
     use crate::myc;
     use crate::myc::io::WriteMysqlExt;
     use crate::{Column, ColumnFlags, ColumnType};
@@ -305,6 +563,7 @@ mod tests {
                     column: String::new(),
                     coltype: $ct,
                     colflags: ColumnFlags::empty(),
+                    ..Default::default()
                 };
 
                 if !$sig {
@@ -317,6 +576,11 @@ mod tests {
                     Into::<$t>::into(Value::parse_from(&mut &data[..], $ct, !$sig).unwrap()),
                     v
                 );
+                assert_eq!(
+                    <$t>::from_mysql_value(Value::parse_from(&mut &data[..], $ct, !$sig).unwrap())
+                        .unwrap(),
+                    v
+                );
             }
         };
     }
@@ -469,4 +733,70 @@ mod tests {
         ColumnType::MYSQL_TYPE_BLOB
     );
     rt!(string, &str, "foobar", ColumnType::MYSQL_TYPE_STRING);
+
+    #[test]
+    fn it_converts_fallibly() {
+        let text = |s: &'static str| Value::bytes(s.as_bytes());
+        assert_eq!(Value(ValueInner::Int(-1)).try_get::<i8>().unwrap(), -1);
+        assert!(Value(ValueInner::Int(-1)).try_get::<u64>().is_err());
+        assert!(Value(ValueInner::UInt(256)).try_get::<u8>().is_err());
+        assert_eq!(Value(ValueInner::UInt(256)).try_get::<i64>().unwrap(), 256);
+        assert_eq!(text(" 42 ").try_get::<i32>().unwrap(), 42);
+        assert!(text("forty-two").try_get::<i32>().is_err());
+        assert!(text("4.2").try_get::<i32>().is_err());
+        assert!(Value(ValueInner::Double(4.0)).try_get::<i32>().is_err());
+
+        assert_eq!(Value(ValueInner::Int(3)).try_get::<f64>().unwrap(), 3.0);
+        assert!(Value(ValueInner::Int(i64::MAX - 1))
+            .try_get::<f64>()
+            .is_err());
+        assert!(Value(ValueInner::Int(i64::MAX)).try_get::<f64>().is_err());
+        assert!(Value(ValueInner::UInt(u64::MAX)).try_get::<f64>().is_err());
+        assert_eq!(
+            Value(ValueInner::Int(i64::MIN)).try_get::<f64>().unwrap(),
+            i64::MIN as f64
+        );
+        assert_eq!(
+            Value(ValueInner::Double(0.5)).try_get::<f32>().unwrap(),
+            0.5
+        );
+        assert!(Value(ValueInner::Double(0.1)).try_get::<f32>().is_err());
+        assert_eq!(text("2.5").try_get::<f64>().unwrap(), 2.5);
+
+        assert!(Value::null().try_get::<i32>().is_err());
+        assert!(Value::null().try_get::<&str>().is_err());
+        assert_eq!(Value::null().try_get::<Option<i32>>().unwrap(), None);
+        assert_eq!(text("7").try_get::<Option<i32>>().unwrap(), Some(7));
+        assert_eq!(text("abc").try_get::<String>().unwrap(), "abc");
+        assert!(Value::bytes(b"\xff").try_get::<&str>().is_err());
+        assert!(Value(ValueInner::Int(1)).try_get::<String>().is_err());
+
+        assert_eq!(
+            text("2020-02-29").try_get::<chrono::NaiveDate>().unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2020, 2, 29).unwrap()
+        );
+        assert_eq!(
+            text("2020-02-29 23:59:58.5")
+                .try_get::<chrono::NaiveDateTime>()
+                .unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2020, 2, 29)
+                .unwrap()
+                .and_hms_milli_opt(23, 59, 58, 500)
+                .unwrap()
+        );
+        assert!(Value(ValueInner::Date(&[0, 0, 0, 0]))
+            .try_get::<chrono::NaiveDate>()
+            .is_err());
+        assert!(Value(ValueInner::Date(&[0xe4, 0x07, 0x02]))
+            .try_get::<chrono::NaiveDate>()
+            .is_err());
+        assert!(Value(ValueInner::Time(&[1, 0, 0, 0, 0, 0, 0, 1]))
+            .try_get::<time::Duration>()
+            .is_err());
+        assert!(Value(ValueInner::Time(&[
+            0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff
+        ]))
+        .try_get::<time::Duration>()
+        .is_err());
+    }
 }
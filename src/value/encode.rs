@@ -3,6 +3,8 @@ use crate::myc::constants::{ColumnFlags, ColumnType};
 use crate::myc::io::WriteMysqlExt;
 use crate::Column;
 use byteorder::{LittleEndian, WriteBytesExt};
+use chrono::NaiveDateTime;
+use std::convert::TryFrom;
 use std::io::{self, Write};
 
 /// Implementors of this trait can be sent as a single resultset value to a MySQL/MariaDB client.
@@ -17,6 +19,56 @@ pub trait ToMysqlValue {
     fn is_null(&self) -> bool {
         false
     }
+
+    /// How [`RowWriter`](crate::RowWriter) should treat this value before encoding it.
+    ///
+    /// The default implementation gives no hint. Types that are sent as another value, such as
+    /// wrappers around a `String`, should return that value's hint, so that it is treated the same
+    /// way.
+    fn hint(&self) -> Hint<'_> {
+        Hint::None
+    }
+}
+
+/// What [`RowWriter`](crate::RowWriter) needs to know about a value, besides its encoding, to
+/// send it to a column the way MySQL would. See [`ToMysqlValue::hint`].
+///
+/// ```
+/// # use msql_srv::{Column, Hint, ToMysqlValue};
+/// # use std::io;
+/// struct Name(String);
+///
+/// impl ToMysqlValue for Name {
+///     fn to_mysql_text<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+///         self.0.to_mysql_text(w)
+///     }
+///     fn to_mysql_bin<W: io::Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+///         self.0.to_mysql_bin(w, c)
+///     }
+///     fn hint(&self) -> Hint<'_> {
+///         self.0.hint()
+///     }
+/// }
+/// ```
+#[non_exhaustive]
+pub enum Hint<'a> {
+    /// Nothing special.
+    None,
+    /// A string, which is checked against the members of `ENUM` and `SET` columns and re-encoded
+    /// in the client's character set.
+    Text(&'a str),
+    /// A time zone aware instant, as a UTC date and time, which is converted to the session time
+    /// zone for `TIMESTAMP` columns.
+    Utc(NaiveDateTime),
+    /// A set, which is checked against, and ordered like, the members of `SET` columns.
+    Set(Vec<&'a str>),
+    /// The bytes the value is sent as to columns with the `binary` collation, in both protocols.
+    Binary(&'a [u8]),
+    /// An exact decimal number, which is held to the precision and scale of `DECIMAL` columns in
+    /// both protocols.
+    Decimal(String),
+    /// A boolean, which is sent as a single bit to `BIT` columns in both protocols.
+    Bool(bool),
 }
 
 macro_rules! mysql_text_trivial {
@@ -29,13 +81,85 @@ macro_rules! mysql_text_trivial {
 }
 
 use std::fmt;
-fn bad<V: fmt::Debug>(v: V, c: &Column) -> io::Error {
+pub(super) fn bad<V: fmt::Debug>(v: V, c: &Column) -> io::Error {
     io::Error::new(
         io::ErrorKind::InvalidData,
         format!("tried to use {:?} as {:?}", v, c.coltype),
     )
 }
 
+/// Check that the decimal number `text` fits `DECIMAL` column `c`, and pad it with as many digits
+/// after the decimal point as the column has `decimals`.
+///
+/// The number must not have more digits after the decimal point than the column's scale (not
+/// counting trailing zeros), nor more digits in total than its precision (as given by its
+/// `column_length` and `decimals`, if the length is one a `DECIMAL` can have), and must not be
+/// negative if the column is `UNSIGNED`. A column that sets neither, such as one made with just
+/// [`Column::new`](crate::Column::new), takes the number with the digits it has.
+pub(crate) fn fit_decimal(text: &str, c: &Column) -> io::Result<String> {
+    let out_of_range = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} does not fit DECIMAL({}, {}) column {}",
+                text, c.column_length, c.decimals, c.column
+            ),
+        )
+    };
+
+    let (negative, number) = match text.as_bytes().first() {
+        Some(b'-') => (true, &text[1..]),
+        Some(b'+') => (false, &text[1..]),
+        _ => (false, text),
+    };
+    let (int, frac) = match number.find('.') {
+        Some(at) => (&number[..at], &number[at + 1..]),
+        None => (number, ""),
+    };
+    let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if int.len() + frac.len() == 0 || !is_digits(int) || !is_digits(frac) {
+        return Err(bad(text, c));
+    }
+
+    // DECIMAL(65, 30) is the widest, so a longer length (such as the default one) declares no
+    // precision, and a column without precision or decimals takes the number as it is
+    let precision = match c.column_length {
+        0 | 68..=u32::MAX => None,
+        length => Some(length),
+    };
+    let (frac, scale) = if precision.is_some() || c.decimals != 0 {
+        // trailing zeros do not count, so 1.50 fits DECIMAL(M, 1)
+        (frac.trim_end_matches('0'), usize::from(c.decimals))
+    } else {
+        (frac, frac.len())
+    };
+    let unsigned = c.colflags.contains(ColumnFlags::UNSIGNED_FLAG);
+    let zero = number.bytes().all(|b| b == b'0' || b == b'.');
+    if frac.len() > scale || (unsigned && negative && !zero) {
+        return Err(out_of_range());
+    }
+    let int = int.trim_start_matches('0');
+    if let Some(length) = precision {
+        // the length of a DECIMAL(M, D) column includes the sign and the decimal point
+        let precision = length.saturating_sub(u32::from(scale > 0) + u32::from(!unsigned));
+        if (int.len() + scale) as u32 > precision {
+            return Err(out_of_range());
+        }
+    }
+
+    let mut fitted = String::with_capacity(int.len() + scale + 3);
+    if negative && !zero {
+        fitted.push('-');
+    }
+    fitted.push_str(if int.is_empty() { "0" } else { int });
+    if scale > 0 {
+        fitted.push('.');
+        fitted.push_str(frac);
+        fitted.extend(std::iter::repeat('0').take(scale - frac.len()));
+    }
+    Ok(fitted)
+}
+
 impl<T> ToMysqlValue for Option<T>
 where
     T: ToMysqlValue,
@@ -60,6 +184,10 @@ where
     fn is_null(&self) -> bool {
         self.is_none()
     }
+
+    fn hint(&self) -> Hint<'_> {
+        self.as_ref().map_or(Hint::None, ToMysqlValue::hint)
+    }
 }
 
 // NOTE: these rules can all go away when TryFrom stabilizes
@@ -348,6 +476,62 @@ impl ToMysqlValue for f64 {
     }
 }
 
+/// Booleans are sent as 1 and 0, as MySQL stores them in `TINYINT(1)` columns. They can also be
+/// written to other integer columns, and to `BIT(1)` columns, which get the bit itself in both
+/// protocols.
+impl ToMysqlValue for bool {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        u8::from(*self).to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        match c.coltype {
+            ColumnType::MYSQL_TYPE_TINY => w.write_u8(u8::from(*self)),
+            ColumnType::MYSQL_TYPE_BIT => w.write_lenenc_str(&[u8::from(*self)]).map(|_| ()),
+            _ => u8::from(*self).to_mysql_bin(w, c),
+        }
+    }
+    fn hint(&self) -> Hint<'_> {
+        Hint::Bool(*self)
+    }
+}
+
+macro_rules! wide_numeric {
+    ($t:ty) => {
+        /// 128-bit integers are sent as text to `DECIMAL` columns, such as `DECIMAL(38)`, if they
+        /// fit their precision, and can be written to `BIGINT` columns if they fit.
+        impl ToMysqlValue for $t {
+            mysql_text_trivial!();
+            fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+                match c.coltype {
+                    ColumnType::MYSQL_TYPE_DECIMAL | ColumnType::MYSQL_TYPE_NEWDECIMAL => {
+                        let text = fit_decimal(&self.to_string(), c)?;
+                        w.write_lenenc_str(text.as_bytes()).map(|_| ())
+                    }
+                    ColumnType::MYSQL_TYPE_LONGLONG
+                        if c.colflags.contains(ColumnFlags::UNSIGNED_FLAG) =>
+                    {
+                        match u64::try_from(*self) {
+                            Ok(v) => w.write_u64::<LittleEndian>(v),
+                            Err(_) => Err(bad(self, c)),
+                        }
+                    }
+                    ColumnType::MYSQL_TYPE_LONGLONG => match i64::try_from(*self) {
+                        Ok(v) => w.write_i64::<LittleEndian>(v),
+                        Err(_) => Err(bad(self, c)),
+                    },
+                    _ => Err(bad(self, c)),
+                }
+            }
+            fn hint(&self) -> Hint<'_> {
+                Hint::Decimal(self.to_string())
+            }
+        }
+    };
+}
+
+wide_numeric!(i128);
+wide_numeric!(u128);
+
 impl ToMysqlValue for String {
     fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
         self.as_bytes().to_mysql_text(w)
@@ -355,6 +539,9 @@ impl ToMysqlValue for String {
     fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
         self.as_bytes().to_mysql_bin(w, c)
     }
+    fn hint(&self) -> Hint<'_> {
+        Hint::Text(self)
+    }
 }
 
 impl ToMysqlValue for str {
@@ -364,6 +551,9 @@ impl ToMysqlValue for str {
     fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
         self.as_bytes().to_mysql_bin(w, c)
     }
+    fn hint(&self) -> Hint<'_> {
+        Hint::Text(self)
+    }
 }
 
 impl ToMysqlValue for [u8] {
@@ -410,9 +600,66 @@ where
     fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
         (*self).to_mysql_bin(w, c)
     }
+    fn hint(&self) -> Hint<'_> {
+        (*self).hint()
+    }
+}
+
+macro_rules! smart_pointer {
+    ($($p:ty),*) => {
+        $(
+            impl<T> ToMysqlValue for $p
+            where
+                T: ToMysqlValue + ?Sized,
+            {
+                fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+                    (**self).to_mysql_text(w)
+                }
+                fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+                    (**self).to_mysql_bin(w, c)
+                }
+                fn is_null(&self) -> bool {
+                    (**self).is_null()
+                }
+                fn hint(&self) -> Hint<'_> {
+                    (**self).hint()
+                }
+            }
+        )*
+    };
+}
+
+smart_pointer!(Box<T>, std::rc::Rc<T>, std::sync::Arc<T>);
+
+impl<'a, T> ToMysqlValue for std::borrow::Cow<'a, T>
+where
+    T: ToMysqlValue + ToOwned + ?Sized,
+{
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        (**self).to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        (**self).to_mysql_bin(w, c)
+    }
+    fn is_null(&self) -> bool {
+        (**self).is_null()
+    }
+    fn hint(&self) -> Hint<'_> {
+        (**self).hint()
+    }
 }
 
-use chrono::{self, Datelike, NaiveDate, NaiveDateTime, Timelike};
+#[cfg(feature = "bytes")]
+impl ToMysqlValue for bytes::Bytes {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self[..].to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        self[..].to_mysql_bin(w, c)
+    }
+}
+
+use chrono::{self, DateTime, Datelike, NaiveDate, Timelike, Utc};
 impl ToMysqlValue for NaiveDate {
     fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
         w.write_lenenc_str(
@@ -495,6 +742,18 @@ impl ToMysqlValue for NaiveDateTime {
     }
 }
 
+impl ToMysqlValue for DateTime<Utc> {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.naive_utc().to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        self.naive_utc().to_mysql_bin(w, c)
+    }
+    fn hint(&self) -> Hint<'_> {
+        Hint::Utc(self.naive_utc())
+    }
+}
+
 use std::time::Duration;
 impl ToMysqlValue for Duration {
     fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
@@ -667,6 +926,7 @@ mod tests {
     use super::ToMysqlValue;
     use crate::myc::value;
     use crate::myc::value::convert::from_value;
+    use crate::value::bin;
     use crate::{Column, ColumnFlags, ColumnType};
     use chrono::{self, TimeZone};
     use std::time;
@@ -727,6 +987,8 @@ mod tests {
         rt!(dur_zero, time::Duration, time::Duration::from_secs(0));
         rt!(bytes, Vec<u8>, vec![0x42, 0x00, 0x1a]);
         rt!(string, String, "foobar".to_owned());
+        rt!(bool_true, bool, true);
+        rt!(bool_false, bool, false);
     }
 
     mod roundtrip_bin {
@@ -745,6 +1007,7 @@ mod tests {
                         column: String::new(),
                         coltype: $ct,
                         colflags: ColumnFlags::empty(),
+                        ..Default::default()
                     };
 
                     if !$sig {
@@ -912,5 +1175,130 @@ mod tests {
             "foobar".to_owned(),
             ColumnType::MYSQL_TYPE_STRING
         );
+        rt!(bool_tiny, bool, true, ColumnType::MYSQL_TYPE_TINY, true);
+        rt!(bool_long, bool, true, ColumnType::MYSQL_TYPE_LONG, false);
+        rt!(
+            i128_longlong,
+            i128,
+            -1,
+            ColumnType::MYSQL_TYPE_LONGLONG,
+            true
+        );
+        rt!(
+            u128_longlong,
+            u128,
+            1,
+            ColumnType::MYSQL_TYPE_LONGLONG,
+            false
+        );
+    }
+
+    #[test]
+    fn it_fits_decimals() {
+        use super::fit_decimal;
+        // DECIMAL(5, 2)
+        let c = Column::new("", "d", ColumnType::MYSQL_TYPE_NEWDECIMAL)
+            .with_length(7)
+            .with_decimals(2);
+        assert_eq!(fit_decimal("-123.4", &c).unwrap(), "-123.40");
+        assert_eq!(fit_decimal("+007", &c).unwrap(), "7.00");
+        assert_eq!(fit_decimal(".5", &c).unwrap(), "0.50");
+        assert_eq!(fit_decimal("-0", &c).unwrap(), "0.00");
+        assert!(fit_decimal("1234", &c).is_err());
+        assert!(fit_decimal("1.234", &c).is_err());
+        assert!(fit_decimal("", &c).is_err());
+        assert!(fit_decimal("-", &c).is_err());
+        assert!(fit_decimal("1e3", &c).is_err());
+        assert!(fit_decimal("1.2.3", &c).is_err());
+        assert_eq!(fit_decimal("1.500", &c).unwrap(), "1.50");
+        assert!(fit_decimal("1.2340", &c).is_err());
+
+        // DECIMAL(3, 0) UNSIGNED
+        let c = Column::new("", "d", ColumnType::MYSQL_TYPE_NEWDECIMAL)
+            .with_length(3)
+            .with_flags(ColumnFlags::UNSIGNED_FLAG);
+        assert_eq!(fit_decimal("999", &c).unwrap(), "999");
+        assert_eq!(fit_decimal("-0", &c).unwrap(), "0");
+        assert!(fit_decimal("-1", &c).is_err());
+        assert!(fit_decimal("1000", &c).is_err());
+        assert_eq!(fit_decimal("12.0", &c).unwrap(), "12");
+        assert!(fit_decimal("12.5", &c).is_err());
+
+        // no declared precision
+        let c = Column::new("", "d", ColumnType::MYSQL_TYPE_NEWDECIMAL).with_decimals(1);
+        assert_eq!(fit_decimal("123456789", &c).unwrap(), "123456789.0");
+
+        // neither precision nor scale
+        let c = Column::new("", "d", ColumnType::MYSQL_TYPE_NEWDECIMAL);
+        assert_eq!(fit_decimal("-1.250", &c).unwrap(), "-1.250");
+        assert_eq!(fit_decimal("7", &c).unwrap(), "7");
+    }
+
+    #[test]
+    fn it_encodes_wide_integers() {
+        let c = Column::new("", "d", ColumnType::MYSQL_TYPE_NEWDECIMAL);
+        assert_eq!(
+            &bin(i128::MIN, &c).unwrap()[1..],
+            i128::MIN.to_string().as_bytes()
+        );
+        assert_eq!(
+            &bin(u128::MAX, &c).unwrap()[1..],
+            u128::MAX.to_string().as_bytes()
+        );
+        assert_eq!(
+            &bin(-5i128, &c.clone().with_decimals(2)).unwrap()[1..],
+            b"-5.00"
+        );
+        // DECIMAL(3, 0)
+        assert!(bin(1000i128, &c.clone().with_length(4)).is_err());
+        let unsigned = c.clone().with_flags(ColumnFlags::UNSIGNED_FLAG);
+        assert!(bin(-1i128, &unsigned).is_err());
+
+        let c = Column::new("", "n", ColumnType::MYSQL_TYPE_LONGLONG);
+        assert!(bin(i128::from(i64::MAX) + 1, &c).is_err());
+        let c = c.with_flags(ColumnFlags::UNSIGNED_FLAG);
+        assert!(bin(-1i128, &c).is_err());
+        let c = Column::new("", "n", ColumnType::MYSQL_TYPE_LONG);
+        assert!(bin(1u128, &c).is_err());
+    }
+
+    #[test]
+    fn it_encodes_smart_pointers() {
+        use std::borrow::Cow;
+        use std::rc::Rc;
+        use std::sync::Arc;
+
+        let c = Column::new("", "s", ColumnType::MYSQL_TYPE_STRING);
+        let expected = bin("foo", &c).unwrap();
+        assert_eq!(bin(Box::<str>::from("foo"), &c).unwrap(), expected);
+        assert_eq!(bin(Arc::<str>::from("foo"), &c).unwrap(), expected);
+        assert_eq!(bin(Rc::<str>::from("foo"), &c).unwrap(), expected);
+        assert_eq!(bin(Cow::Borrowed("foo"), &c).unwrap(), expected);
+        assert_eq!(
+            bin(Cow::<str>::Owned("foo".to_owned()), &c).unwrap(),
+            expected
+        );
+        assert!(matches!(
+            Arc::<str>::from("foo").hint(),
+            super::Hint::Text("foo")
+        ));
+        assert!(Box::new(None::<u8>).is_null());
+        assert_eq!(
+            bin(Cow::Borrowed(&b"foo"[..]), &c).unwrap(),
+            bin(b"foo".to_vec(), &c).unwrap()
+        );
+
+        let c = Column::new("", "b", ColumnType::MYSQL_TYPE_BIT);
+        assert_eq!(bin(true, &c).unwrap(), [1, 1]);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn it_encodes_bytes() {
+        let c = Column::new("", "b", ColumnType::MYSQL_TYPE_BLOB);
+        assert_eq!(
+            bin(bytes::Bytes::from_static(b"\x00\x01"), &c).unwrap(),
+            [2, 0, 1]
+        );
     }
 }
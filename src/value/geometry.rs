@@ -0,0 +1,460 @@
+//! Support for spatial `GEOMETRY` values with `geo-types`.
+//!
+//! MySQL stores and sends geometries as a little-endian SRID, followed by the geometry in the
+//! well-known binary (WKB) format.
+
+use super::decode::{bad_conversion, Value, ValueInner};
+use super::encode::bad;
+use crate::myc::constants::ColumnType;
+use crate::{Column, FromMysqlValue, ToMysqlValue};
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use geo_types::{
+    Coord, Geometry, GeometryCollection, LineString, MultiLineString, MultiPoint, MultiPolygon,
+    Point, Polygon,
+};
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+/// A geometry along with the identifier of its spatial reference system.
+///
+/// Geometries that are written to a column without a `Spatial` have SRID 0, which is the
+/// Cartesian plane.
+///
+/// ```
+/// # use msql_srv::Spatial;
+/// use geo_types::Point;
+///
+/// let here = Spatial {
+///     srid: 4326,
+///     geometry: Point::new(10.75, 59.91).into(),
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spatial {
+    /// The spatial reference system identifier, such as 4326 for WGS 84.
+    pub srid: u32,
+    /// The geometry.
+    pub geometry: Geometry<f64>,
+}
+
+const POINT: u32 = 1;
+const LINE_STRING: u32 = 2;
+const POLYGON: u32 = 3;
+const MULTI_POINT: u32 = 4;
+const MULTI_LINE_STRING: u32 = 5;
+const MULTI_POLYGON: u32 = 6;
+const GEOMETRY_COLLECTION: u32 = 7;
+
+/// How deeply geometry collections may be nested in parameters.
+const MAX_DEPTH: usize = 32;
+
+fn encode(srid: u32, write: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+    let mut w = srid.to_le_bytes().to_vec();
+    write(&mut w);
+    w
+}
+
+fn header(w: &mut Vec<u8>, kind: u32) {
+    // little-endian
+    w.push(1);
+    w.extend_from_slice(&kind.to_le_bytes());
+}
+
+fn count(w: &mut Vec<u8>, n: usize) {
+    w.extend_from_slice(&(n as u32).to_le_bytes());
+}
+
+fn coord(w: &mut Vec<u8>, c: Coord<f64>) {
+    w.extend_from_slice(&c.x.to_le_bytes());
+    w.extend_from_slice(&c.y.to_le_bytes());
+}
+
+fn ring(w: &mut Vec<u8>, l: &LineString<f64>) {
+    count(w, l.0.len());
+    for &c in &l.0 {
+        coord(w, c);
+    }
+}
+
+fn point(w: &mut Vec<u8>, p: &Point<f64>) {
+    header(w, POINT);
+    coord(w, p.0);
+}
+
+fn line_string(w: &mut Vec<u8>, l: &LineString<f64>) {
+    header(w, LINE_STRING);
+    ring(w, l);
+}
+
+fn polygon(w: &mut Vec<u8>, p: &Polygon<f64>) {
+    header(w, POLYGON);
+    if p.exterior().0.is_empty() {
+        return count(w, 0);
+    }
+    count(w, 1 + p.interiors().len());
+    ring(w, p.exterior());
+    for interior in p.interiors() {
+        ring(w, interior);
+    }
+}
+
+fn multi_point(w: &mut Vec<u8>, m: &MultiPoint<f64>) {
+    header(w, MULTI_POINT);
+    count(w, m.0.len());
+    for p in &m.0 {
+        point(w, p);
+    }
+}
+
+fn multi_line_string(w: &mut Vec<u8>, m: &MultiLineString<f64>) {
+    header(w, MULTI_LINE_STRING);
+    count(w, m.0.len());
+    for l in &m.0 {
+        line_string(w, l);
+    }
+}
+
+fn multi_polygon(w: &mut Vec<u8>, m: &MultiPolygon<f64>) {
+    header(w, MULTI_POLYGON);
+    count(w, m.0.len());
+    for p in &m.0 {
+        polygon(w, p);
+    }
+}
+
+fn geometry_collection(w: &mut Vec<u8>, c: &GeometryCollection<f64>) {
+    header(w, GEOMETRY_COLLECTION);
+    count(w, c.0.len());
+    for g in &c.0 {
+        geometry(w, g);
+    }
+}
+
+/// Lines, rectangles and triangles have no WKB type of their own, and are sent as line strings
+/// and polygons.
+fn geometry(w: &mut Vec<u8>, g: &Geometry<f64>) {
+    match g {
+        Geometry::Point(p) => point(w, p),
+        Geometry::Line(l) => line_string(w, &LineString(vec![l.start, l.end])),
+        Geometry::LineString(l) => line_string(w, l),
+        Geometry::Polygon(p) => polygon(w, p),
+        Geometry::MultiPoint(m) => multi_point(w, m),
+        Geometry::MultiLineString(m) => multi_line_string(w, m),
+        Geometry::MultiPolygon(m) => multi_polygon(w, m),
+        Geometry::GeometryCollection(c) => geometry_collection(w, c),
+        Geometry::Rect(r) => polygon(w, &r.to_polygon()),
+        Geometry::Triangle(t) => polygon(w, &t.to_polygon()),
+    }
+}
+
+fn write_geometry<W: Write>(data: Vec<u8>, w: &mut W, c: Option<&Column>) -> io::Result<()> {
+    match c {
+        Some(c) if c.coltype != ColumnType::MYSQL_TYPE_GEOMETRY => Err(bad(data, c)),
+        Some(c) => data.to_mysql_bin(w, c),
+        None => data.to_mysql_text(w),
+    }
+}
+
+macro_rules! geometry_to_mysql {
+    ($($t:ty => $write:ident),*) => {
+        $(
+            impl ToMysqlValue for $t {
+                fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+                    write_geometry(encode(0, |data| $write(data, self)), w, None)
+                }
+                fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+                    write_geometry(encode(0, |data| $write(data, self)), w, Some(c))
+                }
+            }
+        )*
+    };
+}
+
+geometry_to_mysql!(
+    Point<f64> => point,
+    LineString<f64> => line_string,
+    Polygon<f64> => polygon,
+    MultiPoint<f64> => multi_point,
+    MultiLineString<f64> => multi_line_string,
+    MultiPolygon<f64> => multi_polygon,
+    GeometryCollection<f64> => geometry_collection,
+    Geometry<f64> => geometry
+);
+
+impl ToMysqlValue for Spatial {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_geometry(
+            encode(self.srid, |data| geometry(data, &self.geometry)),
+            w,
+            None,
+        )
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        let data = encode(self.srid, |data| geometry(data, &self.geometry));
+        write_geometry(data, w, Some(c))
+    }
+}
+
+/// Reads WKB in either byte order.
+struct Reader<'a> {
+    b: &'a [u8],
+    little_endian: bool,
+}
+
+impl Reader<'_> {
+    fn u32(&mut self) -> Option<u32> {
+        if self.little_endian {
+            self.b.read_u32::<LittleEndian>().ok()
+        } else {
+            self.b.read_u32::<BigEndian>().ok()
+        }
+    }
+
+    fn coord(&mut self) -> Option<Coord<f64>> {
+        let (x, y) = if self.little_endian {
+            (
+                self.b.read_f64::<LittleEndian>(),
+                self.b.read_f64::<LittleEndian>(),
+            )
+        } else {
+            (
+                self.b.read_f64::<BigEndian>(),
+                self.b.read_f64::<BigEndian>(),
+            )
+        };
+        Some(Coord {
+            x: x.ok()?,
+            y: y.ok()?,
+        })
+    }
+
+    fn ring(&mut self) -> Option<LineString<f64>> {
+        (0..self.u32()?).map(|_| self.coord()).collect()
+    }
+
+    /// The members of a multi-geometry, which must all be of the given `kind`, if any.
+    fn members(&mut self, depth: usize, kind: Option<u32>) -> Option<Vec<Geometry<f64>>> {
+        (0..self.u32()?)
+            .map(|_| {
+                let g = self.geometry(depth + 1)?;
+                match (kind, &g) {
+                    (Some(POINT), Geometry::Point(_))
+                    | (Some(LINE_STRING), Geometry::LineString(_))
+                    | (Some(POLYGON), Geometry::Polygon(_))
+                    | (None, _) => Some(g),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    fn geometry(&mut self, depth: usize) -> Option<Geometry<f64>> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        self.little_endian = match self.b.read_u8().ok()? {
+            0 => false,
+            1 => true,
+            _ => return None,
+        };
+        let g = match self.u32()? {
+            POINT => Geometry::Point(Point(self.coord()?)),
+            LINE_STRING => Geometry::LineString(self.ring()?),
+            POLYGON => {
+                let mut rings = (0..self.u32()?)
+                    .map(|_| self.ring())
+                    .collect::<Option<Vec<_>>>()?;
+                let exterior = if rings.is_empty() {
+                    LineString(Vec::new())
+                } else {
+                    rings.remove(0)
+                };
+                Geometry::Polygon(Polygon::new(exterior, rings))
+            }
+            MULTI_POINT => Geometry::MultiPoint(
+                self.members(depth, Some(POINT))?
+                    .into_iter()
+                    .filter_map(|g| Point::try_from(g).ok())
+                    .collect(),
+            ),
+            MULTI_LINE_STRING => Geometry::MultiLineString(MultiLineString(
+                self.members(depth, Some(LINE_STRING))?
+                    .into_iter()
+                    .filter_map(|g| LineString::try_from(g).ok())
+                    .collect(),
+            )),
+            MULTI_POLYGON => Geometry::MultiPolygon(MultiPolygon(
+                self.members(depth, Some(POLYGON))?
+                    .into_iter()
+                    .filter_map(|g| Polygon::try_from(g).ok())
+                    .collect(),
+            )),
+            GEOMETRY_COLLECTION => {
+                Geometry::GeometryCollection(GeometryCollection(self.members(depth, None)?))
+            }
+            _ => return None,
+        };
+        Some(g)
+    }
+}
+
+/// `GEOMETRY` parameters are sent as bytes, in the same format as MySQL stores them.
+impl<'a> FromMysqlValue<'a> for Spatial {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        let mut b = match v.into_inner() {
+            ValueInner::Bytes(b) => b,
+            _ => return Err(bad_conversion::<Self>(v)),
+        };
+        let srid = b
+            .read_u32::<LittleEndian>()
+            .map_err(|_| bad_conversion::<Self>(v))?;
+        let mut r = Reader {
+            b,
+            little_endian: true,
+        };
+        match r.geometry(0) {
+            Some(geometry) if r.b.is_empty() => Ok(Spatial { srid, geometry }),
+            _ => Err(bad_conversion::<Self>(v)),
+        }
+    }
+}
+
+/// The SRID of the parameter is ignored. Use [`Spatial`] to get it as well.
+impl<'a> FromMysqlValue<'a> for Geometry<f64> {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        Spatial::from_mysql_value(v).map(|s| s.geometry)
+    }
+}
+
+macro_rules! geometry_from_mysql {
+    ($($t:ty),*) => {
+        $(
+            /// The SRID of the parameter is ignored. Use [`Spatial`] to get it as well.
+            impl<'a> FromMysqlValue<'a> for $t {
+                fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+                    <$t>::try_from(Geometry::from_mysql_value(v)?)
+                        .map_err(|_| bad_conversion::<Self>(v))
+                }
+            }
+        )*
+    };
+}
+
+geometry_from_mysql!(
+    Point<f64>,
+    LineString<f64>,
+    Polygon<f64>,
+    MultiPoint<f64>,
+    MultiLineString<f64>,
+    MultiPolygon<f64>,
+    GeometryCollection<f64>
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::value::{read_bin_value, Value as MycValue};
+    use crate::value::bin;
+    use geo_types::{Line, Rect};
+
+    fn wkb<T: ToMysqlValue>(v: T) -> Vec<u8> {
+        let c = Column::new("", "g", ColumnType::MYSQL_TYPE_GEOMETRY);
+        let data = bin(v, &c).unwrap();
+        match read_bin_value(&mut &data[..], ColumnType::MYSQL_TYPE_GEOMETRY, false).unwrap() {
+            MycValue::Bytes(b) => b,
+            v => unreachable!("{:?}", v),
+        }
+    }
+
+    fn square() -> Polygon<f64> {
+        Polygon::new(
+            LineString::from(vec![(0., 0.), (4., 0.), (4., 4.), (0., 4.), (0., 0.)]),
+            vec![LineString::from(vec![
+                (1., 1.),
+                (2., 1.),
+                (2., 2.),
+                (1., 1.),
+            ])],
+        )
+    }
+
+    #[test]
+    fn it_encodes_geometries() {
+        let c = Column::new("", "g", ColumnType::MYSQL_TYPE_GEOMETRY);
+        assert_eq!(c.character_set, 63);
+
+        let here = Spatial {
+            srid: 4326,
+            geometry: Point::new(1.5, -2.0).into(),
+        };
+        let mut expected = vec![0xe6, 0x10, 0, 0, 1, 1, 0, 0, 0];
+        expected.extend_from_slice(&1.5f64.to_le_bytes());
+        expected.extend_from_slice(&(-2.0f64).to_le_bytes());
+        assert_eq!(wkb(&here), expected);
+        assert_eq!(&wkb(Point::new(1.5, -2.0))[..4], [0, 0, 0, 0]);
+
+        let mut text = Vec::new();
+        here.to_mysql_text(&mut text).unwrap();
+        assert_eq!(&text[1..], &expected[..]);
+
+        let line = Geometry::Line(Line::new((0., 0.), (1., 1.)));
+        assert_eq!(wkb(line), wkb(LineString::from(vec![(0., 0.), (1., 1.)])));
+        let rect = Geometry::Rect(Rect::new((0., 0.), (4., 4.)));
+        assert_eq!(wkb(rect), wkb(Rect::new((0., 0.), (4., 4.)).to_polygon()));
+
+        let c = Column::new("", "b", ColumnType::MYSQL_TYPE_BLOB);
+        assert!(bin(Point::new(0., 0.), &c).is_err());
+    }
+
+    #[test]
+    fn it_decodes_geometries() {
+        let roundtrip = |g: Geometry<f64>| {
+            let data = wkb(&g);
+            assert_eq!(
+                Value(ValueInner::Bytes(&data))
+                    .try_get::<Geometry<f64>>()
+                    .unwrap(),
+                g
+            );
+        };
+        roundtrip(Point::new(1.5, -2.0).into());
+        roundtrip(LineString::from(vec![(0., 0.), (1., 1.)]).into());
+        roundtrip(square().into());
+        roundtrip(Polygon::new(LineString(Vec::new()), Vec::new()).into());
+        roundtrip(MultiPoint::from(vec![(0., 0.), (1., 1.)]).into());
+        roundtrip(MultiLineString(vec![LineString::from(vec![(0., 0.), (1., 1.)])]).into());
+        roundtrip(MultiPolygon(vec![square(), square()]).into());
+        roundtrip(Geometry::GeometryCollection(GeometryCollection(vec![
+            Point::new(0., 0.).into(),
+            Geometry::GeometryCollection(GeometryCollection(vec![square().into()])),
+        ])));
+
+        let data = wkb(Spatial {
+            srid: 3857,
+            geometry: square().into(),
+        });
+        let v = Value(ValueInner::Bytes(&data));
+        assert_eq!(v.try_get::<Spatial>().unwrap().srid, 3857);
+        assert_eq!(v.try_get::<Polygon<f64>>().unwrap(), square());
+        assert!(v.try_get::<Point<f64>>().is_err());
+
+        // big-endian WKB
+        let mut data = vec![0, 0, 0, 0, 0, 0, 0, 0, 1];
+        data.extend_from_slice(&1.5f64.to_be_bytes());
+        data.extend_from_slice(&2.5f64.to_be_bytes());
+        let v = Value(ValueInner::Bytes(&data));
+        assert_eq!(v.try_get::<Point<f64>>().unwrap(), Point::new(1.5, 2.5));
+
+        let data = wkb(Point::new(0., 0.));
+        let bad = |b: &[u8]| Value(ValueInner::Bytes(b)).try_get::<Spatial>().is_err();
+        assert!(bad(&data[..data.len() - 1]));
+        assert!(bad(&[&data[..], &[0]].concat()));
+        assert!(bad(&[0, 0, 0, 0, 1, 8, 0, 0, 0]));
+        assert!(bad(&[0, 0, 0]));
+
+        // a multi-point must only hold points
+        let mut data = vec![0, 0, 0, 0, 1, 4, 0, 0, 0, 1, 0, 0, 0];
+        data.extend_from_slice(&wkb(LineString(Vec::new()))[4..]);
+        assert!(bad(&data));
+    }
+}
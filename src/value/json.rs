@@ -0,0 +1,132 @@
+//! Support for `JSON` values with `serde_json`.
+
+use super::decode::{bad_conversion, Value, ValueInner};
+use crate::{Column, FromMysqlValue, ToMysqlValue};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+/// A value that is sent to and received from the client as JSON.
+///
+/// Any `T: Serialize` can be written to a `JSON` column (or a text column) by wrapping it in
+/// `Json`, and any `T: Deserialize` can be read from a parameter the client sent as JSON text:
+///
+/// ```no_run
+/// # use msql_srv::{Json, ParamParser};
+/// # use std::io;
+/// #[derive(serde::Deserialize)]
+/// struct Settings {
+///     theme: String,
+/// }
+///
+/// # fn on_execute(params: ParamParser) -> io::Result<()> {
+/// let (id, Json(settings)): (u32, Json<Settings>) = params.parse()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Json<T>(pub T);
+
+fn encode<T: Serialize + ?Sized>(v: &T) -> io::Result<Vec<u8>> {
+    serde_json::to_vec(v).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+impl<T: Serialize> ToMysqlValue for Json<T> {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        encode(&self.0)?.to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        encode(&self.0)?.to_mysql_bin(w, c)
+    }
+}
+
+impl ToMysqlValue for serde_json::Value {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        encode(self)?.to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        encode(self)?.to_mysql_bin(w, c)
+    }
+}
+
+impl<'a, T: Deserialize<'a>> FromMysqlValue<'a> for Json<T> {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        match v.into_inner() {
+            // JSON parameters are sent as text
+            ValueInner::Bytes(b) => serde_json::from_slice(b).map(Json).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "cannot convert {:?} to {}: {}",
+                        v.into_inner(),
+                        std::any::type_name::<T>(),
+                        e
+                    ),
+                )
+            }),
+            _ => Err(bad_conversion::<Self>(v)),
+        }
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for serde_json::Value {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        Json::from_mysql_value(v).map(|Json(v)| v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::constants::ColumnType;
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Settings<'a> {
+        theme: &'a str,
+        width: u32,
+    }
+
+    #[test]
+    fn it_encodes_json() {
+        let c = Column::new("", "settings", ColumnType::MYSQL_TYPE_JSON);
+        assert_eq!(c.character_set, 63);
+
+        let settings = Json(Settings {
+            theme: "dark",
+            width: 80,
+        });
+        let mut data = Vec::new();
+        settings.to_mysql_bin(&mut data, &c).unwrap();
+        assert_eq!(&data[1..], br#"{"theme":"dark","width":80}"#);
+        assert_eq!(usize::from(data[0]), data.len() - 1);
+
+        let mut text = Vec::new();
+        json!({"a": [1, null]}).to_mysql_text(&mut text).unwrap();
+        assert_eq!(&text[1..], br#"{"a":[1,null]}"#);
+
+        let c = Column::new("", "n", ColumnType::MYSQL_TYPE_LONG);
+        assert!(json!(1).to_mysql_bin(&mut Vec::new(), &c).is_err());
+    }
+
+    #[test]
+    fn it_decodes_json() {
+        let v = Value(ValueInner::Bytes(br#"{"theme": "dark", "width": 80}"#));
+        assert_eq!(
+            v.try_get::<Json<Settings<'_>>>().unwrap().0,
+            Settings {
+                theme: "dark",
+                width: 80
+            }
+        );
+        assert_eq!(
+            v.try_get::<serde_json::Value>().unwrap(),
+            json!({"theme": "dark", "width": 80})
+        );
+        assert!(Value(ValueInner::Bytes(b"{"))
+            .try_get::<serde_json::Value>()
+            .is_err());
+        assert!(Value(ValueInner::Int(1))
+            .try_get::<serde_json::Value>()
+            .is_err());
+    }
+}
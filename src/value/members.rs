@@ -0,0 +1,158 @@
+//! Support for `ENUM` and `SET` values, which are members of a list attached to their column.
+//!
+//! `BTreeSet` and `HashSet` stand for `SET` values, which are sent in either direction as the
+//! comma-separated list of their members. When a set is written to a `SET` column that has
+//! [`members`](Column::members), it is checked against and ordered like those.
+
+use super::decode::{text, Value};
+use super::encode::Hint;
+use crate::myc::constants::ColumnFlags;
+use crate::{Column, FromMysqlValue, ToMysqlValue};
+use std::collections::{BTreeSet, HashSet};
+use std::hash::{BuildHasher, Hash};
+use std::io::{self, Write};
+
+/// Check that the string `s`, which is about to be written to `c`, is a member of its `ENUM`, or a
+/// comma-separated list of members of its `SET`.
+///
+/// Columns without [`members`](Column::members) accept any string. The empty string is always
+/// accepted, as it is what MySQL stores for invalid `ENUM` values, and for the empty `SET`.
+pub(crate) fn check(c: &Column, s: &str) -> io::Result<()> {
+    if c.members.is_empty() || s.is_empty() {
+        return Ok(());
+    }
+    let is_member = |m: &str| c.members.iter().any(|member| member == m);
+    let ok = if c.colflags.contains(ColumnFlags::SET_FLAG) {
+        s.split(',').all(is_member)
+    } else if c.colflags.contains(ColumnFlags::ENUM_FLAG) {
+        is_member(s)
+    } else {
+        true
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{:?} is not one of {:?} of column {}",
+                s, c.members, c.column
+            ),
+        ))
+    }
+}
+
+/// Join the members of a set with commas, in the order they are declared in `c`, as MySQL does.
+pub(crate) fn join(c: Option<&Column>, mut set: Vec<&str>) -> String {
+    if let Some(c) = c {
+        set.sort_by_key(|m| c.members.iter().position(|member| member == m));
+    }
+    set.join(",")
+}
+
+fn members<'a, S: AsRef<str> + 'a>(set: impl IntoIterator<Item = &'a S>) -> Vec<&'a str> {
+    set.into_iter().map(AsRef::as_ref).collect()
+}
+
+impl<S: AsRef<str>> ToMysqlValue for BTreeSet<S> {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        join(None, members(self)).to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        join(Some(c), members(self)).to_mysql_bin(w, c)
+    }
+    fn hint(&self) -> Hint<'_> {
+        Hint::Set(members(self))
+    }
+}
+
+impl<S: AsRef<str>, H: BuildHasher> ToMysqlValue for HashSet<S, H> {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        join(None, members(self)).to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        join(Some(c), members(self)).to_mysql_bin(w, c)
+    }
+    fn hint(&self) -> Hint<'_> {
+        Hint::Set(members(self))
+    }
+}
+
+fn split<'a, T>(v: Value<'a>) -> io::Result<impl Iterator<Item = &'a str>> {
+    Ok(text::<T>(v)?.split(',').filter(|m| !m.is_empty()))
+}
+
+impl<'a, S: From<&'a str> + Ord> FromMysqlValue<'a> for BTreeSet<S> {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        Ok(split::<Self>(v)?.map(S::from).collect())
+    }
+}
+
+impl<'a, S: From<&'a str> + Eq + Hash, H: BuildHasher + Default> FromMysqlValue<'a>
+    for HashSet<S, H>
+{
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        Ok(split::<Self>(v)?.map(S::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::constants::ColumnType;
+    use crate::ValueInner;
+
+    fn weekdays() -> Column {
+        Column::new("", "days", ColumnType::MYSQL_TYPE_STRING).with_set(&["mon", "tue", "wed"])
+    }
+
+    #[test]
+    fn it_checks_members() {
+        let c = Column::new("", "size", ColumnType::MYSQL_TYPE_STRING).with_enum(&["s", "m"]);
+        assert!(c.colflags.contains(ColumnFlags::ENUM_FLAG));
+        assert!(check(&c, "m").is_ok());
+        assert!(check(&c, "").is_ok());
+        assert!(check(&c, "xl").is_err());
+        assert!(check(&c, "s,m").is_err());
+
+        let c = weekdays();
+        assert!(c.colflags.contains(ColumnFlags::SET_FLAG));
+        assert!(check(&c, "mon,wed").is_ok());
+        assert!(check(&c, "mon,sun").is_err());
+
+        let c = Column::new("", "any", ColumnType::MYSQL_TYPE_STRING);
+        assert!(check(&c, "xl").is_ok());
+    }
+
+    #[test]
+    fn it_encodes_sets() {
+        let set: BTreeSet<_> = ["wed", "mon"].iter().copied().collect();
+        let mut data = Vec::new();
+        set.to_mysql_bin(&mut data, &weekdays()).unwrap();
+        assert_eq!(&data[1..], b"mon,wed");
+
+        let set: HashSet<String> = ["tue".to_owned()].iter().cloned().collect();
+        let mut data = Vec::new();
+        set.to_mysql_text(&mut data).unwrap();
+        assert_eq!(&data[1..], b"tue");
+
+        let mut data = Vec::new();
+        BTreeSet::<&str>::new().to_mysql_text(&mut data).unwrap();
+        assert_eq!(data, [0]);
+    }
+
+    #[test]
+    fn it_decodes_sets() {
+        let v = Value(ValueInner::Bytes(b"mon,wed"));
+        let set: BTreeSet<&str> = v.try_get().unwrap();
+        assert_eq!(set.into_iter().collect::<Vec<_>>(), ["mon", "wed"]);
+        let set: HashSet<String> = v.try_get().unwrap();
+        assert!(set.contains("wed"));
+
+        let empty: BTreeSet<String> = Value(ValueInner::Bytes(b"")).try_get().unwrap();
+        assert!(empty.is_empty());
+        assert!(Value(ValueInner::Int(1))
+            .try_get::<BTreeSet<String>>()
+            .is_err());
+    }
+}
@@ -1,5 +1,36 @@
+mod bit;
+pub(crate) mod coerce;
+#[cfg(feature = "rust_decimal")]
+mod decimal;
 mod decode;
 mod encode;
+#[cfg(feature = "geo-types")]
+mod geometry;
+#[cfg(feature = "json")]
+mod json;
+pub(crate) mod members;
+mod owned;
+mod temporal;
+#[cfg(feature = "time03")]
+mod time03;
+#[cfg(feature = "uuid")]
+mod uuid;
 
-pub use self::decode::{Value, ValueInner};
-pub use self::encode::ToMysqlValue;
+pub use self::bit::Bit;
+pub use self::decode::{FromMysqlValue, Value, ValueInner};
+pub(crate) use self::encode::fit_decimal;
+pub use self::encode::{Hint, ToMysqlValue};
+#[cfg(feature = "geo-types")]
+pub use self::geometry::Spatial;
+#[cfg(feature = "json")]
+pub use self::json::Json;
+pub use self::owned::OwnedValue;
+pub use self::temporal::{MysqlDate, MysqlDateTime, MysqlTime};
+
+/// The binary protocol encoding of `v` in column `c`, for tests.
+#[cfg(test)]
+pub(crate) fn bin<T: ToMysqlValue>(v: T, c: &crate::Column) -> std::io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    v.to_mysql_bin(&mut data, c)?;
+    Ok(data)
+}
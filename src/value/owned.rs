@@ -0,0 +1,361 @@
+//! Owned values, which outlive the packet they were read from.
+
+use super::decode::{Value, ValueInner};
+use super::temporal::{MysqlDateTime, MysqlTime};
+use crate::myc;
+use crate::myc::constants::ColumnType;
+use crate::{Column, ToMysqlValue};
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+
+/// An owned counterpart of [`ValueInner`], for keeping values around after the packet they were
+/// sent in is gone, such as to cache, log or replay the parameters of a prepared statement.
+///
+/// Temporal values are kept in the binary encoding the client sent them in, so that they can be
+/// read back exactly as they came through [`as_value`](OwnedValue::as_value).
+///
+/// Two `Double`s are equal, and hash the same, when they have the same bits, so that an
+/// `OwnedValue` can be used as a key even when it holds a `NaN`.
+///
+/// ```
+/// # use msql_srv::{MysqlDate, OwnedValue};
+/// let v = OwnedValue::Date(vec![0xe4, 0x07, 2, 29]);
+/// assert_eq!(
+///     v.as_value().try_get::<MysqlDate>().unwrap().to_string(),
+///     "2020-02-29"
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub enum OwnedValue {
+    /// The MySQL `NULL` value.
+    NULL,
+    /// An untyped sequence of bytes (usually a text type or `MYSQL_TYPE_BLOB`).
+    Bytes(Vec<u8>),
+    /// A signed integer.
+    Int(i64),
+    /// An unsigned integer.
+    UInt(u64),
+    /// A floating point number.
+    Double(f64),
+    /// A [binary encoding](https://mariadb.com/kb/en/library/resultset-row/#date-binary-encoding)
+    /// of a `MYSQL_TYPE_DATE`.
+    Date(Vec<u8>),
+    /// A [binary encoding](https://mariadb.com/kb/en/library/resultset-row/#time-binary-encoding)
+    /// of a `MYSQL_TYPE_TIME`.
+    Time(Vec<u8>),
+    /// A [binary
+    /// encoding](https://mariadb.com/kb/en/library/resultset-row/#timestamp-binary-encoding) of a
+    /// `MYSQL_TYPE_TIMESTAMP` or `MYSQL_TYPE_DATETIME`.
+    Datetime(Vec<u8>),
+}
+
+impl OwnedValue {
+    /// Borrow this value as a [`Value`], to read it with [`Value::try_get`].
+    pub fn as_value(&self) -> Value<'_> {
+        Value(match *self {
+            OwnedValue::NULL => ValueInner::NULL,
+            OwnedValue::Bytes(ref b) => ValueInner::Bytes(b),
+            OwnedValue::Int(i) => ValueInner::Int(i),
+            OwnedValue::UInt(u) => ValueInner::UInt(u),
+            OwnedValue::Double(f) => ValueInner::Double(f),
+            OwnedValue::Date(ref b) => ValueInner::Date(b),
+            OwnedValue::Time(ref b) => ValueInner::Time(b),
+            OwnedValue::Datetime(ref b) => ValueInner::Datetime(b),
+        })
+    }
+
+    /// Returns true if this is a NULL value
+    pub fn is_null(&self) -> bool {
+        matches!(self, OwnedValue::NULL)
+    }
+}
+
+impl<'a> From<Value<'a>> for OwnedValue {
+    fn from(v: Value<'a>) -> Self {
+        match v.into_inner() {
+            ValueInner::NULL => OwnedValue::NULL,
+            ValueInner::Bytes(b) => OwnedValue::Bytes(b.to_vec()),
+            ValueInner::Int(i) => OwnedValue::Int(i),
+            ValueInner::UInt(u) => OwnedValue::UInt(u),
+            ValueInner::Double(f) => OwnedValue::Double(f),
+            ValueInner::Date(b) => OwnedValue::Date(b.to_vec()),
+            ValueInner::Time(b) => OwnedValue::Time(b.to_vec()),
+            ValueInner::Datetime(b) => OwnedValue::Datetime(b.to_vec()),
+        }
+    }
+}
+
+impl PartialEq for OwnedValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (OwnedValue::NULL, OwnedValue::NULL) => true,
+            (OwnedValue::Bytes(a), OwnedValue::Bytes(b))
+            | (OwnedValue::Date(a), OwnedValue::Date(b))
+            | (OwnedValue::Time(a), OwnedValue::Time(b))
+            | (OwnedValue::Datetime(a), OwnedValue::Datetime(b)) => a == b,
+            (OwnedValue::Int(a), OwnedValue::Int(b)) => a == b,
+            (OwnedValue::UInt(a), OwnedValue::UInt(b)) => a == b,
+            (OwnedValue::Double(a), OwnedValue::Double(b)) => a.to_bits() == b.to_bits(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for OwnedValue {}
+
+impl Hash for OwnedValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match *self {
+            OwnedValue::NULL => {}
+            OwnedValue::Bytes(ref b)
+            | OwnedValue::Date(ref b)
+            | OwnedValue::Time(ref b)
+            | OwnedValue::Datetime(ref b) => b.hash(state),
+            OwnedValue::Int(i) => i.hash(state),
+            OwnedValue::UInt(u) => u.hash(state),
+            OwnedValue::Double(f) => f.to_bits().hash(state),
+        }
+    }
+}
+
+/// Dates are sent as `DATE`s when written to a `DATE` column, and as `DATETIME`s otherwise.
+impl ToMysqlValue for OwnedValue {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        match *self {
+            OwnedValue::NULL => None::<u8>.to_mysql_text(w),
+            OwnedValue::Bytes(ref b) => b.to_mysql_text(w),
+            OwnedValue::Int(i) => i.to_mysql_text(w),
+            OwnedValue::UInt(u) => u.to_mysql_text(w),
+            OwnedValue::Double(f) => f.to_mysql_text(w),
+            OwnedValue::Date(_) | OwnedValue::Datetime(_) => {
+                let dt = self.as_value().try_get::<MysqlDateTime>()?;
+                if matches!(self, OwnedValue::Date(_)) && dt.is_midnight() {
+                    dt.date().to_mysql_text(w)
+                } else {
+                    dt.to_mysql_text(w)
+                }
+            }
+            OwnedValue::Time(_) => self.as_value().try_get::<MysqlTime>()?.to_mysql_text(w),
+        }
+    }
+
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        match *self {
+            OwnedValue::NULL => None::<u8>.to_mysql_bin(w, c),
+            OwnedValue::Bytes(ref b) => b.to_mysql_bin(w, c),
+            // integers are as lenient about the column type as mysql_common's are
+            OwnedValue::Int(i) => myc::value::Value::Int(i).to_mysql_bin(w, c),
+            OwnedValue::UInt(u) => u.to_mysql_bin(w, c),
+            OwnedValue::Double(f) => f.to_mysql_bin(w, c),
+            OwnedValue::Date(_) | OwnedValue::Datetime(_) => {
+                let dt = self.as_value().try_get::<MysqlDateTime>()?;
+                if c.coltype == ColumnType::MYSQL_TYPE_DATE && dt.is_midnight() {
+                    dt.date().to_mysql_bin(w, c)
+                } else {
+                    dt.to_mysql_bin(w, c)
+                }
+            }
+            OwnedValue::Time(_) => self.as_value().try_get::<MysqlTime>()?.to_mysql_bin(w, c),
+        }
+    }
+
+    fn is_null(&self) -> bool {
+        OwnedValue::is_null(self)
+    }
+}
+
+impl From<myc::value::Value> for OwnedValue {
+    fn from(v: myc::value::Value) -> Self {
+        use myc::value::Value as MycValue;
+        match v {
+            MycValue::NULL => OwnedValue::NULL,
+            MycValue::Bytes(b) => OwnedValue::Bytes(b),
+            MycValue::Int(i) => OwnedValue::Int(i),
+            MycValue::UInt(u) => OwnedValue::UInt(u),
+            MycValue::Float(f) => OwnedValue::Double(f64::from(f)),
+            MycValue::Double(f) => OwnedValue::Double(f),
+            MycValue::Date(year, month, day, hour, minute, second, micros) => {
+                let dt = MysqlDateTime {
+                    year,
+                    month,
+                    day,
+                    hour,
+                    minute,
+                    second,
+                    micros,
+                };
+                // written by hand, since the bytes are kept even if the value is out of range
+                let mut b = Vec::new();
+                if !dt.is_zero() {
+                    b.write_u16::<LittleEndian>(year).unwrap();
+                    b.extend_from_slice(&[month, day]);
+                }
+                if !dt.is_midnight() {
+                    b.extend_from_slice(&[hour, minute, second]);
+                }
+                if micros != 0 {
+                    b.write_u32::<LittleEndian>(micros).unwrap();
+                }
+                if dt.is_midnight() {
+                    OwnedValue::Date(b)
+                } else {
+                    OwnedValue::Datetime(b)
+                }
+            }
+            MycValue::Time(negative, days, hours, minutes, seconds, micros) => {
+                let t = MysqlTime {
+                    negative,
+                    days,
+                    hours,
+                    minutes,
+                    seconds,
+                    micros,
+                };
+                let mut b = Vec::new();
+                if t != MysqlTime::default() {
+                    b.push(u8::from(negative));
+                    b.write_u32::<LittleEndian>(days).unwrap();
+                    b.extend_from_slice(&[hours, minutes, seconds]);
+                }
+                if micros != 0 {
+                    b.write_u32::<LittleEndian>(micros).unwrap();
+                }
+                OwnedValue::Time(b)
+            }
+        }
+    }
+}
+
+/// Fails for temporal values whose binary encoding is malformed.
+impl TryFrom<OwnedValue> for myc::value::Value {
+    type Error = io::Error;
+
+    fn try_from(v: OwnedValue) -> io::Result<Self> {
+        use myc::value::Value as MycValue;
+        Ok(match v {
+            OwnedValue::NULL => MycValue::NULL,
+            OwnedValue::Bytes(b) => MycValue::Bytes(b),
+            OwnedValue::Int(i) => MycValue::Int(i),
+            OwnedValue::UInt(u) => MycValue::UInt(u),
+            OwnedValue::Double(f) => MycValue::Double(f),
+            OwnedValue::Date(_) | OwnedValue::Datetime(_) => {
+                let dt = v.as_value().try_get::<MysqlDateTime>()?;
+                MycValue::Date(
+                    dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second, dt.micros,
+                )
+            }
+            OwnedValue::Time(_) => {
+                let t = v.as_value().try_get::<MysqlTime>()?;
+                MycValue::Time(t.negative, t.days, t.hours, t.minutes, t.seconds, t.micros)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::value::{read_bin_value, Value as MycValue};
+    use crate::MysqlDate;
+    use std::collections::HashSet;
+
+    #[test]
+    fn it_owns_values() {
+        let bytes = b"hello".to_vec();
+        let v = OwnedValue::from(Value(ValueInner::Bytes(&bytes)));
+        drop(bytes);
+        assert_eq!(v, OwnedValue::Bytes(b"hello".to_vec()));
+        assert_eq!(v.as_value().try_get::<&str>().unwrap(), "hello");
+
+        let date = [0xe4, 0x07, 2, 29];
+        let v = OwnedValue::from(Value(ValueInner::Date(&date)));
+        assert_eq!(
+            v.as_value().try_get::<MysqlDate>().unwrap().to_string(),
+            "2020-02-29"
+        );
+        assert!(OwnedValue::from(Value::null()).is_null());
+    }
+
+    #[test]
+    fn it_hashes_values() {
+        let mut set = HashSet::new();
+        assert!(set.insert(OwnedValue::Double(f64::NAN)));
+        assert!(!set.insert(OwnedValue::Double(f64::NAN)));
+        assert!(set.insert(OwnedValue::Int(1)));
+        assert!(set.insert(OwnedValue::UInt(1)));
+        assert!(set.insert(OwnedValue::Bytes(vec![1])));
+        assert!(set.insert(OwnedValue::Date(vec![1])));
+        assert_eq!(set.len(), 5);
+    }
+
+    #[test]
+    fn it_converts_to_and_from_mysql_common() {
+        for v in vec![
+            MycValue::NULL,
+            MycValue::Bytes(b"abc".to_vec()),
+            MycValue::Int(-1),
+            MycValue::UInt(u64::MAX),
+            MycValue::Double(0.5),
+            MycValue::Date(0, 0, 0, 0, 0, 0, 0),
+            MycValue::Date(2020, 2, 29, 0, 0, 0, 0),
+            MycValue::Date(2020, 2, 29, 12, 30, 0, 0),
+            MycValue::Date(2020, 2, 29, 0, 0, 0, 1),
+            MycValue::Time(false, 0, 0, 0, 0, 0),
+            MycValue::Time(true, 1, 2, 3, 4, 0),
+            MycValue::Time(false, 0, 0, 0, 0, 5),
+        ] {
+            let owned = OwnedValue::from(v.clone());
+            assert_eq!(MycValue::try_from(owned).unwrap(), v);
+        }
+        assert_eq!(
+            OwnedValue::from(MycValue::Float(0.5)),
+            OwnedValue::Double(0.5)
+        );
+        assert!(MycValue::try_from(OwnedValue::Time(vec![1, 2, 3])).is_err());
+    }
+
+    #[test]
+    fn it_encodes_owned_values() {
+        let encoded = |v: &OwnedValue, coltype| {
+            let c = Column::new("", "c", coltype);
+            let mut data = Vec::new();
+            v.to_mysql_bin(&mut data, &c)?;
+            read_bin_value(&mut &data[..], coltype, false)
+        };
+
+        let v = OwnedValue::Int(7);
+        assert_eq!(
+            encoded(&v, ColumnType::MYSQL_TYPE_SHORT).unwrap(),
+            MycValue::Int(7)
+        );
+
+        let v = OwnedValue::from(MycValue::Date(2020, 2, 29, 0, 0, 0, 0));
+        for &coltype in &[ColumnType::MYSQL_TYPE_DATE, ColumnType::MYSQL_TYPE_DATETIME] {
+            assert_eq!(
+                encoded(&v, coltype).unwrap(),
+                MycValue::Date(2020, 2, 29, 0, 0, 0, 0)
+            );
+        }
+        let v = OwnedValue::from(MycValue::Date(2020, 2, 29, 1, 0, 0, 0));
+        assert!(encoded(&v, ColumnType::MYSQL_TYPE_DATE).is_err());
+
+        let v = OwnedValue::from(MycValue::Time(true, 0, 1, 2, 3, 0));
+        assert_eq!(
+            encoded(&v, ColumnType::MYSQL_TYPE_TIME).unwrap(),
+            MycValue::Time(true, 0, 1, 2, 3, 0)
+        );
+        let mut data = Vec::new();
+        v.to_mysql_text(&mut data).unwrap();
+        assert_eq!(&data[1..], b"-01:02:03");
+
+        let mut data = Vec::new();
+        OwnedValue::from(MycValue::Date(2020, 2, 29, 0, 0, 0, 0))
+            .to_mysql_text(&mut data)
+            .unwrap();
+        assert_eq!(&data[1..], b"2020-02-29");
+        assert!(OwnedValue::NULL.is_null());
+    }
+}
@@ -0,0 +1,776 @@
+//! Date and time values that cover the full range of their MySQL counterparts.
+
+use super::decode::{bad_conversion, text, Value, ValueInner};
+use super::encode::bad;
+use crate::myc::constants::ColumnType;
+use crate::myc::io::WriteMysqlExt;
+use crate::{Column, FromMysqlValue, ToMysqlValue};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Write};
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_HOUR: i64 = 3600 * MICROS_PER_SECOND;
+/// The length of [`MysqlTime::MAX`] in microseconds.
+const MAX_TIME_MICROS: i64 = 838 * MICROS_PER_HOUR + (59 * 60 + 59) * MICROS_PER_SECOND;
+
+/// A MySQL `TIME` value, which is a possibly negative interval rather than a time of day.
+///
+/// `TIME` values range from `-838:59:59` to `838:59:59`, and may have a fractional part of up to
+/// six digits. A `MysqlTime` holds the value the way it is encoded in the binary protocol, so
+/// `hours` is below 24, and whole days are counted in `days`. Values that are out of range are
+/// rejected when they are sent to the client.
+///
+/// ```
+/// # use msql_srv::MysqlTime;
+/// let t = MysqlTime::from_micros(-(50 * 3600 + 1) * 1_000_000 - 500_000).unwrap();
+/// assert_eq!(t.days, 2);
+/// assert_eq!(t.to_string(), "-50:00:01.500000");
+/// assert!(MysqlTime::from_micros(839 * 3600 * 1_000_000).is_none());
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MysqlTime {
+    /// Whether the interval is negative.
+    pub negative: bool,
+    /// The number of whole days.
+    pub days: u32,
+    /// The number of hours beyond the whole days, from 0 to 23.
+    pub hours: u8,
+    /// The number of minutes, from 0 to 59.
+    pub minutes: u8,
+    /// The number of seconds, from 0 to 59.
+    pub seconds: u8,
+    /// The number of microseconds, from 0 to 999 999.
+    pub micros: u32,
+}
+
+impl MysqlTime {
+    /// The largest `TIME` value, `838:59:59`.
+    pub const MAX: MysqlTime = MysqlTime {
+        negative: false,
+        days: 34,
+        hours: 22,
+        minutes: 59,
+        seconds: 59,
+        micros: 0,
+    };
+
+    /// The smallest `TIME` value, `-838:59:59`.
+    pub const MIN: MysqlTime = MysqlTime {
+        negative: true,
+        ..MysqlTime::MAX
+    };
+
+    /// The `TIME` value that is `micros` microseconds long, if it is within the range of `TIME`.
+    pub fn from_micros(micros: i64) -> Option<Self> {
+        let abs = micros.unsigned_abs();
+        if abs > MAX_TIME_MICROS as u64 {
+            return None;
+        }
+        let secs = abs / 1_000_000;
+        Some(MysqlTime {
+            negative: micros < 0,
+            days: (secs / 86_400) as u32,
+            hours: ((secs % 86_400) / 3600) as u8,
+            minutes: ((secs % 3600) / 60) as u8,
+            seconds: (secs % 60) as u8,
+            micros: (abs % 1_000_000) as u32,
+        })
+    }
+
+    /// The length of this interval in microseconds, or `None` if that does not fit in an `i64`.
+    pub fn as_micros(&self) -> Option<i64> {
+        let hours = i64::from(self.days) * 24 + i64::from(self.hours);
+        let rest = (i64::from(self.minutes) * 60 + i64::from(self.seconds)) * MICROS_PER_SECOND
+            + i64::from(self.micros);
+        let micros = hours.checked_mul(MICROS_PER_HOUR)?.checked_add(rest)?;
+        Some(if self.negative { -micros } else { micros })
+    }
+
+    /// Whether every field is within its range, and the whole value is within the range of
+    /// `TIME`.
+    pub fn is_valid(&self) -> bool {
+        self.hours < 24
+            && self.minutes < 60
+            && self.seconds < 60
+            && self.micros < 1_000_000
+            && self.days <= MysqlTime::MAX.days
+            && self
+                .as_micros()
+                .map_or(false, |micros| micros.abs() <= MAX_TIME_MICROS)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.days == 0
+            && self.hours == 0
+            && self.minutes == 0
+            && self.seconds == 0
+            && self.micros == 0
+    }
+
+    /// Parse a `TIME` in the form `[-]H:MM:SS[.ffffff]`, as MySQL sends it as text.
+    pub(super) fn parse(s: &str) -> Option<Self> {
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+        let (hms, frac) = match s.find('.') {
+            Some(i) => (&s[..i], &s[i + 1..]),
+            None => (s, ""),
+        };
+        let mut parts = hms.splitn(3, ':');
+        let h: i64 = parts.next()?.parse().ok()?;
+        let m: i64 = parts.next()?.parse().ok()?;
+        let sec: i64 = parts.next()?.parse().ok()?;
+        if !(0..=838).contains(&h)
+            || !(0..60).contains(&m)
+            || !(0..60).contains(&sec)
+            || frac.len() > 6
+        {
+            return None;
+        }
+        let micros = if frac.is_empty() {
+            0
+        } else {
+            frac.parse::<i64>().ok()? * 10i64.pow(6 - frac.len() as u32)
+        };
+        let micros = (h * 3600 + m * 60 + sec) * MICROS_PER_SECOND + micros;
+        MysqlTime::from_micros(if negative { -micros } else { micros })
+    }
+}
+
+/// Formats the value as MySQL does, as `[-]HH:MM:SS[.ffffff]`.
+impl fmt::Display for MysqlTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negative && !self.is_zero() {
+            f.write_str("-")?;
+        }
+        let hours = u64::from(self.days) * 24 + u64::from(self.hours);
+        write!(f, "{:02}:{:02}:{:02}", hours, self.minutes, self.seconds)?;
+        if self.micros != 0 {
+            write!(f, ".{:06}", self.micros)?;
+        }
+        Ok(())
+    }
+}
+
+impl ToMysqlValue for MysqlTime {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        if !self.is_valid() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:?} is out of range for TIME", self),
+            ));
+        }
+        w.write_lenenc_str(self.to_string().as_bytes()).map(|_| ())
+    }
+
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        if c.coltype != ColumnType::MYSQL_TYPE_TIME || !self.is_valid() {
+            return Err(bad(self, c));
+        }
+        if self.is_zero() {
+            return w.write_u8(0);
+        }
+        w.write_u8(if self.micros != 0 { 12 } else { 8 })?;
+        w.write_u8(u8::from(self.negative))?;
+        w.write_u32::<LittleEndian>(self.days)?;
+        w.write_u8(self.hours)?;
+        w.write_u8(self.minutes)?;
+        w.write_u8(self.seconds)?;
+        if self.micros != 0 {
+            w.write_u32::<LittleEndian>(self.micros)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for MysqlTime {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        let t = match v.into_inner() {
+            ValueInner::Time([]) => MysqlTime::default(),
+            ValueInner::Time(mut b) if matches!(b.len(), 8 | 12) => MysqlTime {
+                negative: b.read_u8()? != 0,
+                days: b.read_u32::<LittleEndian>()?,
+                hours: b.read_u8()?,
+                minutes: b.read_u8()?,
+                seconds: b.read_u8()?,
+                micros: if b.is_empty() {
+                    0
+                } else {
+                    b.read_u32::<LittleEndian>()?
+                },
+            },
+            ValueInner::Bytes(_) => {
+                MysqlTime::parse(text::<Self>(v)?).ok_or_else(|| bad_conversion::<Self>(v))?
+            }
+            _ => return Err(bad_conversion::<Self>(v)),
+        };
+        if t.is_valid() {
+            Ok(t)
+        } else {
+            Err(bad_conversion::<Self>(v))
+        }
+    }
+}
+
+/// A MySQL `DATE` value, which unlike `chrono::NaiveDate` may be zero or partially zero.
+///
+/// Under a permissive `sql_mode`, MySQL stores dates such as `0000-00-00` and `2020-00-00`, and
+/// with `ALLOW_INVALID_DATES` also days that do not exist, such as `2020-02-31`. A `MysqlDate`
+/// holds any of these as they are, and only converts to a calendar date when it is a real one.
+/// Converting from a `chrono` date with a year outside of `0..=9999` gives a value that is not
+/// [valid](MysqlDate::is_valid), and so is rejected when it is sent.
+///
+/// ```
+/// # use msql_srv::MysqlDate;
+/// # use chrono::NaiveDate;
+/// # use std::convert::TryFrom;
+/// assert_eq!(MysqlDate::ZERO.to_string(), "0000-00-00");
+/// assert!(NaiveDate::try_from(MysqlDate::ZERO).is_err());
+///
+/// let leap = NaiveDate::from_ymd_opt(2020, 2, 29).unwrap();
+/// assert_eq!(NaiveDate::try_from(MysqlDate::from(leap)).unwrap(), leap);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MysqlDate {
+    /// The year, from 0 to 9999.
+    pub year: u16,
+    /// The month, from 1 to 12, or 0.
+    pub month: u8,
+    /// The day of the month, from 1 to 31, or 0.
+    pub day: u8,
+}
+
+impl MysqlDate {
+    /// The zero date, `0000-00-00`.
+    pub const ZERO: MysqlDate = MysqlDate {
+        year: 0,
+        month: 0,
+        day: 0,
+    };
+
+    /// Whether this is the zero date.
+    pub fn is_zero(&self) -> bool {
+        *self == MysqlDate::ZERO
+    }
+
+    /// Whether every field is within the range MySQL accepts, even if the date does not exist.
+    pub fn is_valid(&self) -> bool {
+        self.year <= 9999 && self.month <= 12 && self.day <= 31
+    }
+}
+
+impl fmt::Display for MysqlDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl From<NaiveDate> for MysqlDate {
+    fn from(d: NaiveDate) -> Self {
+        MysqlDate {
+            year: u16::try_from(d.year()).unwrap_or(u16::MAX),
+            month: d.month() as u8,
+            day: d.day() as u8,
+        }
+    }
+}
+
+impl TryFrom<MysqlDate> for NaiveDate {
+    type Error = io::Error;
+    fn try_from(d: MysqlDate) -> io::Result<Self> {
+        NaiveDate::from_ymd_opt(i32::from(d.year), u32::from(d.month), u32::from(d.day))
+            .ok_or_else(|| not_a_date(d))
+    }
+}
+
+/// A MySQL `DATETIME` or `TIMESTAMP` value, which unlike `chrono::NaiveDateTime` may have a zero
+/// or partially zero date.
+///
+/// See [`MysqlDate`] for the dates that MySQL accepts.
+///
+/// ```
+/// # use msql_srv::MysqlDateTime;
+/// # use chrono::NaiveDateTime;
+/// # use std::convert::TryFrom;
+/// assert_eq!(MysqlDateTime::ZERO.to_string(), "0000-00-00 00:00:00");
+/// assert!(NaiveDateTime::try_from(MysqlDateTime::ZERO).is_err());
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MysqlDateTime {
+    /// The year, from 0 to 9999.
+    pub year: u16,
+    /// The month, from 1 to 12, or 0.
+    pub month: u8,
+    /// The day of the month, from 1 to 31, or 0.
+    pub day: u8,
+    /// The hour, from 0 to 23.
+    pub hour: u8,
+    /// The minute, from 0 to 59.
+    pub minute: u8,
+    /// The second, from 0 to 59.
+    pub second: u8,
+    /// The number of microseconds, from 0 to 999 999.
+    pub micros: u32,
+}
+
+impl MysqlDateTime {
+    /// The zero date and time, `0000-00-00 00:00:00`.
+    pub const ZERO: MysqlDateTime = MysqlDateTime {
+        year: 0,
+        month: 0,
+        day: 0,
+        hour: 0,
+        minute: 0,
+        second: 0,
+        micros: 0,
+    };
+
+    /// The date part of this value.
+    pub fn date(&self) -> MysqlDate {
+        MysqlDate {
+            year: self.year,
+            month: self.month,
+            day: self.day,
+        }
+    }
+
+    /// Whether this is the zero date and time.
+    pub fn is_zero(&self) -> bool {
+        *self == MysqlDateTime::ZERO
+    }
+
+    /// Whether every field is within the range MySQL accepts, even if the date does not exist.
+    pub fn is_valid(&self) -> bool {
+        self.date().is_valid()
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+            && self.micros < 1_000_000
+    }
+
+    pub(super) fn is_midnight(&self) -> bool {
+        self.hour == 0 && self.minute == 0 && self.second == 0 && self.micros == 0
+    }
+
+    /// Parse a date in the form `YYYY-MM-DD[ HH:MM:SS[.ffffff]]`, as MySQL sends it as text.
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        // like MySQL, also accept the ISO 8601 `T` between the date and the time
+        let (date, time) = match s.find(|c| c == ' ' || c == 'T') {
+            Some(i) => (&s[..i], &s[i + 1..]),
+            None => (s, "00:00:00"),
+        };
+        let mut parts = date.splitn(3, '-');
+        let year = parts.next()?.parse().ok()?;
+        let month = parts.next()?.parse().ok()?;
+        let day = parts.next()?.parse().ok()?;
+
+        let (hms, frac) = match time.find('.') {
+            Some(i) => (&time[..i], &time[i + 1..]),
+            None => (time, ""),
+        };
+        let mut parts = hms.splitn(3, ':');
+        let hour = parts.next()?.parse().ok()?;
+        let minute = parts.next()?.parse().ok()?;
+        let second = parts.next()?.parse().ok()?;
+        if frac.len() > 6 {
+            return None;
+        }
+        let micros = if frac.is_empty() {
+            0
+        } else {
+            frac.parse::<u32>().ok()? * 10u32.pow(6 - frac.len() as u32)
+        };
+        Some(MysqlDateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            micros,
+        })
+    }
+}
+
+/// Formats the value as MySQL does, as `YYYY-MM-DD HH:MM:SS[.ffffff]`.
+impl fmt::Display for MysqlDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:02}:{:02}:{:02}",
+            self.date(),
+            self.hour,
+            self.minute,
+            self.second
+        )?;
+        if self.micros != 0 {
+            write!(f, ".{:06}", self.micros)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<MysqlDate> for MysqlDateTime {
+    fn from(d: MysqlDate) -> Self {
+        MysqlDateTime {
+            year: d.year,
+            month: d.month,
+            day: d.day,
+            ..MysqlDateTime::ZERO
+        }
+    }
+}
+
+impl From<NaiveDateTime> for MysqlDateTime {
+    fn from(dt: NaiveDateTime) -> Self {
+        MysqlDateTime {
+            hour: dt.hour() as u8,
+            minute: dt.minute() as u8,
+            second: dt.second() as u8,
+            micros: dt.nanosecond() / 1_000,
+            ..MysqlDate::from(dt.date()).into()
+        }
+    }
+}
+
+impl TryFrom<MysqlDateTime> for NaiveDateTime {
+    type Error = io::Error;
+    fn try_from(dt: MysqlDateTime) -> io::Result<Self> {
+        NaiveDate::try_from(dt.date())?
+            .and_hms_micro_opt(
+                u32::from(dt.hour),
+                u32::from(dt.minute),
+                u32::from(dt.second),
+                dt.micros,
+            )
+            .ok_or_else(|| not_a_date(dt))
+    }
+}
+
+pub(super) fn not_a_date<T: fmt::Display>(v: T) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{} is not a calendar date", v),
+    )
+}
+
+fn out_of_range<T: fmt::Debug>(v: T, coltype: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{:?} is out of range for {}", v, coltype),
+    )
+}
+
+impl ToMysqlValue for MysqlDate {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        if !self.is_valid() {
+            return Err(out_of_range(self, "DATE"));
+        }
+        w.write_lenenc_str(self.to_string().as_bytes()).map(|_| ())
+    }
+
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        if c.coltype != ColumnType::MYSQL_TYPE_DATE || !self.is_valid() {
+            return Err(bad(self, c));
+        }
+        if self.is_zero() {
+            return w.write_u8(0);
+        }
+        w.write_u8(4)?;
+        w.write_u16::<LittleEndian>(self.year)?;
+        w.write_u8(self.month)?;
+        w.write_u8(self.day)
+    }
+}
+
+impl ToMysqlValue for MysqlDateTime {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        if !self.is_valid() {
+            return Err(out_of_range(self, "DATETIME"));
+        }
+        w.write_lenenc_str(self.to_string().as_bytes()).map(|_| ())
+    }
+
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        match c.coltype {
+            ColumnType::MYSQL_TYPE_DATETIME | ColumnType::MYSQL_TYPE_TIMESTAMP
+                if self.is_valid() => {}
+            _ => return Err(bad(self, c)),
+        }
+        if self.is_zero() {
+            return w.write_u8(0);
+        }
+        w.write_u8(if self.micros != 0 { 11 } else { 7 })?;
+        w.write_u16::<LittleEndian>(self.year)?;
+        w.write_u8(self.month)?;
+        w.write_u8(self.day)?;
+        w.write_u8(self.hour)?;
+        w.write_u8(self.minute)?;
+        w.write_u8(self.second)?;
+        if self.micros != 0 {
+            w.write_u32::<LittleEndian>(self.micros)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for MysqlDateTime {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        let dt = match v.into_inner() {
+            ValueInner::Date(mut b) | ValueInner::Datetime(mut b)
+                if matches!(b.len(), 0 | 4 | 7 | 11) =>
+            {
+                let mut dt = MysqlDateTime::ZERO;
+                if !b.is_empty() {
+                    dt.year = b.read_u16::<LittleEndian>()?;
+                    dt.month = b.read_u8()?;
+                    dt.day = b.read_u8()?;
+                }
+                if !b.is_empty() {
+                    dt.hour = b.read_u8()?;
+                    dt.minute = b.read_u8()?;
+                    dt.second = b.read_u8()?;
+                }
+                if !b.is_empty() {
+                    dt.micros = b.read_u32::<LittleEndian>()?;
+                }
+                dt
+            }
+            ValueInner::Bytes(_) => {
+                MysqlDateTime::parse(text::<Self>(v)?).ok_or_else(|| bad_conversion::<Self>(v))?
+            }
+            _ => return Err(bad_conversion::<Self>(v)),
+        };
+        if dt.is_valid() {
+            Ok(dt)
+        } else {
+            Err(bad_conversion::<Self>(v))
+        }
+    }
+}
+
+/// Values with a time other than midnight are rejected.
+impl<'a> FromMysqlValue<'a> for MysqlDate {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        let dt = MysqlDateTime::from_mysql_value(v)?;
+        if dt.is_midnight() {
+            Ok(dt.date())
+        } else {
+            Err(bad_conversion::<Self>(v))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::value::{read_bin_value, Value as MycValue};
+
+    fn time(
+        negative: bool,
+        days: u32,
+        hours: u8,
+        minutes: u8,
+        seconds: u8,
+        micros: u32,
+    ) -> MysqlTime {
+        MysqlTime {
+            negative,
+            days,
+            hours,
+            minutes,
+            seconds,
+            micros,
+        }
+    }
+
+    #[test]
+    fn it_encodes_times() {
+        let c = Column::new("", "t", ColumnType::MYSQL_TYPE_TIME);
+        for &t in &[
+            time(true, 2, 2, 0, 1, 500_000),
+            time(false, 0, 1, 2, 3, 0),
+            MysqlTime::MIN,
+            MysqlTime::MAX,
+        ] {
+            let mut data = Vec::new();
+            t.to_mysql_bin(&mut data, &c).unwrap();
+            assert_eq!(
+                read_bin_value(&mut &data[..], ColumnType::MYSQL_TYPE_TIME, false).unwrap(),
+                MycValue::Time(t.negative, t.days, t.hours, t.minutes, t.seconds, t.micros)
+            );
+            let v = Value(ValueInner::Time(&data[1..]));
+            assert_eq!(v.try_get::<MysqlTime>().unwrap(), t);
+        }
+
+        let mut data = Vec::new();
+        MysqlTime::default().to_mysql_bin(&mut data, &c).unwrap();
+        assert_eq!(data, [0]);
+
+        let too_long = time(false, 34, 23, 0, 0, 0);
+        assert!(too_long.to_mysql_bin(&mut Vec::new(), &c).is_err());
+        assert!(too_long.to_mysql_text(&mut Vec::new()).is_err());
+        assert!(time(false, 0, 24, 0, 0, 0)
+            .to_mysql_bin(&mut Vec::new(), &c)
+            .is_err());
+
+        assert_eq!(MysqlTime::MIN.to_string(), "-838:59:59");
+        assert_eq!(time(false, 0, 1, 2, 3, 40).to_string(), "01:02:03.000040");
+    }
+
+    #[test]
+    fn it_decodes_times() {
+        let t = |s: &'static [u8]| Value(ValueInner::Bytes(s)).try_get::<MysqlTime>();
+        assert_eq!(t(b"-838:59:59").unwrap(), MysqlTime::MIN);
+        assert_eq!(t(b"-50:00:01.5").unwrap(), time(true, 2, 2, 0, 1, 500_000));
+        assert_eq!(t(b"00:00:00").unwrap(), MysqlTime::default());
+        assert!(t(b"839:00:00").is_err());
+        assert!(t(b"3000000000000:00:00").is_err());
+        assert!(t(b"--1:00:00").is_err());
+        assert!(t(b"1:60:00").is_err());
+        assert!(t(b"1:00:00.1234567").is_err());
+
+        let out_of_range = [0, 35, 0, 0, 0, 0, 0, 0];
+        assert!(Value(ValueInner::Time(&out_of_range))
+            .try_get::<MysqlTime>()
+            .is_err());
+        assert!(Value(ValueInner::Int(1)).try_get::<MysqlTime>().is_err());
+    }
+
+    #[test]
+    fn it_converts_micros() {
+        for &t in &[
+            MysqlTime::MIN,
+            MysqlTime::MAX,
+            time(true, 0, 0, 0, 0, 1),
+            time(false, 1, 0, 0, 0, 0),
+        ] {
+            assert_eq!(MysqlTime::from_micros(t.as_micros().unwrap()), Some(t));
+        }
+        assert_eq!(MysqlTime::from_micros(0), Some(MysqlTime::default()));
+        assert!(MysqlTime::from_micros(MysqlTime::MIN.as_micros().unwrap() - 1).is_none());
+
+        let huge = time(true, u32::MAX, 23, 59, 59, 999_999);
+        assert_eq!(huge.as_micros(), None);
+        assert!(!huge.is_valid());
+        assert_eq!(huge.to_string(), "-103079215103:59:59.999999");
+    }
+
+    fn datetime(
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+        micros: u32,
+    ) -> MysqlDateTime {
+        MysqlDateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            micros,
+        }
+    }
+
+    #[test]
+    fn it_encodes_zero_dates() {
+        let c = Column::new("", "d", ColumnType::MYSQL_TYPE_DATE);
+        for &d in &[
+            MysqlDate::ZERO,
+            datetime(2020, 0, 0, 0, 0, 0, 0).date(),
+            datetime(2020, 2, 31, 0, 0, 0, 0).date(),
+        ] {
+            let mut data = Vec::new();
+            d.to_mysql_bin(&mut data, &c).unwrap();
+            assert_eq!(
+                read_bin_value(&mut &data[..], ColumnType::MYSQL_TYPE_DATE, false).unwrap(),
+                MycValue::Date(d.year, d.month, d.day, 0, 0, 0, 0)
+            );
+            let v = Value(ValueInner::Date(&data[1..]));
+            assert_eq!(v.try_get::<MysqlDate>().unwrap(), d);
+        }
+
+        let c = Column::new("", "dt", ColumnType::MYSQL_TYPE_DATETIME);
+        for &dt in &[
+            MysqlDateTime::ZERO,
+            datetime(0, 0, 0, 12, 0, 0, 0),
+            datetime(2020, 2, 0, 1, 2, 3, 40),
+        ] {
+            let mut data = Vec::new();
+            dt.to_mysql_bin(&mut data, &c).unwrap();
+            assert_eq!(
+                read_bin_value(&mut &data[..], ColumnType::MYSQL_TYPE_DATETIME, false).unwrap(),
+                MycValue::Date(dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second, dt.micros)
+            );
+            let v = Value(ValueInner::Datetime(&data[1..]));
+            assert_eq!(v.try_get::<MysqlDateTime>().unwrap(), dt);
+        }
+
+        let mut data = Vec::new();
+        MysqlDateTime::ZERO.to_mysql_bin(&mut data, &c).unwrap();
+        assert_eq!(data, [0]);
+        MysqlDate::ZERO.to_mysql_text(&mut data).unwrap();
+        assert_eq!(&data[2..], b"0000-00-00");
+        assert_eq!(
+            datetime(2020, 2, 0, 1, 2, 3, 40).to_string(),
+            "2020-02-00 01:02:03.000040"
+        );
+
+        assert!(MysqlDate::ZERO.to_mysql_bin(&mut Vec::new(), &c).is_err());
+        let bad_month = datetime(2020, 13, 1, 0, 0, 0, 0);
+        assert!(bad_month.to_mysql_bin(&mut Vec::new(), &c).is_err());
+        assert!(bad_month.date().to_mysql_text(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn it_decodes_zero_dates() {
+        let dt = |s: &'static [u8]| Value(ValueInner::Bytes(s)).try_get::<MysqlDateTime>();
+        assert_eq!(dt(b"0000-00-00 00:00:00").unwrap(), MysqlDateTime::ZERO);
+        assert_eq!(
+            dt(b"2020-02-31 23:59:58.5").unwrap(),
+            datetime(2020, 2, 31, 23, 59, 58, 500_000)
+        );
+        assert_eq!(dt(b"2020-00-00").unwrap(), datetime(2020, 0, 0, 0, 0, 0, 0));
+        assert!(dt(b"2020-02-32").is_err());
+        assert!(dt(b"2020-02-01 24:00:00").is_err());
+        assert!(dt(b"yesterday").is_err());
+
+        assert_eq!(
+            Value(ValueInner::Date(&[])).try_get::<MysqlDate>().unwrap(),
+            MysqlDate::ZERO
+        );
+        assert!(Value(ValueInner::Datetime(&[0, 0, 0, 0, 1, 0, 0]))
+            .try_get::<MysqlDate>()
+            .is_err());
+        assert!(Value(ValueInner::Date(&[0, 0, 0]))
+            .try_get::<MysqlDate>()
+            .is_err());
+    }
+
+    #[test]
+    fn it_converts_zero_dates() {
+        let dt = NaiveDate::from_ymd_opt(2020, 2, 29)
+            .unwrap()
+            .and_hms_micro_opt(23, 59, 58, 500_000)
+            .unwrap();
+        let mdt = MysqlDateTime::from(dt);
+        assert_eq!(mdt, datetime(2020, 2, 29, 23, 59, 58, 500_000));
+        assert_eq!(NaiveDateTime::try_from(mdt).unwrap(), dt);
+        assert_eq!(NaiveDate::try_from(mdt.date()).unwrap(), dt.date());
+
+        assert!(NaiveDate::try_from(MysqlDate::ZERO).is_err());
+        assert!(NaiveDate::try_from(datetime(2021, 2, 29, 0, 0, 0, 0).date()).is_err());
+        assert!(NaiveDateTime::try_from(MysqlDateTime::ZERO).is_err());
+
+        let ancient = NaiveDate::from_ymd_opt(-1, 1, 1).unwrap();
+        assert!(!MysqlDate::from(ancient).is_valid());
+    }
+}
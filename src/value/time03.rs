@@ -0,0 +1,324 @@
+//! Support for the date and time types of `time` 0.3.
+//!
+//! Dates and times are converted to and from their `chrono` counterparts, which this crate
+//! encodes and decodes natively.
+
+use super::decode::{bad_conversion, Value};
+use super::encode::Hint;
+use super::temporal::not_a_date;
+use crate::{Column, FromMysqlValue, MysqlDate, MysqlDateTime, MysqlTime, ToMysqlValue};
+use chrono::{Datelike, FixedOffset, NaiveDate, NaiveDateTime, Timelike};
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use time03::{Date, Duration, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+/// `d` as a `chrono::NaiveDate`, or an error if its year is beyond chrono's range (as it may be
+/// with time's `large-dates` feature). `ty` names the type being encoded, for the error.
+fn to_chrono_date(d: Date, ty: &str) -> io::Result<NaiveDate> {
+    NaiveDate::from_ymd_opt(d.year(), u32::from(u8::from(d.month())), u32::from(d.day()))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is out of range for {}", d, ty),
+            )
+        })
+}
+
+fn to_chrono(dt: PrimitiveDateTime, ty: &str) -> io::Result<NaiveDateTime> {
+    // any time::Time is also a valid chrono time of day
+    Ok(to_chrono_date(dt.date(), ty)?
+        .and_hms_micro_opt(
+            u32::from(dt.hour()),
+            u32::from(dt.minute()),
+            u32::from(dt.second()),
+            dt.microsecond(),
+        )
+        .unwrap())
+}
+
+fn from_chrono_date(d: NaiveDate) -> Option<Date> {
+    let month = Month::try_from(d.month() as u8).ok()?;
+    Date::from_calendar_date(d.year(), month, d.day() as u8).ok()
+}
+
+fn from_chrono(dt: NaiveDateTime) -> Option<PrimitiveDateTime> {
+    let time = Time::from_hms_micro(
+        dt.hour() as u8,
+        dt.minute() as u8,
+        dt.second() as u8,
+        dt.nanosecond() / 1_000,
+    )
+    .ok()?;
+    Some(PrimitiveDateTime::new(from_chrono_date(dt.date())?, time))
+}
+
+impl From<Date> for MysqlDate {
+    fn from(d: Date) -> Self {
+        MysqlDate {
+            year: u16::try_from(d.year()).unwrap_or(u16::MAX),
+            month: u8::from(d.month()),
+            day: d.day(),
+        }
+    }
+}
+
+impl TryFrom<MysqlDate> for Date {
+    type Error = io::Error;
+    fn try_from(d: MysqlDate) -> io::Result<Self> {
+        from_chrono_date(NaiveDate::try_from(d)?).ok_or_else(|| not_a_date(d))
+    }
+}
+
+impl From<PrimitiveDateTime> for MysqlDateTime {
+    fn from(dt: PrimitiveDateTime) -> Self {
+        MysqlDateTime {
+            hour: dt.hour(),
+            minute: dt.minute(),
+            second: dt.second(),
+            micros: dt.microsecond(),
+            ..MysqlDate::from(dt.date()).into()
+        }
+    }
+}
+
+impl TryFrom<MysqlDateTime> for PrimitiveDateTime {
+    type Error = io::Error;
+    fn try_from(dt: MysqlDateTime) -> io::Result<Self> {
+        from_chrono(NaiveDateTime::try_from(dt)?).ok_or_else(|| not_a_date(dt))
+    }
+}
+
+impl ToMysqlValue for Date {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        to_chrono_date(*self, "DATE")?.to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        to_chrono_date(*self, "DATE")?.to_mysql_bin(w, c)
+    }
+}
+
+impl ToMysqlValue for PrimitiveDateTime {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        to_chrono(*self, "DATETIME")?.to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        to_chrono(*self, "DATETIME")?.to_mysql_bin(w, c)
+    }
+}
+
+fn utc(dt: OffsetDateTime) -> io::Result<NaiveDateTime> {
+    // converted by chrono, since time panics when the result is beyond its range
+    let local = to_chrono(PrimitiveDateTime::new(dt.date(), dt.time()), "TIMESTAMP")?;
+    local
+        .checked_sub_signed(chrono::Duration::seconds(i64::from(
+            dt.offset().whole_seconds(),
+        )))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is out of range for TIMESTAMP", dt),
+            )
+        })
+}
+
+impl ToMysqlValue for OffsetDateTime {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        utc(*self)?.to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        utc(*self)?.to_mysql_bin(w, c)
+    }
+    fn hint(&self) -> Hint<'_> {
+        // values that are out of range fail once they are encoded
+        utc(*self).map_or(Hint::None, Hint::Utc)
+    }
+}
+
+/// The `TIME` value of `d`, or an error if it is out of the range of `TIME`.
+fn time(d: &Duration) -> io::Result<MysqlTime> {
+    i64::try_from(d.whole_microseconds())
+        .ok()
+        .and_then(MysqlTime::from_micros)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is out of range for TIME", d),
+            )
+        })
+}
+
+/// `TIME` values may be negative, and range from `-838:59:59` to `838:59:59`.
+impl ToMysqlValue for Duration {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        time(self)?.to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        time(self)?.to_mysql_bin(w, c)
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for Date {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        from_chrono_date(NaiveDate::from_mysql_value(v)?).ok_or_else(|| bad_conversion::<Self>(v))
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for PrimitiveDateTime {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        from_chrono(NaiveDateTime::from_mysql_value(v)?).ok_or_else(|| bad_conversion::<Self>(v))
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for OffsetDateTime {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        PrimitiveDateTime::from_mysql_value(v).map(PrimitiveDateTime::assume_utc)
+    }
+
+    fn from_mysql_value_in(v: Value<'a>, time_zone: FixedOffset) -> io::Result<Self> {
+        let offset = UtcOffset::from_whole_seconds(time_zone.local_minus_utc())
+            .map_err(|_| bad_conversion::<Self>(v))?;
+        PrimitiveDateTime::from_mysql_value(v).map(|dt| dt.assume_offset(offset))
+    }
+}
+
+impl<'a> FromMysqlValue<'a> for Duration {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        let micros = MysqlTime::from_mysql_value(v)?.as_micros();
+        micros
+            .map(Duration::microseconds)
+            .ok_or_else(|| bad_conversion::<Self>(v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::constants::ColumnType;
+    use crate::myc::value::{read_bin_value, Value as MycValue};
+    use crate::value::bin;
+    use crate::ValueInner;
+
+    fn date(y: i32, m: u8, d: u8) -> Date {
+        Date::from_calendar_date(y, Month::try_from(m).unwrap(), d).unwrap()
+    }
+
+    fn datetime(y: i32, mo: u8, d: u8, h: u8, mi: u8, s: u8, us: u32) -> PrimitiveDateTime {
+        date(y, mo, d).with_hms_micro(h, mi, s, us).unwrap()
+    }
+
+    fn text<T: ToMysqlValue>(v: T) -> Vec<u8> {
+        let mut data = Vec::new();
+        v.to_mysql_text(&mut data).unwrap();
+        data.remove(0);
+        data
+    }
+
+    #[test]
+    fn it_encodes_dates_and_times() {
+        let c = Column::new("", "c", ColumnType::MYSQL_TYPE_DATE);
+        let data = bin(date(2020, 2, 29), &c).unwrap();
+        assert_eq!(
+            read_bin_value(&mut &data[..], ColumnType::MYSQL_TYPE_DATE, false).unwrap(),
+            MycValue::Date(2020, 2, 29, 0, 0, 0, 0)
+        );
+
+        let dt = datetime(2020, 2, 29, 23, 59, 58, 500_000);
+        let c = Column::new("", "c", ColumnType::MYSQL_TYPE_DATETIME);
+        let data = bin(dt, &c).unwrap();
+        assert_eq!(
+            read_bin_value(&mut &data[..], ColumnType::MYSQL_TYPE_DATETIME, false).unwrap(),
+            MycValue::Date(2020, 2, 29, 23, 59, 58, 500_000)
+        );
+        assert_eq!(text(dt), b"2020-02-29 23:59:58.500000");
+
+        let odt =
+            datetime(2020, 3, 1, 1, 59, 58, 0).assume_offset(UtcOffset::from_hms(2, 0, 0).unwrap());
+        assert_eq!(text(odt), b"2020-02-29 23:59:58");
+        let utc = to_chrono(datetime(2020, 2, 29, 23, 59, 58, 0), "DATETIME").unwrap();
+        assert!(matches!(odt.hint(), Hint::Utc(dt) if dt == utc));
+
+        let d = -(Duration::hours(50) + Duration::milliseconds(1500));
+        let c = Column::new("", "c", ColumnType::MYSQL_TYPE_TIME);
+        let data = bin(d, &c).unwrap();
+        assert_eq!(
+            read_bin_value(&mut &data[..], ColumnType::MYSQL_TYPE_TIME, false).unwrap(),
+            MycValue::Time(true, 2, 2, 0, 1, 500_000)
+        );
+        assert_eq!(text(d), b"-50:00:01.500000");
+        assert_eq!(bin(Duration::ZERO, &c).unwrap(), [0]);
+    }
+
+    #[test]
+    fn it_rejects_dates_beyond_chrono() {
+        // the tests enable time's large-dates feature
+        let far = date(300_000, 1, 1);
+        let c = Column::new("", "c", ColumnType::MYSQL_TYPE_DATE);
+        let err = far.to_mysql_text(&mut Vec::new()).unwrap_err();
+        assert!(err.to_string().ends_with("out of range for DATE"));
+        assert!(bin(far, &c).is_err());
+        let err = far.midnight().to_mysql_text(&mut Vec::new()).unwrap_err();
+        assert!(err.to_string().ends_with("out of range for DATETIME"));
+        assert_eq!(MysqlDate::from(far).year, u16::MAX);
+
+        let odt = far.midnight().assume_utc();
+        assert!(matches!(odt.hint(), Hint::None));
+        let err = odt.to_mysql_text(&mut Vec::new()).unwrap_err();
+        assert!(err.to_string().ends_with("out of range for TIMESTAMP"));
+    }
+
+    #[test]
+    fn it_decodes_dates_and_times() {
+        let dt = Value(ValueInner::Datetime(&[0xe4, 0x07, 2, 29, 23, 59, 58]));
+        assert_eq!(
+            dt.try_get::<PrimitiveDateTime>().unwrap(),
+            datetime(2020, 2, 29, 23, 59, 58, 0)
+        );
+        assert_eq!(
+            dt.try_get::<OffsetDateTime>().unwrap(),
+            datetime(2020, 2, 29, 23, 59, 58, 0).assume_utc()
+        );
+        let plus_two = FixedOffset::east_opt(2 * 3600).unwrap();
+        let odt = OffsetDateTime::from_mysql_value_in(dt, plus_two).unwrap();
+        assert_eq!(odt.offset(), UtcOffset::from_hms(2, 0, 0).unwrap());
+        assert_eq!(odt, datetime(2020, 2, 29, 21, 59, 58, 0).assume_utc());
+        let d = Value(ValueInner::Date(&[0xe4, 0x07, 2, 29]));
+        assert_eq!(d.try_get::<Date>().unwrap(), date(2020, 2, 29));
+
+        let t = Value(ValueInner::Time(&[
+            1, 2, 0, 0, 0, 2, 0, 1, 0x20, 0xa1, 7, 0,
+        ]));
+        assert_eq!(
+            t.try_get::<Duration>().unwrap(),
+            -(Duration::hours(50) + Duration::milliseconds(1500))
+        );
+        let t = Value(ValueInner::Bytes(b"-838:59:58.5"));
+        assert_eq!(
+            t.try_get::<Duration>().unwrap(),
+            -(Duration::hours(838) + Duration::minutes(59) + Duration::milliseconds(58_500))
+        );
+        assert!(Value(ValueInner::Bytes(b"-838:59:59.5"))
+            .try_get::<Duration>()
+            .is_err());
+        assert!(Value(ValueInner::Bytes(b"1:60:00"))
+            .try_get::<Duration>()
+            .is_err());
+    }
+
+    #[test]
+    fn it_converts_zero_dates() {
+        let d = MysqlDate::from(date(2020, 2, 29));
+        assert_eq!(Date::try_from(d).unwrap(), date(2020, 2, 29));
+        assert!(Date::try_from(MysqlDate::ZERO).is_err());
+
+        let dt = datetime(2020, 2, 29, 23, 59, 58, 500_000);
+        assert_eq!(
+            PrimitiveDateTime::try_from(MysqlDateTime::from(dt)).unwrap(),
+            dt
+        );
+        let zero_day = MysqlDateTime {
+            day: 0,
+            ..MysqlDateTime::from(dt)
+        };
+        assert!(PrimitiveDateTime::try_from(zero_day).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+//! Support for `uuid::Uuid` values.
+
+use super::decode::{bad_conversion, Value, ValueInner};
+use super::encode::Hint;
+use crate::{CharacterSet, Column, FromMysqlValue, ToMysqlValue};
+use std::io::{self, Write};
+use uuid::Uuid;
+
+/// UUIDs are sent as their 16 bytes to binary columns, such as `BINARY(16)`, and in their
+/// hyphenated form to all other columns, such as `CHAR(36)`.
+impl ToMysqlValue for Uuid {
+    fn to_mysql_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.hyphenated()
+            .encode_lower(&mut Uuid::encode_buffer())
+            .to_mysql_text(w)
+    }
+    fn to_mysql_bin<W: Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+        if c.character_set == CharacterSet::Binary.collation() {
+            self.as_bytes().to_mysql_bin(w, c)
+        } else {
+            self.hyphenated()
+                .encode_lower(&mut Uuid::encode_buffer())
+                .to_mysql_bin(w, c)
+        }
+    }
+    fn hint(&self) -> Hint<'_> {
+        Hint::Binary(self.as_bytes())
+    }
+}
+
+/// UUID parameters may be sent as 16 bytes, or as text in any of the forms `Uuid` can parse.
+impl<'a> FromMysqlValue<'a> for Uuid {
+    fn from_mysql_value(v: Value<'a>) -> io::Result<Self> {
+        match v.into_inner() {
+            ValueInner::Bytes(b) if b.len() == 16 => {
+                Uuid::from_slice(b).map_err(|_| bad_conversion::<Self>(v))
+            }
+            ValueInner::Bytes(b) => std::str::from_utf8(b)
+                .map_err(|_| bad_conversion::<Self>(v))
+                .and_then(|s| Uuid::parse_str(s).map_err(|_| bad_conversion::<Self>(v))),
+            _ => Err(bad_conversion::<Self>(v)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::constants::ColumnType;
+
+    const ID: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+    #[test]
+    fn it_encodes_uuids() {
+        let id = Uuid::parse_str(ID).unwrap();
+
+        let mut data = Vec::new();
+        let c = Column::new("", "id", ColumnType::MYSQL_TYPE_STRING);
+        id.to_mysql_bin(&mut data, &c).unwrap();
+        assert_eq!(&data[1..], ID.as_bytes());
+
+        let mut data = Vec::new();
+        let c = c.with_character_set(CharacterSet::Binary.collation());
+        id.to_mysql_bin(&mut data, &c).unwrap();
+        assert_eq!(data[0], 16);
+        assert_eq!(&data[1..], id.as_bytes());
+
+        let mut data = Vec::new();
+        id.to_mysql_text(&mut data).unwrap();
+        assert_eq!(&data[1..], ID.as_bytes());
+    }
+
+    #[test]
+    fn it_decodes_uuids() {
+        let id = Uuid::parse_str(ID).unwrap();
+        let v = Value(ValueInner::Bytes(ID.as_bytes()));
+        assert_eq!(v.try_get::<Uuid>().unwrap(), id);
+        let v = Value(ValueInner::Bytes(id.as_bytes()));
+        assert_eq!(v.try_get::<Uuid>().unwrap(), id);
+        assert!(Value(ValueInner::Bytes(b"not a uuid"))
+            .try_get::<Uuid>()
+            .is_err());
+        assert!(Value(ValueInner::Int(1)).try_get::<Uuid>().is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use crate::myc::constants::{ColumnFlags, ColumnType};
+use crate::{Column, ErrorKind, QueryResultWriter};
+use std::convert::TryFrom;
+use std::io;
+
+/// The severity of a [`Warning`](struct.Warning.html), as shown in the `Level` column of
+/// `SHOW WARNINGS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningLevel {
+    /// An informational note.
+    Note,
+    /// A warning.
+    Warning,
+    /// An error.
+    Error,
+}
+
+impl WarningLevel {
+    /// The name MySQL uses for this level.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WarningLevel::Note => "Note",
+            WarningLevel::Warning => "Warning",
+            WarningLevel::Error => "Error",
+        }
+    }
+}
+
+/// A condition raised while executing a statement that did not cause the statement to fail.
+///
+/// Warnings are attached to a response using
+/// [`QueryResultWriter::add_warning`](struct.QueryResultWriter.html#method.add_warning) or
+/// [`RowWriter::add_warning`](struct.RowWriter.html#method.add_warning).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// The severity of this warning.
+    pub level: WarningLevel,
+    /// The error code associated with this warning.
+    pub code: ErrorKind,
+    /// A human-readable description of this warning.
+    pub message: String,
+}
+
+impl Warning {
+    /// Create a new warning.
+    pub fn new<S: Into<String>>(level: WarningLevel, code: ErrorKind, message: S) -> Self {
+        Warning {
+            level,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// The warning count to report for `warnings` in OK and EOF packets.
+pub(crate) fn warning_count(warnings: &[Warning]) -> u16 {
+    u16::try_from(warnings.len()).unwrap_or(u16::MAX)
+}
+
+/// Determine whether `query` is `SHOW WARNINGS` or `SHOW COUNT(*) WARNINGS`, and if so, which.
+pub(crate) fn is_show_warnings(query: &[u8]) -> Option<bool> {
+    let query = std::str::from_utf8(query).ok()?;
+    let mut words = query.trim().trim_end_matches(';').split_whitespace();
+    let is = |word: Option<&str>, s: &str| matches!(word, Some(w) if w.eq_ignore_ascii_case(s));
+    match (words.next(), words.next(), words.next(), words.next()) {
+        (show, warnings, None, None) if is(show, "SHOW") && is(warnings, "WARNINGS") => Some(false),
+        (show, count, warnings, None)
+            if is(show, "SHOW") && is(count, "COUNT(*)") && is(warnings, "WARNINGS") =>
+        {
+            Some(true)
+        }
+        _ => None,
+    }
+}
+
+/// Reply to `SHOW WARNINGS` (or `SHOW COUNT(*) WARNINGS` if `count_only` is set) the same way
+/// MySQL does.
+pub(crate) fn show_warnings(
+    warnings: &[Warning],
+    count_only: bool,
+    results: QueryResultWriter<'_>,
+) -> io::Result<()> {
+    if count_only {
+        let cols = &[Column::new(
+            "",
+            "@@session.warning_count",
+            ColumnType::MYSQL_TYPE_LONGLONG,
+        )
+        .with_flags(ColumnFlags::UNSIGNED_FLAG)];
+        let mut w = results.start(cols)?;
+        w.write_col(warning_count(warnings))?;
+        return w.finish();
+    }
+
+    let cols = &[
+        Column::new("", "Level", ColumnType::MYSQL_TYPE_VAR_STRING)
+            .with_flags(ColumnFlags::NOT_NULL_FLAG),
+        Column::new("", "Code", ColumnType::MYSQL_TYPE_LONG)
+            .with_flags(ColumnFlags::NOT_NULL_FLAG | ColumnFlags::UNSIGNED_FLAG),
+        Column::new("", "Message", ColumnType::MYSQL_TYPE_VAR_STRING)
+            .with_flags(ColumnFlags::NOT_NULL_FLAG),
+    ];
+    let mut w = results.start(cols)?;
+    for warning in warnings {
+        w.write_col(warning.level.as_str())?;
+        w.write_col(warning.code as u16 as u32)?;
+        w.write_col(&*warning.message)?;
+        w.end_row()?;
+    }
+    w.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_recognizes_show_warnings() {
+        assert_eq!(is_show_warnings(b"SHOW WARNINGS"), Some(false));
+        assert_eq!(is_show_warnings(b"  show  warnings ;"), Some(false));
+        assert_eq!(is_show_warnings(b"SHOW COUNT(*) WARNINGS"), Some(true));
+        assert_eq!(is_show_warnings(b"show count(*) warnings;"), Some(true));
+        assert_eq!(is_show_warnings(b"SHOW WARNINGS LIMIT 1"), None);
+        assert_eq!(is_show_warnings(b"SHOW ERRORS"), None);
+        assert_eq!(is_show_warnings(b"SELECT 1"), None);
+    }
+}
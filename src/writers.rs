@@ -1,27 +1,72 @@
-use crate::myc::constants::StatusFlags;
+use crate::myc::constants::{CapabilityFlags, StatusFlags};
 use crate::myc::io::WriteMysqlExt;
 use crate::packet::PacketWriter;
-use crate::{Column, ErrorKind};
+use crate::{Column, ErrorKind, SessionStateChange};
 use byteorder::{LittleEndian, WriteBytesExt};
 use std::io::{self, Write};
 
-pub(crate) fn write_eof_packet(w: &mut PacketWriter, s: StatusFlags) -> io::Result<()> {
-    w.write_all(&[0xFE, 0x00, 0x00])?;
+pub(crate) fn write_eof_packet(
+    w: &mut PacketWriter,
+    warnings: u16,
+    s: StatusFlags,
+) -> io::Result<()> {
+    w.write_u8(0xFE)?;
+    w.write_u16::<LittleEndian>(warnings)?;
     w.write_u16::<LittleEndian>(s.bits())?;
     w.end_packet()
 }
 
+/// The contents of an OK packet.
+pub(crate) struct OkPacket<'a> {
+    pub(crate) rows: u64,
+    pub(crate) last_insert_id: u64,
+    pub(crate) status: StatusFlags,
+    pub(crate) warnings: u16,
+    pub(crate) info: &'a [u8],
+    pub(crate) session_state_changes: &'a [SessionStateChange],
+}
+
+impl<'a> OkPacket<'a> {
+    pub(crate) fn new(rows: u64, last_insert_id: u64, status: StatusFlags) -> Self {
+        OkPacket {
+            rows,
+            last_insert_id,
+            status,
+            warnings: 0,
+            info: &[],
+            session_state_changes: &[],
+        }
+    }
+}
+
 pub(crate) fn write_ok_packet(
     w: &mut PacketWriter,
-    rows: u64,
-    last_insert_id: u64,
-    s: StatusFlags,
+    client_capabilities: CapabilityFlags,
+    ok: OkPacket<'_>,
 ) -> io::Result<()> {
+    let session_track = client_capabilities.contains(CapabilityFlags::CLIENT_SESSION_TRACK);
+    let mut status = ok.status;
+    if session_track && !ok.session_state_changes.is_empty() {
+        status.insert(StatusFlags::SERVER_SESSION_STATE_CHANGED);
+    }
+
     w.write_u8(0x00)?; // OK packet type
-    w.write_lenenc_int(rows)?;
-    w.write_lenenc_int(last_insert_id)?;
-    w.write_u16::<LittleEndian>(s.bits())?;
-    w.write_all(&[0x00, 0x00])?; // no warnings
+    w.write_lenenc_int(ok.rows)?;
+    w.write_lenenc_int(ok.last_insert_id)?;
+    w.write_u16::<LittleEndian>(status.bits())?;
+    w.write_u16::<LittleEndian>(ok.warnings)?;
+    if session_track {
+        w.write_lenenc_str(ok.info)?;
+        if status.contains(StatusFlags::SERVER_SESSION_STATE_CHANGED) {
+            let mut state = Vec::new();
+            for change in ok.session_state_changes {
+                change.write_to(&mut state)?;
+            }
+            w.write_lenenc_str(&state)?;
+        }
+    } else {
+        w.write_all(ok.info)?;
+    }
     w.end_packet()
 }
 
@@ -36,15 +81,17 @@ pub fn write_err(err: ErrorKind, msg: &[u8], w: &mut PacketWriter) -> io::Result
 
 use std::borrow::Borrow;
 
-pub(crate) fn write_prepare_ok<'a, PI, CI>(
+pub(crate) fn write_prepare_ok<'p, 'c, PI, CI>(
     id: u32,
     params: PI,
     columns: CI,
     w: &mut PacketWriter,
+    status: StatusFlags,
+    optional_metadata: Option<bool>,
 ) -> io::Result<()>
 where
-    PI: IntoIterator<Item = &'a Column>,
-    CI: IntoIterator<Item = &'a Column>,
+    PI: IntoIterator<Item = &'p Column>,
+    CI: IntoIterator<Item = &'c Column>,
     <PI as IntoIterator>::IntoIter: ExactSizeIterator,
     <CI as IntoIterator>::IntoIter: ExactSizeIterator,
 {
@@ -58,10 +105,16 @@ where
     w.write_u16::<LittleEndian>(pi.len() as u16)?;
     w.write_u8(0x00)?;
     w.write_u16::<LittleEndian>(0)?; // number of warnings
+    if let Some(full) = optional_metadata {
+        w.write_u8(full as u8)?; // metadata follows
+        if !full {
+            return w.end_packet();
+        }
+    }
     w.end_packet()?;
 
-    write_column_definitions(pi, w, false, true)?;
-    write_column_definitions(ci, w, false, true)
+    write_column_definitions(pi, w, false, true, status)?;
+    write_column_definitions(ci, w, false, true, status)
 }
 
 pub(crate) fn write_column_definitions<'a, I>(
@@ -69,6 +122,7 @@ pub(crate) fn write_column_definitions<'a, I>(
     w: &mut PacketWriter,
     is_comm_field_list_response: bool,
     only_eof_on_nonempty: bool,
+    status: StatusFlags,
 ) -> io::Result<()>
 where
     I: IntoIterator<Item = &'a Column>,
@@ -76,19 +130,18 @@ where
     let mut empty = true;
     for c in i {
         let c = c.borrow();
-        use crate::myc::constants::UTF8_GENERAL_CI;
         w.write_lenenc_str(b"def")?;
-        w.write_lenenc_str(b"")?;
+        w.write_lenenc_str(c.schema.as_bytes())?;
         w.write_lenenc_str(c.table.as_bytes())?;
-        w.write_lenenc_str(b"")?;
+        w.write_lenenc_str(c.org_table.as_bytes())?;
         w.write_lenenc_str(c.column.as_bytes())?;
-        w.write_lenenc_str(b"")?;
+        w.write_lenenc_str(c.org_column.as_bytes())?;
         w.write_lenenc_int(0xC)?;
-        w.write_u16::<LittleEndian>(UTF8_GENERAL_CI)?;
-        w.write_u32::<LittleEndian>(1024)?;
+        w.write_u16::<LittleEndian>(c.character_set)?;
+        w.write_u32::<LittleEndian>(c.column_length)?;
         w.write_u8(c.coltype as u8)?;
         w.write_u16::<LittleEndian>(c.colflags.bits())?;
-        w.write_all(&[0x00])?; // decimals
+        w.write_u8(c.decimals)?;
         w.write_all(&[0x00, 0x00])?; // unused
 
         if is_comm_field_list_response {
@@ -105,17 +158,184 @@ where
     if empty && only_eof_on_nonempty {
         Ok(())
     } else {
-        write_eof_packet(w, StatusFlags::empty())
+        write_eof_packet(w, 0, status)
     }
 }
 
-pub(crate) fn column_definitions<'a, I>(i: I, w: &mut PacketWriter) -> io::Result<()>
-where
-    I: IntoIterator<Item = &'a Column>,
-    <I as IntoIterator>::IntoIter: ExactSizeIterator,
-{
-    let i = i.into_iter();
-    w.write_lenenc_int(i.len() as u64)?;
+/// How the column definitions of a resultset should be sent to the client.
+pub(crate) enum ColumnMetadata<'a> {
+    /// Always send column definitions.
+    Always,
+    /// The client negotiated `CLIENT_OPTIONAL_RESULTSET_METADATA`, and set `resultset_metadata`
+    /// to `FULL` (`true`) or `NONE` (`false`).
+    Optional(bool),
+    /// The client negotiated `MARIADB_CLIENT_CACHE_METADATA`, and has cached these column
+    /// definitions for the statement being executed.
+    Cached(&'a mut Vec<Column>),
+}
+
+impl ColumnMetadata<'static> {
+    /// How column definitions should be sent for resultsets that are not tied to a prepared
+    /// statement, given the negotiated metadata options.
+    pub(crate) fn for_query(optional_metadata: Option<bool>, cache_metadata: bool) -> Self {
+        match optional_metadata {
+            Some(full) => ColumnMetadata::Optional(full),
+            // MariaDB clients expect to be told that metadata follows for every resultset
+            None if cache_metadata => ColumnMetadata::Optional(true),
+            None => ColumnMetadata::Always,
+        }
+    }
+}
+
+pub(crate) fn column_definitions(
+    columns: &[Column],
+    w: &mut PacketWriter,
+    status: StatusFlags,
+    metadata: &mut ColumnMetadata<'_>,
+) -> io::Result<()> {
+    w.write_lenenc_int(columns.len() as u64)?;
+    let send = match *metadata {
+        ColumnMetadata::Always => true,
+        ColumnMetadata::Optional(full) => full,
+        ColumnMetadata::Cached(ref mut cached) => {
+            let changed = cached[..] != *columns;
+            if changed {
+                **cached = columns.to_vec();
+            }
+            changed
+        }
+    };
+    if let ColumnMetadata::Optional(_) | ColumnMetadata::Cached(_) = *metadata {
+        w.write_u8(send as u8)?; // metadata follows
+    }
     w.end_packet()?;
-    write_column_definitions(i, w, false, false)
+
+    if send {
+        write_column_definitions(columns, w, false, false, status)
+    } else {
+        write_eof_packet(w, 0, status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myc::constants::ColumnType;
+    use crate::packet::PacketReader;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn packets<F>(f: F) -> Vec<Vec<u8>>
+    where
+        F: FnOnce(&mut PacketWriter) -> io::Result<()>,
+    {
+        let buf = Buffer::default();
+        let mut w = PacketWriter::new(buf.clone());
+        f(&mut w).unwrap();
+        w.flush().unwrap();
+
+        let data = RefCell::borrow(&buf.0).clone();
+        let mut r = PacketReader::new(io::Cursor::new(data));
+        let mut packets = Vec::new();
+        while let Some((_, p)) = r.next().unwrap() {
+            packets.push(p.to_vec());
+        }
+        packets
+    }
+
+    fn columns() -> Vec<Column> {
+        vec![
+            Column::new("t", "a", ColumnType::MYSQL_TYPE_LONGLONG),
+            Column::new("t", "b", ColumnType::MYSQL_TYPE_VAR_STRING),
+        ]
+    }
+
+    #[test]
+    fn it_sends_metadata() {
+        let cols = columns();
+        let p = packets(|w| {
+            column_definitions(&cols, w, StatusFlags::empty(), &mut ColumnMetadata::Always)
+        });
+        assert_eq!(p.len(), 4);
+        assert_eq!(p[0], &[0x02]);
+        assert_eq!(p[3][0], 0xFE);
+
+        let p = packets(|w| {
+            column_definitions(
+                &cols,
+                w,
+                StatusFlags::empty(),
+                &mut ColumnMetadata::Optional(true),
+            )
+        });
+        assert_eq!(p.len(), 4);
+        assert_eq!(p[0], &[0x02, 0x01]);
+    }
+
+    #[test]
+    fn it_skips_optional_metadata() {
+        let cols = columns();
+        let p = packets(|w| {
+            column_definitions(
+                &cols,
+                w,
+                StatusFlags::empty(),
+                &mut ColumnMetadata::Optional(false),
+            )
+        });
+        assert_eq!(p.len(), 2);
+        assert_eq!(p[0], &[0x02, 0x00]);
+        assert_eq!(p[1][0], 0xFE);
+
+        let p = packets(|w| {
+            write_prepare_ok(1, &cols[..1], &cols, w, StatusFlags::empty(), Some(false))
+        });
+        assert_eq!(p.len(), 1);
+        assert_eq!(
+            p[0],
+            &[0x00, 0x01, 0, 0, 0, 0x02, 0, 0x01, 0, 0, 0, 0, 0x00]
+        );
+    }
+
+    #[test]
+    fn it_skips_cached_metadata() {
+        let cols = columns();
+        let mut cached = Vec::new();
+        let p = packets(|w| {
+            column_definitions(
+                &cols,
+                w,
+                StatusFlags::empty(),
+                &mut ColumnMetadata::Cached(&mut cached),
+            )
+        });
+        assert_eq!(p.len(), 4);
+        assert_eq!(p[0], &[0x02, 0x01]);
+        assert_eq!(cached, cols);
+
+        let p = packets(|w| {
+            column_definitions(
+                &cols,
+                w,
+                StatusFlags::empty(),
+                &mut ColumnMetadata::Cached(&mut cached),
+            )
+        });
+        assert_eq!(p.len(), 2);
+        assert_eq!(p[0], &[0x02, 0x00]);
+        assert_eq!(p[1][0], 0xFE);
+    }
 }
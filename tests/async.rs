@@ -145,6 +145,7 @@ fn no_rows() {
         column: "a".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
     TestingShim::new(
         move |_, w| w.start(&cols[..])?.finish(),
@@ -250,6 +251,7 @@ fn empty_on_drop() {
         column: "a".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
     TestingShim::new(
         move |_, w| w.start(&cols[..]).map(|_| ()),
@@ -275,6 +277,7 @@ fn it_queries_nulls() {
                 column: "a".to_owned(),
                 coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
                 colflags: myc::constants::ColumnFlags::empty(),
+                ..Default::default()
             }];
             let mut w = w.start(cols)?;
             w.write_col(None::<i16>)?;
@@ -304,6 +307,7 @@ fn it_queries() {
                 column: "a".to_owned(),
                 coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
                 colflags: myc::constants::ColumnFlags::empty(),
+                ..Default::default()
             }];
             let mut w = w.start(cols)?;
             w.write_col(1024i16)?;
@@ -334,12 +338,14 @@ fn it_queries_many_rows() {
                     column: "a".to_owned(),
                     coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
                     colflags: myc::constants::ColumnFlags::empty(),
+                    ..Default::default()
                 },
                 Column {
                     table: String::new(),
                     column: "b".to_owned(),
                     coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
                     colflags: myc::constants::ColumnFlags::empty(),
+                    ..Default::default()
                 },
             ];
             let mut w = w.start(cols)?;
@@ -375,6 +381,7 @@ fn it_prepares() {
         column: "a".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
     let cols2 = cols.clone();
     let params = vec![Column {
@@ -382,6 +389,7 @@ fn it_prepares() {
         column: "c".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
 
     TestingShim::new(
@@ -427,42 +435,49 @@ fn insert_exec() {
             column: "username".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "email".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "pw".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "created".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_DATETIME,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "session".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "rss".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "mail".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
     ];
 
@@ -548,6 +563,7 @@ fn send_long() {
         column: "a".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
     let cols2 = cols.clone();
     let params = vec![Column {
@@ -555,6 +571,7 @@ fn send_long() {
         column: "c".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_BLOB,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
 
     TestingShim::new(
@@ -600,12 +617,14 @@ fn it_prepares_many() {
             column: "a".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "b".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
     ];
     let cols2 = cols.clone();
@@ -653,6 +672,7 @@ fn prepared_empty() {
         column: "a".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
     let cols2 = cols.clone();
     let params = vec![Column {
@@ -660,6 +680,7 @@ fn prepared_empty() {
         column: "c".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
 
     TestingShim::new(
@@ -689,6 +710,7 @@ fn prepared_no_params() {
         column: "a".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
     let cols2 = cols.clone();
     let params = vec![];
@@ -725,12 +747,14 @@ fn prepared_nulls() {
             column: "a".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "b".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
     ];
     let cols2 = cols.clone();
@@ -740,12 +764,14 @@ fn prepared_nulls() {
             column: "c".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "d".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
     ];
 
@@ -797,6 +823,7 @@ fn prepared_no_rows() {
         column: "a".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
     let cols2 = cols.clone();
     TestingShim::new(
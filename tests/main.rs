@@ -7,16 +7,20 @@ extern crate nom;
 use mysql::prelude::*;
 use std::io;
 use std::net;
+use std::sync::{Arc, Mutex};
 use std::thread;
 
 use msql_srv::{
-    Column, ErrorKind, InitWriter, MysqlIntermediary, MysqlShim, ParamParser, QueryResultWriter,
-    StatementMetaWriter,
+    Column, EmulatedStatements, ErrorKind, Hint, InitWriter, ManagedStatements, MysqlIntermediary,
+    MysqlShim, MysqlStatementShim, OwnedValue, ParamParser, QueryResultWriter, SessionStateChange,
+    StatementMetaWriter, StatementWriter, StatusFlags, ToMysqlValue, Warning, WarningLevel,
 };
 
 struct TestingShim<Q, P, E, I> {
     columns: Vec<Column>,
     params: Vec<Column>,
+    track_transactions: bool,
+    query_attributes: bool,
     on_q: Q,
     on_p: P,
     on_e: E,
@@ -55,6 +59,14 @@ where
     fn on_query(&mut self, query: &str, results: QueryResultWriter) -> io::Result<()> {
         (self.on_q)(query, results)
     }
+
+    fn track_transactions(&self) -> bool {
+        self.track_transactions
+    }
+
+    fn supports_query_attributes(&self) -> bool {
+        self.query_attributes
+    }
 }
 
 impl<Q, P, E, I> TestingShim<Q, P, E, I>
@@ -68,6 +80,8 @@ where
         TestingShim {
             columns: Vec::new(),
             params: Vec::new(),
+            track_transactions: false,
+            query_attributes: false,
             on_q,
             on_p,
             on_e,
@@ -85,9 +99,26 @@ where
         self
     }
 
+    fn with_transaction_tracking(mut self) -> Self {
+        self.track_transactions = true;
+        self
+    }
+
+    fn with_query_attributes(mut self) -> Self {
+        self.query_attributes = true;
+        self
+    }
+
     fn test<C>(self, c: C)
     where
         C: FnOnce(&mut mysql::Conn) -> (),
+    {
+        self.test_with_capabilities(mysql::consts::CapabilityFlags::empty(), c)
+    }
+
+    fn test_with_capabilities<C>(self, capabilities: mysql::consts::CapabilityFlags, c: C)
+    where
+        C: FnOnce(&mut mysql::Conn),
     {
         let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
         let port = listener.local_addr().unwrap().port();
@@ -96,11 +127,69 @@ where
             MysqlIntermediary::run_on_tcp(self, s)
         });
 
-        let mut db = mysql::Conn::new(&format!("mysql://127.0.0.1:{}", port)).unwrap();
+        let opts = mysql::Opts::from_url(&format!("mysql://127.0.0.1:{}", port)).unwrap();
+        let opts = mysql::OptsBuilder::from_opts(opts).additional_capabilities(capabilities);
+        let mut db = mysql::Conn::new(opts).unwrap();
         c(&mut db);
         drop(db);
         jh.join().unwrap().unwrap();
     }
+
+    /// Like `test`, but for exchanges the mysql client cannot make; `c` is handed the server's
+    /// handshake and must complete the connection phase itself.
+    fn test_raw<C>(self, c: C)
+    where
+        C: FnOnce(&mut net::TcpStream, Vec<u8>),
+    {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let jh = thread::spawn(move || {
+            let (s, _) = listener.accept().unwrap();
+            MysqlIntermediary::run_on_tcp(self, s)
+        });
+
+        let mut s = net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let handshake = recv_packet(&mut s);
+        c(&mut s, handshake);
+        drop(s);
+        jh.join().unwrap().unwrap();
+    }
+}
+
+fn send_packet(s: &mut net::TcpStream, seq: u8, payload: &[u8]) {
+    use std::io::Write;
+    let len = (payload.len() as u32).to_le_bytes();
+    s.write_all(&[len[0], len[1], len[2], seq]).unwrap();
+    s.write_all(payload).unwrap();
+}
+
+fn recv_packet(s: &mut net::TcpStream) -> Vec<u8> {
+    use std::io::Read;
+    let mut header = [0; 4];
+    s.read_exact(&mut header).unwrap();
+    let mut payload = vec![0; u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize];
+    s.read_exact(&mut payload).unwrap();
+    payload
+}
+
+/// Answer the server's handshake as a client with the given capabilities, and check that the
+/// connection is accepted.
+fn send_handshake(s: &mut net::TcpStream, capabilities: u32) {
+    let mut handshake = capabilities.to_le_bytes().to_vec();
+    handshake.extend(&[0x00, 0x00, 0x00, 0x01, 0x21]);
+    handshake.extend(&[0; 23]);
+    handshake.extend(b"jon\0");
+    send_packet(s, 1, &handshake);
+    assert_eq!(recv_packet(s)[0], 0x00);
+}
+
+/// The capabilities the server offers in its handshake.
+fn server_capabilities(handshake: &[u8]) -> u32 {
+    // protocol version, server version, connection id, first part of the salt
+    let at = 1 + handshake[1..].iter().position(|&b| b == 0).unwrap() + 1 + 4 + 9;
+    let low = u16::from_le_bytes([handshake[at], handshake[at + 1]]);
+    let high = u16::from_le_bytes([handshake[at + 5], handshake[at + 6]]);
+    u32::from(high) << 16 | u32::from(low)
 }
 
 #[test]
@@ -162,6 +251,22 @@ fn it_inits_on_use_query_ok() {
     });
 }
 
+#[test]
+fn it_inits_with_session_state() {
+    TestingShim::new(
+        |_, _| unreachable!(),
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |schema, mut writer| {
+            writer.add_session_state_change(SessionStateChange::Schema(schema.to_owned()));
+            writer.ok()
+        },
+    )
+    .test_with_capabilities(mysql::consts::CapabilityFlags::CLIENT_SESSION_TRACK, |db| {
+        assert!(db.select_db("test"))
+    });
+}
+
 #[test]
 fn it_pings() {
     TestingShim::new(
@@ -186,6 +291,394 @@ fn empty_response() {
     })
 }
 
+#[test]
+fn completed_with_info() {
+    TestingShim::new(
+        |_, mut w| {
+            w.set_info(&b"Rows matched: 1  Changed: 1  Warnings: 0"[..]);
+            w.completed(1, 0)
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        db.query_drop("UPDATE foo SET a = 1").unwrap();
+        assert_eq!(db.affected_rows(), 1);
+        assert_eq!(db.info_str(), "Rows matched: 1  Changed: 1  Warnings: 0");
+    })
+}
+
+#[test]
+fn completed_with_session_state() {
+    TestingShim::new(
+        |_, mut w| {
+            w.set_info(&b"autocommit changed"[..]);
+            w.add_session_state_change(SessionStateChange::SystemVariable {
+                name: "autocommit".to_owned(),
+                value: "OFF".to_owned(),
+            });
+            w.add_session_state_change(SessionStateChange::StateChange(true));
+            w.completed(0, 0)
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test_with_capabilities(mysql::consts::CapabilityFlags::CLIENT_SESSION_TRACK, |db| {
+        db.query_drop("SET autocommit = 0").unwrap();
+        assert_eq!(db.info_str(), "autocommit changed");
+    })
+}
+
+#[test]
+fn completed_with_warnings() {
+    TestingShim::new(
+        |_, mut w| {
+            w.add_warning(Warning::new(
+                WarningLevel::Warning,
+                ErrorKind::ER_TRUNCATED_WRONG_VALUE,
+                "Truncated incorrect DOUBLE value: 'x'",
+            ));
+            w.add_warning(Warning::new(
+                WarningLevel::Note,
+                ErrorKind::ER_BAD_TABLE_ERROR,
+                "Unknown table 'foo'",
+            ));
+            w.completed(1, 0)
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        db.query_drop("DELETE FROM foo WHERE a = 'x'").unwrap();
+        assert_eq!(db.warnings(), 2);
+
+        let warnings: Vec<(String, u32, String)> = db.query("SHOW WARNINGS").unwrap();
+        assert_eq!(
+            warnings,
+            vec![
+                (
+                    "Warning".to_owned(),
+                    ErrorKind::ER_TRUNCATED_WRONG_VALUE as u32,
+                    "Truncated incorrect DOUBLE value: 'x'".to_owned()
+                ),
+                (
+                    "Note".to_owned(),
+                    ErrorKind::ER_BAD_TABLE_ERROR as u32,
+                    "Unknown table 'foo'".to_owned()
+                ),
+            ]
+        );
+        assert_eq!(
+            db.query_first::<u64, _>("SHOW COUNT(*) WARNINGS").unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            db.query_first::<u64, _>("SELECT @@warning_count").unwrap(),
+            Some(2)
+        );
+    })
+}
+
+#[test]
+fn rows_with_warnings() {
+    TestingShim::new(
+        |_, w| {
+            let cols = &[Column {
+                table: String::new(),
+                column: "a".to_owned(),
+                coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+                colflags: myc::constants::ColumnFlags::empty(),
+                ..Default::default()
+            }];
+            let mut w = w.start(cols)?;
+            w.write_col(1024i16)?;
+            w.add_warning(Warning::new(
+                WarningLevel::Warning,
+                ErrorKind::ER_WARN_DATA_OUT_OF_RANGE,
+                "Out of range value for column 'a' at row 1",
+            ));
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        assert_eq!(db.query_iter("SELECT a FROM foo").unwrap().count(), 1);
+        assert_eq!(db.warnings(), 1);
+        assert_eq!(
+            db.query_first::<u64, _>("SHOW COUNT(*) WARNINGS").unwrap(),
+            Some(1)
+        );
+    })
+}
+
+#[test]
+fn error_shows_as_warning() {
+    TestingShim::new(
+        |_, w| {
+            w.error(
+                ErrorKind::ER_NO_SUCH_TABLE,
+                &b"Table 'foo' doesn't exist"[..],
+            )
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        assert!(db.query_drop("SELECT a FROM foo").is_err());
+        let warnings: Vec<(String, u32, String)> = db.query("show warnings;").unwrap();
+        assert_eq!(
+            warnings,
+            vec![(
+                "Error".to_owned(),
+                ErrorKind::ER_NO_SUCH_TABLE as u32,
+                "Table 'foo' doesn't exist".to_owned()
+            )]
+        );
+    })
+}
+
+#[test]
+fn it_reports_status_flags() {
+    TestingShim::new(
+        |q, mut w| {
+            assert_eq!(w.status_flags(), StatusFlags::SERVER_STATUS_AUTOCOMMIT);
+            if q == "SET sql_mode = 'NO_BACKSLASH_ESCAPES'" {
+                let status = w.status_flags() | StatusFlags::SERVER_STATUS_NO_BACKSLASH_ESCAPES;
+                w.set_status_flags(status);
+            }
+            w.completed(0, 0)
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        assert!(!db.no_backslash_escape());
+        db.query_drop("SET sql_mode = 'NO_BACKSLASH_ESCAPES'")
+            .unwrap();
+        assert!(db.no_backslash_escape());
+        assert!(db.ping());
+        assert!(db.no_backslash_escape());
+    })
+}
+
+#[test]
+fn it_tracks_transactions() {
+    let in_trans = StatusFlags::SERVER_STATUS_AUTOCOMMIT | StatusFlags::SERVER_STATUS_IN_TRANS;
+    let mut expected = vec![
+        StatusFlags::SERVER_STATUS_AUTOCOMMIT,
+        in_trans,
+        in_trans,
+        StatusFlags::SERVER_STATUS_AUTOCOMMIT,
+        StatusFlags::empty(),
+        StatusFlags::SERVER_STATUS_AUTOCOMMIT,
+    ]
+    .into_iter();
+    TestingShim::new(
+        move |_, w| {
+            assert_eq!(w.status_flags(), expected.next().unwrap());
+            w.completed(0, 0)
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .with_transaction_tracking()
+    .test(|db| {
+        db.query_drop("SELECT 1").unwrap();
+        db.query_drop("BEGIN").unwrap();
+        db.query_drop("INSERT INTO t VALUES (1)").unwrap();
+        db.query_drop("COMMIT").unwrap();
+        db.query_drop("SET autocommit = 0").unwrap();
+        db.query_drop("SET autocommit = 1").unwrap();
+    })
+}
+
+#[test]
+fn it_keeps_session_state_when_queries_fail() {
+    let cols = [Column::new(
+        "",
+        "name",
+        myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING,
+    )];
+    let in_trans = StatusFlags::SERVER_STATUS_AUTOCOMMIT | StatusFlags::SERVER_STATUS_IN_TRANS;
+    TestingShim::new(
+        move |q, w| match q {
+            "BEGIN" => w.completed(0, 0),
+            "COMMIT" | "SET NAMES latin1" | "SET time_zone = '+01:00'" => {
+                w.error(ErrorKind::ER_UNKNOWN_ERROR, &b"not now"[..])
+            }
+            _ => {
+                assert_eq!(w.status_flags(), in_trans);
+                assert_eq!(w.time_zone().local_minus_utc(), 0);
+                let mut w = w.start(&cols)?;
+                w.write_col("é")?;
+                w.finish()
+            }
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .with_transaction_tracking()
+    .test(|db| {
+        db.query_drop("BEGIN").unwrap();
+        assert!(db.query_drop("COMMIT").is_err());
+        assert!(db.query_drop("SET NAMES latin1").is_err());
+        assert!(db.query_drop("SET time_zone = '+01:00'").is_err());
+        let name: Vec<u8> = db.query_first("SELECT name").unwrap().unwrap();
+        assert_eq!(name, "é".as_bytes());
+    })
+}
+
+#[test]
+fn it_only_offers_query_attributes_on_request() {
+    TestingShim::new(
+        |q, w| {
+            assert_eq!(q, "SELECT 1");
+            assert!(w.query_attributes().is_empty());
+            w.completed(0, 0)
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test_raw(|s, handshake| {
+        assert_eq!(server_capabilities(&handshake) & 0x0800_0000, 0);
+        // attributes are not expected even if the client claims to send them
+        send_handshake(s, 0x0803_a685);
+        send_packet(s, 0, b"\x03SELECT 1");
+        assert_eq!(recv_packet(s)[0], 0x00);
+    });
+}
+
+#[test]
+fn it_restores_character_set_on_reset() {
+    let cols = [Column::new(
+        "",
+        "name",
+        myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING,
+    )];
+    TestingShim::new(
+        move |q, w| {
+            if q.starts_with("SET") {
+                return w.completed(0, 0);
+            }
+            let mut w = w.start(&cols)?;
+            w.write_col("é")?;
+            w.end_row()?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test_raw(|s, _| {
+        send_handshake(s, 0x0003_a685);
+        // the value of the only row of a one-column resultset
+        let select = |s: &mut net::TcpStream| {
+            send_packet(s, 0, b"\x03SELECT name");
+            assert_eq!(recv_packet(s), [1]);
+            recv_packet(s); // column definition
+            assert_eq!(recv_packet(s)[0], 0xfe);
+            let row = recv_packet(s);
+            assert_eq!(recv_packet(s)[0], 0xfe);
+            row[1..].to_vec()
+        };
+
+        send_packet(s, 0, b"\x03SET NAMES latin1");
+        assert_eq!(recv_packet(s)[0], 0x00);
+        assert_eq!(select(s), [0xe9]);
+
+        // COM_RESET_CONNECTION
+        send_packet(s, 0, &[0x1f]);
+        assert_eq!(recv_packet(s)[0], 0x00);
+        assert_eq!(select(s), "é".as_bytes());
+    });
+}
+
+#[test]
+fn it_receives_query_attributes() {
+    TestingShim::new(
+        |q, w| {
+            assert_eq!(q, "SELECT 1");
+            let attributes = w.query_attributes();
+            assert_eq!(attributes.len(), 1);
+            assert_eq!(attributes[0].name, "traceparent");
+            assert_eq!(Into::<&str>::into(attributes[0].value), "abc");
+            w.completed(0, 0)
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .with_query_attributes()
+    .test_raw(|s, handshake| {
+        assert_ne!(server_capabilities(&handshake) & 0x0800_0000, 0);
+        send_handshake(s, 0x0803_a685);
+
+        // COM_QUERY with one attribute
+        let mut query = vec![0x03, 0x01, 0x01, 0x00, 0x01, 0xfd, 0x00, 0x0b];
+        query.extend(b"traceparent");
+        query.push(0x03);
+        query.extend(b"abc");
+        query.extend(b"SELECT 1");
+        send_packet(s, 0, &query);
+        assert_eq!(recv_packet(s)[0], 0x00);
+    });
+}
+
+#[test]
+fn it_describes_columns() {
+    let cols = [
+        Column::new(
+            "o",
+            "total",
+            myc::constants::ColumnType::MYSQL_TYPE_NEWDECIMAL,
+        )
+        .with_schema("shop")
+        .with_org_table("orders")
+        .with_org_column("price")
+        .with_character_set(63)
+        .with_length(12)
+        .with_decimals(2),
+        Column::new("o", "id", myc::constants::ColumnType::MYSQL_TYPE_STRING).with_length(36),
+    ];
+    TestingShim::new(
+        move |_, w| w.start(&cols[..])?.finish(),
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        let res = db
+            .query_iter("SELECT o.price AS total, o.id FROM orders o")
+            .unwrap();
+        let cols = res.columns();
+        let cols = cols.as_ref();
+        assert_eq!(cols.len(), 2);
+        assert_eq!(cols[0].schema_str(), "shop");
+        assert_eq!(cols[0].table_str(), "o");
+        assert_eq!(cols[0].org_table_str(), "orders");
+        assert_eq!(cols[0].name_str(), "total");
+        assert_eq!(cols[0].org_name_str(), "price");
+        assert_eq!(cols[0].character_set(), 63);
+        assert_eq!(cols[0].column_length(), 12);
+        assert_eq!(cols[0].decimals(), 2);
+        assert_eq!(cols[1].schema_str(), "");
+        assert_eq!(cols[1].org_name_str(), "");
+        assert_eq!(cols[1].character_set(), 33);
+        assert_eq!(cols[1].column_length(), 36);
+        assert_eq!(cols[1].decimals(), 0);
+    })
+}
+
 #[test]
 fn no_rows() {
     let cols = [Column {
@@ -193,6 +686,7 @@ fn no_rows() {
         column: "a".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
     TestingShim::new(
         move |_, w| w.start(&cols[..])?.finish(),
@@ -263,6 +757,7 @@ fn empty_on_drop() {
         column: "a".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
     TestingShim::new(
         move |_, w| w.start(&cols[..]).map(|_| ()),
@@ -275,18 +770,28 @@ fn empty_on_drop() {
     })
 }
 
+#[cfg(feature = "rust_decimal")]
 #[test]
-fn it_queries_nulls() {
+fn it_fits_decimals_to_their_column() {
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    let cols = [Column::new(
+        "",
+        "price",
+        myc::constants::ColumnType::MYSQL_TYPE_NEWDECIMAL,
+    )
+    .with_length(7)
+    .with_decimals(2)];
     TestingShim::new(
-        |_, w| {
-            let cols = &[Column {
-                table: String::new(),
-                column: "a".to_owned(),
-                coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-                colflags: myc::constants::ColumnFlags::empty(),
-            }];
-            let mut w = w.start(cols)?;
-            w.write_col(None::<i16>)?;
+        move |_, w| {
+            let mut w = w.start(&cols)?;
+            assert!(w
+                .write_col(Decimal::from_str("1234567.5").unwrap())
+                .is_err());
+            assert!(w.write_col(Decimal::from_str("1.125").unwrap()).is_err());
+            w.write_col(Decimal::from_str("1.5").unwrap())?;
+            w.end_row()?;
             w.finish()
         },
         |_| unreachable!(),
@@ -294,28 +799,23 @@ fn it_queries_nulls() {
         |_, _| unreachable!(),
     )
     .test(|db| {
-        let row = db
-            .query_iter("SELECT a, b FROM foo")
-            .unwrap()
-            .next()
-            .unwrap()
-            .unwrap();
-        assert_eq!(row.as_ref(0), Some(&mysql::Value::NULL));
+        let price: Option<String> = db.query_first("SELECT price FROM items").unwrap();
+        assert_eq!(price.as_deref(), Some("1.50"));
     })
 }
 
 #[test]
-fn it_queries() {
+fn it_fits_wide_integers_to_decimal_columns() {
+    // DECIMAL(38)
+    let cols =
+        [Column::new("", "n", myc::constants::ColumnType::MYSQL_TYPE_NEWDECIMAL).with_length(39)];
     TestingShim::new(
-        |_, w| {
-            let cols = &[Column {
-                table: String::new(),
-                column: "a".to_owned(),
-                coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
-                colflags: myc::constants::ColumnFlags::empty(),
-            }];
-            let mut w = w.start(cols)?;
-            w.write_col(1024i16)?;
+        move |_, w| {
+            let mut w = w.start(&cols)?;
+            assert!(w.write_col(10i128.pow(38)).is_err());
+            assert!(w.write_col(10u128.pow(38)).is_err());
+            w.write_col(-(10i128.pow(38) - 1))?;
+            w.end_row()?;
             w.finish()
         },
         |_| unreachable!(),
@@ -323,13 +823,92 @@ fn it_queries() {
         |_, _| unreachable!(),
     )
     .test(|db| {
-        let row = db
-            .query_iter("SELECT a, b FROM foo")
-            .unwrap()
-            .next()
-            .unwrap()
-            .unwrap();
-        assert_eq!(row.get::<i16, _>(0), Some(1024));
+        let n: Option<String> = db.query_first("SELECT n FROM numbers").unwrap();
+        assert_eq!(n, Some(format!("-{}", 10i128.pow(38) - 1)));
+    })
+}
+
+#[test]
+fn it_sends_text_to_decimal_columns_unchanged() {
+    let cols = [Column::new(
+        "",
+        "pi",
+        myc::constants::ColumnType::MYSQL_TYPE_NEWDECIMAL,
+    )];
+    TestingShim::new(
+        move |_, w| {
+            let mut w = w.start(&cols)?;
+            w.write_col("3.14")?;
+            w.end_row()?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        let pi: Option<String> = db.query_first("SELECT pi FROM constants").unwrap();
+        assert_eq!(pi.as_deref(), Some("3.14"));
+    })
+}
+
+#[test]
+fn it_queries_nulls() {
+    TestingShim::new(
+        |_, w| {
+            let cols = &[Column {
+                table: String::new(),
+                column: "a".to_owned(),
+                coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+                colflags: myc::constants::ColumnFlags::empty(),
+                ..Default::default()
+            }];
+            let mut w = w.start(cols)?;
+            w.write_col(None::<i16>)?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        let row = db
+            .query_iter("SELECT a, b FROM foo")
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.as_ref(0), Some(&mysql::Value::NULL));
+    })
+}
+
+#[test]
+fn it_queries() {
+    TestingShim::new(
+        |_, w| {
+            let cols = &[Column {
+                table: String::new(),
+                column: "a".to_owned(),
+                coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
+                colflags: myc::constants::ColumnFlags::empty(),
+                ..Default::default()
+            }];
+            let mut w = w.start(cols)?;
+            w.write_col(1024i16)?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        let row = db
+            .query_iter("SELECT a, b FROM foo")
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.get::<i16, _>(0), Some(1024));
     })
 }
 
@@ -342,6 +921,7 @@ fn multi_result() {
                 column: "a".to_owned(),
                 coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
                 colflags: myc::constants::ColumnFlags::empty(),
+                ..Default::default()
             }];
             let mut row = w.start(cols)?;
             row.write_col(1024i16)?;
@@ -386,12 +966,14 @@ fn it_queries_many_rows() {
                     column: "a".to_owned(),
                     coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
                     colflags: myc::constants::ColumnFlags::empty(),
+                    ..Default::default()
                 },
                 Column {
                     table: String::new(),
                     column: "b".to_owned(),
                     coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
                     colflags: myc::constants::ColumnFlags::empty(),
+                    ..Default::default()
                 },
             ];
             let mut w = w.start(cols)?;
@@ -424,6 +1006,7 @@ fn it_prepares() {
         column: "a".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
     let cols2 = cols.clone();
     let params = vec![Column {
@@ -431,6 +1014,7 @@ fn it_prepares() {
         column: "c".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
 
     TestingShim::new(
@@ -476,42 +1060,49 @@ fn insert_exec() {
             column: "username".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "email".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "pw".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "created".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_DATETIME,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "session".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "rss".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "mail".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_VARCHAR,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
     ];
 
@@ -597,6 +1188,7 @@ fn send_long() {
         column: "a".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
     let cols2 = cols.clone();
     let params = vec![Column {
@@ -604,6 +1196,7 @@ fn send_long() {
         column: "c".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_BLOB,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
 
     TestingShim::new(
@@ -649,12 +1242,14 @@ fn it_prepares_many() {
             column: "a".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "b".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
     ];
     let cols2 = cols.clone();
@@ -699,6 +1294,7 @@ fn prepared_empty() {
         column: "a".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
     let cols2 = cols.clone();
     let params = vec![Column {
@@ -706,6 +1302,7 @@ fn prepared_empty() {
         column: "c".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
 
     TestingShim::new(
@@ -736,6 +1333,7 @@ fn prepared_no_params() {
         column: "a".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
     let cols2 = cols.clone();
     let params = vec![];
@@ -767,12 +1365,14 @@ fn prepared_nulls() {
             column: "a".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "b".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
     ];
     let cols2 = cols.clone();
@@ -782,12 +1382,14 @@ fn prepared_nulls() {
             column: "c".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
         Column {
             table: String::new(),
             column: "d".to_owned(),
             coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
             colflags: myc::constants::ColumnFlags::empty(),
+            ..Default::default()
         },
     ];
 
@@ -832,6 +1434,52 @@ fn prepared_nulls() {
     })
 }
 
+#[test]
+fn prepared_out_params() {
+    let params = vec![
+        Column::new("", "a", myc::constants::ColumnType::MYSQL_TYPE_LONGLONG),
+        Column::new("", "b", myc::constants::ColumnType::MYSQL_TYPE_LONGLONG),
+    ];
+    TestingShim::new(
+        |_, _| unreachable!(),
+        |_| 0,
+        move |_, params, w| {
+            let a: i64 = params[0].value.into();
+            let cols = [Column::new(
+                "",
+                "a",
+                myc::constants::ColumnType::MYSQL_TYPE_LONGLONG,
+            )];
+            let mut rw = w.start(&cols)?;
+            rw.write_col(a)?;
+            let w = rw.finish_one()?;
+
+            let out = [Column::new(
+                "",
+                "b",
+                myc::constants::ColumnType::MYSQL_TYPE_LONGLONG,
+            )];
+            let mut rw = w.start_out_params(&out)?;
+            rw.write_col(a * 2)?;
+            rw.finish()
+        },
+        |_, _| unreachable!(),
+    )
+    .with_params(params)
+    .test(|db| {
+        let mut result = db.exec_iter("CALL double(?, ?)", (21, 0)).unwrap();
+        let set = result.next_set().unwrap().unwrap();
+        let rows: Vec<i64> = set.map(|row| row.unwrap().get(0).unwrap()).collect();
+        assert_eq!(rows, vec![21]);
+        let set = result.next_set().unwrap().unwrap();
+        let rows: Vec<i64> = set.map(|row| row.unwrap().get(0).unwrap()).collect();
+        assert_eq!(rows, vec![42]);
+        let set = result.next_set().unwrap().unwrap();
+        assert_eq!(set.count(), 0);
+        assert!(result.next_set().is_none());
+    })
+}
+
 #[test]
 fn prepared_no_rows() {
     let cols = vec![Column {
@@ -839,6 +1487,7 @@ fn prepared_no_rows() {
         column: "a".to_owned(),
         coltype: myc::constants::ColumnType::MYSQL_TYPE_SHORT,
         colflags: myc::constants::ColumnFlags::empty(),
+        ..Default::default()
     }];
     let cols2 = cols.clone();
     TestingShim::new(
@@ -895,3 +1544,798 @@ fn really_long_query() {
         db.query_iter(long).unwrap();
     })
 }
+
+#[test]
+fn managed_statements() {
+    struct Backend {
+        closed: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MysqlShim for Backend {
+        type Error = io::Error;
+
+        fn on_query(&mut self, _: &str, _: QueryResultWriter) -> io::Result<()> {
+            unreachable!()
+        }
+    }
+
+    impl MysqlStatementShim for Backend {
+        type Statement = String;
+
+        fn on_prepare(&mut self, query: &str, info: StatementWriter<String>) -> io::Result<()> {
+            if query.starts_with("SELECT") {
+                info.reply(query.to_owned(), &[], &[])
+            } else {
+                info.error(ErrorKind::ER_PARSE_ERROR, &b"not a SELECT"[..])
+            }
+        }
+
+        fn on_execute(
+            &mut self,
+            stmt: &mut String,
+            _: ParamParser,
+            results: QueryResultWriter,
+        ) -> io::Result<()> {
+            let cols = [Column::new(
+                "",
+                "q",
+                myc::constants::ColumnType::MYSQL_TYPE_STRING,
+            )];
+            let mut rw = results.start(&cols)?;
+            rw.write_col(&**stmt)?;
+            rw.finish()
+        }
+
+        fn on_close(&mut self, stmt: String) {
+            self.closed.lock().unwrap().push(stmt);
+        }
+    }
+
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let closed = Arc::new(Mutex::new(Vec::new()));
+    let backend = ManagedStatements::new(Backend {
+        closed: closed.clone(),
+    });
+    let jh = thread::spawn(move || {
+        let (s, _) = listener.accept().unwrap();
+        MysqlIntermediary::run_on_tcp(backend, s)
+    });
+
+    let mut db = mysql::Conn::new(format!("mysql://127.0.0.1:{}", port)).unwrap();
+    let a = db.prep("SELECT a").unwrap();
+    let b = db.prep("SELECT b").unwrap();
+    assert_ne!(a.id(), b.id());
+    assert!(db.prep("DELETE FROM foo").is_err());
+    let got: Option<String> = db.exec_first(&b, ()).unwrap();
+    assert_eq!(got.as_deref(), Some("SELECT b"));
+    let got: Option<String> = db.exec_first(&a, ()).unwrap();
+    assert_eq!(got.as_deref(), Some("SELECT a"));
+    db.close(a).unwrap();
+    drop(b);
+    drop(db);
+    jh.join().unwrap().unwrap();
+    // the client closes its remaining statements when it disconnects
+    assert_eq!(
+        *closed.lock().unwrap(),
+        vec!["SELECT a".to_owned(), "SELECT b".to_owned()]
+    );
+}
+
+#[test]
+fn managed_statements_are_dropped_on_reset() {
+    struct Backend {
+        closed: Arc<Mutex<Vec<String>>>,
+        resets: Arc<Mutex<usize>>,
+    }
+
+    impl MysqlShim for Backend {
+        type Error = io::Error;
+
+        fn on_query(&mut self, _: &str, _: QueryResultWriter) -> io::Result<()> {
+            unreachable!()
+        }
+
+        fn on_reset(&mut self) {
+            *self.resets.lock().unwrap() += 1;
+        }
+    }
+
+    impl MysqlStatementShim for Backend {
+        type Statement = String;
+
+        fn on_prepare(&mut self, query: &str, info: StatementWriter<String>) -> io::Result<()> {
+            info.reply(query.to_owned(), &[], &[])
+        }
+
+        fn on_execute(
+            &mut self,
+            _: &mut String,
+            _: ParamParser,
+            _: QueryResultWriter,
+        ) -> io::Result<()> {
+            unreachable!()
+        }
+
+        fn on_close(&mut self, stmt: String) {
+            self.closed.lock().unwrap().push(stmt);
+        }
+    }
+
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let closed = Arc::new(Mutex::new(Vec::new()));
+    let resets = Arc::new(Mutex::new(0));
+    let backend = ManagedStatements::new(Backend {
+        closed: closed.clone(),
+        resets: resets.clone(),
+    });
+    let jh = thread::spawn(move || {
+        let (s, _) = listener.accept().unwrap();
+        MysqlIntermediary::run_on_tcp(backend, s)
+    });
+
+    // the mysql client only sends COM_RESET_CONNECTION to servers newer than the one we claim to
+    // be, so speak the protocol by hand
+    let mut s = net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    recv_packet(&mut s);
+    send_handshake(&mut s, 0x0003_a685);
+
+    send_packet(&mut s, 0, b"\x16SELECT a");
+    let prepared = recv_packet(&mut s);
+    assert_eq!(prepared[0], 0x00);
+    let id = [prepared[1], prepared[2], prepared[3], prepared[4]];
+
+    // COM_STMT_RESET
+    send_packet(&mut s, 0, &[0x1a, id[0], id[1], id[2], id[3]]);
+    assert_eq!(recv_packet(&mut s)[0], 0x00);
+    assert!(closed.lock().unwrap().is_empty());
+
+    // COM_RESET_CONNECTION
+    send_packet(&mut s, 0, &[0x1f]);
+    assert_eq!(recv_packet(&mut s)[0], 0x00);
+    assert_eq!(*closed.lock().unwrap(), vec!["SELECT a".to_owned()]);
+    assert_eq!(*resets.lock().unwrap(), 1);
+
+    // the statement is gone
+    send_packet(&mut s, 0, &[0x1a, id[0], id[1], id[2], id[3]]);
+    let err = recv_packet(&mut s);
+    assert_eq!(err[0], 0xff);
+    assert_eq!(u16::from_le_bytes([err[1], err[2]]), 1243);
+
+    send_packet(&mut s, 0, &[0x01]);
+    jh.join().unwrap().unwrap();
+}
+
+#[test]
+fn statements_are_unsupported_by_default() {
+    struct Backend;
+
+    impl MysqlShim for Backend {
+        type Error = io::Error;
+
+        fn on_query(&mut self, _: &str, results: QueryResultWriter) -> io::Result<()> {
+            results.completed(0, 0)
+        }
+    }
+
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let jh = thread::spawn(move || {
+        let (s, _) = listener.accept().unwrap();
+        MysqlIntermediary::run_on_tcp(Backend, s)
+    });
+
+    let mut db = mysql::Conn::new(format!("mysql://127.0.0.1:{}", port)).unwrap();
+    if let mysql::Error::MySqlError(e) = db.prep("SELECT 1").unwrap_err() {
+        assert_eq!(e.code, ErrorKind::ER_UNSUPPORTED_PS as u16);
+    } else {
+        unreachable!();
+    }
+    db.query_drop("SELECT 1").unwrap();
+    drop(db);
+    jh.join().unwrap().unwrap();
+}
+
+#[test]
+fn emulated_statements() {
+    struct Backend;
+
+    impl MysqlShim for Backend {
+        type Error = io::Error;
+
+        fn on_query(&mut self, query: &str, results: QueryResultWriter) -> io::Result<()> {
+            let cols = [Column::new(
+                "",
+                "q",
+                myc::constants::ColumnType::MYSQL_TYPE_STRING,
+            )];
+            let mut rw = results.start(&cols)?;
+            rw.write_col(query)?;
+            rw.finish()
+        }
+    }
+
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let backend = EmulatedStatements::new(Backend);
+    let jh = thread::spawn(move || {
+        let (s, _) = listener.accept().unwrap();
+        MysqlIntermediary::run_on_tcp(backend, s)
+    });
+
+    let mut db = mysql::Conn::new(format!("mysql://127.0.0.1:{}", port)).unwrap();
+    let got: Option<String> = db
+        .exec_first(
+            "SELECT ?, '?', ? /* ? */, ?, ?",
+            (42, "it's", mysql::Value::NULL, 1.5),
+        )
+        .unwrap();
+    assert_eq!(
+        got.as_deref(),
+        Some(r"SELECT 42, '?', 'it\'s' /* ? */, NULL, 1.5")
+    );
+    let got: Option<String> = db
+        .exec_first(
+            "SELECT ?",
+            (chrono::NaiveDate::from_ymd_opt(2020, 2, 29)
+                .unwrap()
+                .and_hms_opt(23, 59, 58)
+                .unwrap(),),
+        )
+        .unwrap();
+    assert_eq!(got.as_deref(), Some("SELECT '2020-02-29 23:59:58'"));
+    let got: Option<String> = db.query_first("SELECT 1").unwrap();
+    assert_eq!(got.as_deref(), Some("SELECT 1"));
+    drop(db);
+    jh.join().unwrap().unwrap();
+}
+
+#[test]
+fn it_transcodes_latin1() {
+    let cols = [
+        Column::new("", "q", myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING),
+        Column::new("", "s", myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING),
+        Column::new("", "b", myc::constants::ColumnType::MYSQL_TYPE_BLOB).with_character_set(63),
+    ];
+    TestingShim::new(
+        move |q, w| {
+            if q.starts_with("SET") {
+                return w.completed(0, 0);
+            }
+            assert_eq!(q, "SELECT 'cafÃ©'");
+            let mut w = w.start(&cols)?;
+            w.write_col(q)?;
+            w.write_col("café")?;
+            w.write_col("café")?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        db.query_drop("SET NAMES latin1").unwrap();
+        let mut result = db.query_iter("SELECT 'café'").unwrap();
+        assert_eq!(result.columns().as_ref()[0].character_set(), 8);
+        let row = result.next().unwrap().unwrap();
+        // the client decodes neither the query nor the results as latin1, so the query makes a
+        // round trip unchanged
+        assert_eq!(
+            row.get::<Vec<u8>, _>(0).unwrap(),
+            "SELECT 'café'".as_bytes()
+        );
+        assert_eq!(row.get::<Vec<u8>, _>(1).unwrap(), b"caf\xe9");
+        // text in binary columns is sent as it is
+        assert_eq!(result.columns().as_ref()[2].character_set(), 63);
+        assert_eq!(row.get::<Vec<u8>, _>(2).unwrap(), "café".as_bytes());
+    });
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn it_serializes_rows() {
+    #[derive(serde::Serialize)]
+    struct User {
+        id: u32,
+        name: String,
+        email: Option<String>,
+        admin: bool,
+    }
+
+    fn users(id: u32) -> Vec<User> {
+        vec![
+            User {
+                id,
+                name: "jon".to_owned(),
+                email: None,
+                admin: true,
+            },
+            User {
+                id: id + 1,
+                name: "ana".to_owned(),
+                email: Some("ana@example.com".to_owned()),
+                admin: false,
+            },
+        ]
+    }
+
+    TestingShim::new(
+        |_, w| w.serialize_rows(users(1)),
+        |_| 0,
+        |_, params, w| {
+            let id = params[0].value.try_get::<u32>()?;
+            w.serialize_rows(users(id))
+        },
+        |_, _| unreachable!(),
+    )
+    .with_params(vec![Column::new(
+        "",
+        "id",
+        myc::constants::ColumnType::MYSQL_TYPE_LONG,
+    )])
+    .test(|db| {
+        let expected = vec![
+            (1, "jon".to_owned(), None, true),
+            (
+                2,
+                "ana".to_owned(),
+                Some("ana@example.com".to_owned()),
+                false,
+            ),
+        ];
+        let mut result = db.query_iter("SELECT * FROM users").unwrap();
+        let columns = result.columns();
+        let columns = columns.as_ref();
+        assert_eq!(columns[0].name_str(), "id");
+        assert_eq!(
+            columns[0].column_type(),
+            mysql::consts::ColumnType::MYSQL_TYPE_LONG
+        );
+        assert!(columns[0]
+            .flags()
+            .contains(mysql::consts::ColumnFlags::UNSIGNED_FLAG));
+        assert!(!columns[2]
+            .flags()
+            .contains(mysql::consts::ColumnFlags::NOT_NULL_FLAG));
+        let rows: Vec<(u32, String, Option<String>, bool)> = result
+            .by_ref()
+            .map(|row| mysql::from_row(row.unwrap()))
+            .collect();
+        assert_eq!(rows, expected);
+        drop(result);
+
+        let rows: Vec<(u32, String, Option<String>, bool)> =
+            db.exec("SELECT * FROM users WHERE id >= ?", (1,)).unwrap();
+        assert_eq!(rows, expected);
+    });
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn it_serializes_rows_to_given_columns() {
+    #[derive(serde::Serialize)]
+    struct Event {
+        id: u32,
+        note: String,
+        at: String,
+    }
+
+    fn events() -> Vec<Event> {
+        vec![Event {
+            id: 7,
+            note: "café".to_owned(),
+            at: "2020-02-29T23:00:00".to_owned(),
+        }]
+    }
+
+    let columns = || {
+        vec![
+            Column::new("", "id", myc::constants::ColumnType::MYSQL_TYPE_LONGLONG),
+            // TEXT
+            Column::new("", "note", myc::constants::ColumnType::MYSQL_TYPE_BLOB),
+            Column::new("", "at", myc::constants::ColumnType::MYSQL_TYPE_DATETIME),
+        ]
+    };
+    TestingShim::new(
+        move |_, w| w.serialize_rows_with(columns(), events()),
+        |_| 0,
+        move |_, _, w| w.serialize_rows_with(columns(), events()),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        let expected = vec![(
+            7u64,
+            "café".to_owned(),
+            chrono::NaiveDate::from_ymd_opt(2020, 2, 29)
+                .unwrap()
+                .and_hms_opt(23, 0, 0)
+                .unwrap(),
+        )];
+        let rows: Vec<(u64, String, chrono::NaiveDateTime)> =
+            db.query("SELECT * FROM events").unwrap();
+        assert_eq!(rows, expected);
+        let rows: Vec<(u64, String, chrono::NaiveDateTime)> =
+            db.exec("SELECT * FROM events", ()).unwrap();
+        assert_eq!(rows, expected);
+    });
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn it_serializes_columns_without_rows() {
+    #[derive(serde::Serialize)]
+    struct User {
+        id: u32,
+        email: Option<String>,
+    }
+
+    TestingShim::new(
+        |q, w| {
+            if q.contains("email") {
+                w.serialize_rows(vec![User { id: 1, email: None }])
+            } else if q.contains("nobody") {
+                w.serialize_rows(Vec::<User>::new())
+            } else {
+                let columns = vec![
+                    Column::new("", "id", myc::constants::ColumnType::MYSQL_TYPE_LONG),
+                    Column::new(
+                        "",
+                        "email",
+                        myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING,
+                    ),
+                ];
+                w.serialize_rows_with(columns, Vec::<User>::new())
+            }
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        let result = db.query_iter("SELECT * FROM users").unwrap();
+        let columns = result.columns();
+        let columns = columns.as_ref();
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns[0].name_str(), "id");
+        assert_eq!(
+            columns[0].column_type(),
+            mysql::consts::ColumnType::MYSQL_TYPE_LONG
+        );
+        drop(result);
+
+        // without rows or columns, there is no resultset
+        let result = db.query_iter("SELECT * FROM nobody").unwrap();
+        assert!(result.columns().as_ref().is_empty());
+        assert_eq!(result.count(), 0);
+
+        // a column without any values is sent as strings
+        let mut result = db
+            .query_iter("SELECT * FROM users WHERE email IS NULL")
+            .unwrap();
+        assert_eq!(
+            result.columns().as_ref()[1].column_type(),
+            mysql::consts::ColumnType::MYSQL_TYPE_VAR_STRING
+        );
+        let rows: Vec<(u32, Option<String>)> = result
+            .by_ref()
+            .map(|row| mysql::from_row(row.unwrap()))
+            .collect();
+        assert_eq!(rows, vec![(1, None)]);
+    });
+}
+
+#[test]
+fn it_converts_timestamps_to_session_time_zone() {
+    use chrono::TimeZone;
+    let cols = [
+        Column::new("", "ts", myc::constants::ColumnType::MYSQL_TYPE_TIMESTAMP),
+        Column::new("", "dt", myc::constants::ColumnType::MYSQL_TYPE_DATETIME),
+    ];
+    TestingShim::new(
+        move |q, w| {
+            if q.starts_with("SET") {
+                return w.completed(0, 0);
+            }
+            assert_eq!(w.time_zone().local_minus_utc(), 2 * 3600);
+            let instant = chrono::Utc.with_ymd_and_hms(2020, 2, 29, 23, 0, 0).unwrap();
+            let mut w = w.start(&cols)?;
+            w.write_col(instant)?;
+            w.write_col(instant)?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        db.query_drop("SET time_zone = '+02:00'").unwrap();
+        // named time zones are passed on, and leave the tracked time zone as it was
+        db.query_drop("SET time_zone = 'Europe/Oslo'").unwrap();
+        let row: (String, String) = db.query_first("SELECT ts, dt FROM t").unwrap().unwrap();
+        assert_eq!(row.0, "2020-03-01 01:00:00");
+        assert_eq!(row.1, "2020-02-29 23:00:00");
+    });
+}
+
+#[test]
+fn it_decodes_datetime_params_in_session_time_zone() {
+    use chrono::TimeZone;
+    let params = vec![Column::new(
+        "",
+        "dt",
+        myc::constants::ColumnType::MYSQL_TYPE_DATETIME,
+    )];
+    TestingShim::new(
+        |_, w| w.completed(0, 0),
+        |_| 0,
+        move |_, params, w| {
+            let dt: chrono::DateTime<chrono::Utc> = params[0].try_get()?;
+            assert_eq!(
+                dt,
+                chrono::Utc.with_ymd_and_hms(2020, 2, 29, 23, 0, 0).unwrap()
+            );
+            w.completed(0, 0)
+        },
+        |_, _| unreachable!(),
+    )
+    .with_params(params)
+    .test(|db| {
+        db.query_drop("SET time_zone = '+02:00'").unwrap();
+        let local = chrono::NaiveDate::from_ymd_opt(2020, 3, 1)
+            .unwrap()
+            .and_hms_opt(1, 0, 0)
+            .unwrap();
+        db.exec_drop("foo", (local,)).unwrap();
+    });
+}
+
+#[test]
+fn it_sends_zero_dates() {
+    let cols = [
+        Column::new("", "d", myc::constants::ColumnType::MYSQL_TYPE_DATE),
+        Column::new("", "dt", myc::constants::ColumnType::MYSQL_TYPE_DATETIME),
+    ];
+    TestingShim::new(
+        move |_, w| {
+            let mut w = w.start(&cols)?;
+            w.write_col(msql_srv::MysqlDate::ZERO)?;
+            w.write_col(msql_srv::MysqlDateTime {
+                day: 0,
+                hour: 12,
+                ..msql_srv::MysqlDateTime::from(msql_srv::MysqlDate {
+                    year: 2020,
+                    month: 2,
+                    day: 0,
+                })
+            })?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        let row: (String, String) = db.query_first("SELECT d, dt FROM t").unwrap().unwrap();
+        assert_eq!(row.0, "0000-00-00");
+        assert_eq!(row.1, "2020-02-00 12:00:00");
+    });
+}
+
+#[test]
+fn it_checks_enum_and_set_members() {
+    let cols = [
+        Column::new("", "size", myc::constants::ColumnType::MYSQL_TYPE_STRING)
+            .with_enum(&["s", "m", "l"]),
+        Column::new("", "days", myc::constants::ColumnType::MYSQL_TYPE_STRING)
+            .with_set(&["mon", "tue", "wed"]),
+    ];
+    TestingShim::new(
+        move |_, w| {
+            let mut w = w.start(&cols)?;
+            assert!(w.write_col("xl").is_err());
+            w.write_col("m")?;
+            let days: std::collections::HashSet<_> = ["wed", "mon"].iter().copied().collect();
+            w.write_col(days)?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        let row: (String, String) = db.query_first("SELECT size, days FROM t").unwrap().unwrap();
+        assert_eq!(row.0, "m");
+        assert_eq!(row.1, "mon,wed");
+    });
+}
+
+#[test]
+fn it_follows_the_hints_of_wrapped_values() {
+    struct Size(String);
+
+    impl ToMysqlValue for Size {
+        fn to_mysql_text<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+            self.0.to_mysql_text(w)
+        }
+        fn to_mysql_bin<W: io::Write>(&self, w: &mut W, c: &Column) -> io::Result<()> {
+            self.0.to_mysql_bin(w, c)
+        }
+        fn hint(&self) -> Hint<'_> {
+            self.0.hint()
+        }
+    }
+
+    let cols = [
+        Column::new("", "size", myc::constants::ColumnType::MYSQL_TYPE_STRING)
+            .with_enum(&["s", "m", "l"]),
+    ];
+    TestingShim::new(
+        move |_, w| {
+            let mut w = w.start(&cols)?;
+            assert!(w.write_col(Size("xl".to_owned())).is_err());
+            w.write_col(Size("m".to_owned()))?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        let size: Option<String> = db.query_first("SELECT size FROM t").unwrap();
+        assert_eq!(size.as_deref(), Some("m"));
+    });
+}
+
+#[test]
+fn it_sends_bools_to_bit_columns() {
+    let cols = [
+        Column::new("", "b", myc::constants::ColumnType::MYSQL_TYPE_BIT).with_length(1),
+        Column::new("", "t", myc::constants::ColumnType::MYSQL_TYPE_TINY),
+    ];
+    TestingShim::new(
+        move |_, w| {
+            let mut w = w.start(&cols)?;
+            w.write_row(&[true, true])?;
+            w.write_row(&[false, false])?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        let rows: Vec<(Vec<u8>, Vec<u8>)> = db.query("SELECT b, t FROM t").unwrap();
+        assert_eq!(rows, [(vec![1], b"1".to_vec()), (vec![0], b"0".to_vec())]);
+    });
+}
+
+#[cfg(feature = "geo-types")]
+#[test]
+fn it_sends_geometries() {
+    use geo_types::Point;
+    let cols = [Column::new(
+        "",
+        "location",
+        myc::constants::ColumnType::MYSQL_TYPE_GEOMETRY,
+    )];
+    TestingShim::new(
+        move |_, w| {
+            let mut w = w.start(&cols)?;
+            w.write_col(msql_srv::Spatial {
+                srid: 4326,
+                geometry: Point::new(10.75, 59.91).into(),
+            })?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(|db| {
+        let location: Vec<u8> = db.query_first("SELECT location FROM t").unwrap().unwrap();
+        assert_eq!(location.len(), 25);
+        assert_eq!(&location[..5], [0xe6, 0x10, 0, 0, 1]);
+        assert_eq!(location[17..], 59.91f64.to_le_bytes());
+    });
+}
+
+#[cfg(feature = "uuid")]
+#[test]
+fn it_sends_uuids_by_column_collation() {
+    let id = uuid::Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap();
+    let cols = [
+        Column::new("", "text", myc::constants::ColumnType::MYSQL_TYPE_STRING),
+        Column::new("", "bin", myc::constants::ColumnType::MYSQL_TYPE_STRING)
+            .with_character_set(msql_srv::CharacterSet::Binary.collation()),
+    ];
+    TestingShim::new(
+        move |_, w| {
+            let mut w = w.start(&cols)?;
+            w.write_col(id)?;
+            w.write_col(id)?;
+            w.finish()
+        },
+        |_| unreachable!(),
+        |_, _, _| unreachable!(),
+        |_, _| unreachable!(),
+    )
+    .test(move |db| {
+        let row: (String, Vec<u8>) = db.query_first("SELECT text, bin FROM t").unwrap().unwrap();
+        assert_eq!(row.0, id.to_string());
+        assert_eq!(row.1, id.as_bytes());
+    });
+}
+
+#[test]
+fn it_coerces_values_when_lenient() {
+    let cols = vec![
+        Column::new("", "n", myc::constants::ColumnType::MYSQL_TYPE_LONG),
+        Column::new("", "f", myc::constants::ColumnType::MYSQL_TYPE_DOUBLE),
+        Column::new("", "d", myc::constants::ColumnType::MYSQL_TYPE_DATE),
+        Column::new("", "s", myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING),
+    ];
+    let cols2 = cols.clone();
+
+    TestingShim::new(
+        |_, _| unreachable!(),
+        |_| 0,
+        move |_, _, w| {
+            let mut w = w.start(&cols)?;
+            assert!(w.write_col("42").is_err());
+            w.set_lenient(true);
+            assert!(w.write_col("42.5").is_err());
+            w.write_col("42")?;
+            w.write_col(7i64)?;
+            w.write_col("2020-02-29")?;
+            w.write_col(1.5f64)?;
+            w.finish()
+        },
+        |_, _| unreachable!(),
+    )
+    .with_params(Vec::new())
+    .with_columns(cols2)
+    .test(|db| {
+        let row: (i32, f64, chrono::NaiveDate, String) = db.exec_first("foo", ()).unwrap().unwrap();
+        assert_eq!(row.0, 42);
+        assert_eq!(row.1, 7.0);
+        assert_eq!(row.2, chrono::NaiveDate::from_ymd_opt(2020, 2, 29).unwrap());
+        assert_eq!(row.3, "1.5");
+    })
+}
+
+#[test]
+fn it_replays_owned_params() {
+    let cols = vec![
+        Column::new("", "n", myc::constants::ColumnType::MYSQL_TYPE_LONGLONG),
+        Column::new("", "s", myc::constants::ColumnType::MYSQL_TYPE_VAR_STRING),
+        Column::new("", "d", myc::constants::ColumnType::MYSQL_TYPE_DATE),
+    ];
+    let cols2 = cols.clone();
+
+    TestingShim::new(
+        |_, _| unreachable!(),
+        |_| 0,
+        move |_, params, w| {
+            let params: Vec<OwnedValue> = params
+                .into_iter()
+                .map(|p| OwnedValue::from(p.value))
+                .collect();
+            assert_eq!(params[1], OwnedValue::Bytes(b"hello".to_vec()));
+            let mut w = w.start(&cols)?;
+            w.write_row(&params)?;
+            w.finish()
+        },
+        |_, _| unreachable!(),
+    )
+    .with_params(cols2.clone())
+    .with_columns(cols2)
+    .test(|db| {
+        let date = chrono::NaiveDate::from_ymd_opt(2020, 2, 29).unwrap();
+        let row: (i64, String, chrono::NaiveDate) = db
+            .exec_first("foo", (-3i64, "hello", date))
+            .unwrap()
+            .unwrap();
+        assert_eq!(row, (-3, "hello".to_owned(), date));
+    })
+}